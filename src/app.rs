@@ -1,17 +1,195 @@
 use eframe::egui;
 use crate::types::*;
 use crate::ui::*;
-use crate::backend::{AccountManager, AccountEvent};
+use crate::backend::{AccountManager, AccountCommand, AccountEvent, OutboxEntry, ParsedMessage, downloads, unix_now};
+use crate::backend::error_log;
+use crate::backend::html::{self, HtmlNode};
+use crate::backend::remote_content::{self, RemoteImage};
+use crate::ui::html_view::{self, HtmlImages, MissingImages};
 use crate::input::{KeyboardHandler, KeyAction, VimState, VimCommand};
 use tokio::sync::mpsc;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc as std_mpsc;
+use std::time::{Duration, Instant};
+
+/// Number of most recent messages fetched per folder
+const EMAIL_FETCH_LIMIT: usize = 50;
 
 #[derive(Debug, Clone)]
 pub struct DeleteConfirmation {
-    #[allow(dead_code)] // Will be used for actual email deletion
+    pub folder: String,
+    pub uid_validity: u32,
     pub email_id: usize,
     pub email_subject: String,
 }
 
+/// What to do with an attachment once it has downloaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttachmentTarget {
+    Save,
+    Open,
+    /// An image the HTML part shows inline
+    Inline,
+}
+
+/// Requests in flight on the backend worker, used for the panels' loading states
+#[derive(Debug, Default)]
+pub struct LoadingState {
+    pub folders: HashSet<String>,              // account email
+    pub emails: HashSet<(String, String)>,     // account email, folder
+    pub bodies: HashSet<(String, usize)>,      // account email, email id
+    pub attachments: HashMap<(String, usize, String), AttachmentTarget>, // account email, email id, part id
+}
+
+impl LoadingState {
+    fn clear_account(&mut self, account_email: &str) {
+        self.folders.remove(account_email);
+        self.emails.retain(|(account, _)| account != account_email);
+        self.bodies.retain(|(account, _)| account != account_email);
+        self.attachments.retain(|(account, ..), _| account != account_email);
+    }
+}
+
+/// Where an image of an HTML part comes from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ImageSource {
+    Inline(String), // Content-ID
+    Remote(String), // URL
+}
+
+/// The open message's HTML part, sanitized once rather than every frame
+struct RenderedHtml {
+    key: (String, usize), // account email, email id
+    nodes: Vec<HtmlNode>,
+    sender: Option<String>,
+    remote_images: Vec<RemoteImage>,
+    inline_textures: HashMap<String, egui::TextureHandle>, // by Content-ID
+    remote_textures: HashMap<String, egui::TextureHandle>, // by URL
+    /// Downloaded images waiting to be turned into textures
+    pending_images: Vec<(ImageSource, Vec<u8>)>,
+    /// Images already asked for, so a failed image is not retried every frame
+    requested_images: HashSet<ImageSource>,
+    /// The user chose to load remote images of this message
+    load_remote_once: bool,
+    /// Remote images may load, once or because the sender is allowed
+    load_remote: bool,
+}
+
+/// The open folder's conversations, rebuilt when its messages change
+struct ThreadCache {
+    /// UIDs of the list the threads index into, in list order
+    ids: Vec<usize>,
+    /// Built with the server's THREAD response at hand, used if it covered
+    /// the list
+    with_server: bool,
+    threads: Vec<EmailThread>,
+}
+
+/// How the open folder and message are shown, kept between frames so it
+/// is not recomputed every frame
+#[derive(Default)]
+struct DisplayState {
+    rendered_html: Option<RenderedHtml>,
+    /// Show the plain-text alternative of HTML mail
+    prefer_plain_text: bool,
+    /// Present in conversation view
+    threads: Option<ThreadCache>,
+    /// The open folder's THREAD response, if the server sent one
+    server_threads: Option<((String, String, u32), Vec<ThreadNode>)>, // account email, folder, UIDVALIDITY
+}
+
+/// Borrowed data for one frame of the panels
+struct PanelView<'a> {
+    folders: &'a [Folder],
+    emails: &'a [Email],
+    current_email: Option<&'a Email>,
+    folders_loading: bool,
+    emails_loading: bool,
+    body_loading: bool,
+    /// Part ids of the current message's attachments being downloaded
+    attachments_loading: HashSet<String>,
+    /// Ids of messages whose bodies are being fetched
+    bodies_loading: HashSet<usize>,
+    html: Option<&'a RenderedHtml>,
+    no_images: HashMap<String, egui::TextureHandle>,
+    plain_text: bool,
+    /// The folder's conversations in conversation view
+    threads: Option<&'a [EmailThread]>,
+    /// The current message's conversation, with reply depths, when it has
+    /// more than one message
+    thread: Vec<(&'a Email, usize)>,
+    /// The account's messages waiting to be sent
+    outbox: Vec<&'a OutboxEntry>,
+}
+
+impl<'a> PanelView<'a> {
+    fn new(
+        accounts: &'a [Account],
+        current_account: usize,
+        selected_folder: usize,
+        selected_email: usize,
+        loading: &LoadingState,
+        display: &'a DisplayState,
+        outbox: &'a [OutboxEntry],
+    ) -> Self {
+        let account = accounts.get(current_account);
+        let folders = account.map(|a| a.folders.as_slice()).unwrap_or(&[]);
+        let emails = account.map(|a| a.emails.as_slice()).unwrap_or(&[]);
+        let current_email = emails.get(selected_email);
+        let account_email = account.map(|a| a.email.clone()).unwrap_or_default();
+        let folder_name = folders.get(selected_folder).map(|f| f.name.clone()).unwrap_or_default();
+        let threads = display.threads.as_ref().map(|cache| cache.threads.as_slice());
+        let thread = threads
+            .and_then(|threads| threads.iter().find(|thread| thread.contains(selected_email)))
+            .filter(|thread| thread.len() > 1)
+            .map(|thread| thread.messages(emails).map(|(entry, email)| (email, entry.depth)).collect())
+            .unwrap_or_default();
+
+        Self {
+            folders,
+            emails,
+            current_email,
+            folders_loading: loading.folders.contains(&account_email),
+            emails_loading: loading.emails.contains(&(account_email.clone(), folder_name)),
+            body_loading: current_email
+                .is_some_and(|email| loading.bodies.contains(&(account_email.clone(), email.id))),
+            attachments_loading: loading.attachments
+                .keys()
+                .filter(|(account, email_id, _)| *account == account_email && current_email.is_some_and(|email| email.id == *email_id))
+                .map(|(.., part_id)| part_id.clone())
+                .collect(),
+            bodies_loading: loading.bodies
+                .iter()
+                .filter(|(account, _)| *account == account_email)
+                .map(|(_, email_id)| *email_id)
+                .collect(),
+            html: display.rendered_html.as_ref(),
+            no_images: HashMap::new(),
+            plain_text: display.prefer_plain_text,
+            threads,
+            thread,
+            outbox: outbox.iter().filter(|entry| entry.account_email == account_email).collect(),
+        }
+    }
+
+    fn preview(&self) -> PreviewContent<'_> {
+        PreviewContent {
+            loading: self.body_loading,
+            downloading: &self.attachments_loading,
+            html: self.html.map(|html| html.nodes.as_slice()),
+            images: HtmlImages {
+                inline: self.html.map_or(&self.no_images, |html| &html.inline_textures),
+                remote: self.html.map_or(&self.no_images, |html| &html.remote_textures),
+                load_remote: self.html.is_some_and(|html| html.load_remote),
+            },
+            remote_images: self.html.map_or(&[], |html| &html.remote_images),
+            plain_text: self.plain_text,
+            thread: &self.thread,
+            bodies_loading: &self.bodies_loading,
+        }
+    }
+}
+
 pub struct MailCrossApp {
     // State
     pub current_account: usize,
@@ -23,133 +201,692 @@ pub struct MailCrossApp {
     pub vim_state: VimState,
     
     // Backend
-    pub account_manager: AccountManager,
+    #[allow(dead_code)] // Owns the worker threads; dropping it stops the backend
+    runtime: tokio::runtime::Runtime,
+    pub command_sender: mpsc::UnboundedSender<AccountCommand>,
     pub event_receiver: Option<mpsc::UnboundedReceiver<AccountEvent>>,
+    /// Read-only copy of the worker's accounts, kept current from events so
+    /// rendering never waits on the network
+    pub accounts: Vec<Account>,
+    pub loading: LoadingState,
+    // What the panels currently show, to detect selection changes
+    viewed_account: usize,
+    viewed_folder: Option<(String, String)>,  // account email, folder
+    viewed_email: Option<(String, usize)>,    // account email, email id
     
     // UI State
     pub status_message: String,
     pub show_help: bool,
     pub help_vim_mode: bool,
     pub composer: ComposerWindow,
+    pub composer_receiver: std_mpsc::Receiver<ComposerMessage>,
     pub delete_confirmation: Option<DeleteConfirmation>,
+    /// Messages removed from the list while their deletion or move is in
+    /// flight, restored if it fails
+    pending_removals: HashMap<(String, String, usize), Email>, // account email, folder, email id
+    display: DisplayState,
     pub search_state: SearchState,
     pub settings: SettingsWindow,
+    pub folder_picker: FolderPicker,
+    pub folder_dialog: FolderDialog,
+    /// The worker's outbox, kept current from events
+    outbox: Vec<OutboxEntry>,
 }
 
 impl MailCrossApp {
-    pub fn new() -> Self {
+    pub fn new(ctx: egui::Context) -> Self {
+        let accounts = vec![
+            // Gmail files sent mail by itself
+            Account::new("Gmail", "user@gmail.com").without_sent_copy(),
+            Account::new("Work", "user@work.com"),
+            Account::new("Personal", "user@personal.com"),
+        ];
+
         let mut account_manager = AccountManager::new();
-        
-        // Create communication channels for events
+        for account in &accounts {
+            account_manager.add_account(account.clone());
+        }
+
+        // Create communication channels for commands and events
+        let (command_sender, command_receiver) = mpsc::unbounded_channel();
+        let (backend_sender, mut backend_receiver) = mpsc::unbounded_channel();
         let (event_sender, event_receiver) = mpsc::unbounded_channel();
-        account_manager.set_event_sender(event_sender);
-        
-        // Add default accounts
-        account_manager.add_account(Account::new("Gmail", "user@gmail.com"));
-        account_manager.add_account(Account::new("Work", "user@work.com"));
-        account_manager.add_account(Account::new("Personal", "user@personal.com"));
+        account_manager.set_event_sender(backend_sender);
+
+        let runtime = tokio::runtime::Runtime::new().expect("failed to start the backend runtime");
+        runtime.spawn(account_manager.run(command_receiver));
+
+        // Relay events to the UI and wake it up, since egui only repaints on input
+        runtime.spawn(async move {
+            while let Some(event) = backend_receiver.recv().await {
+                if event_sender.send(event).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        });
+
+        let mut settings = SettingsWindow::new();
+        settings.load_from_config();
+        let mut keyboard_handler = KeyboardHandler::new();
+        keyboard_handler.vim_mode = settings.get_vim_mode();
+        let _ = command_sender.send(AccountCommand::ConfigureAutoRefresh {
+            enabled: settings.get_auto_refresh(),
+            poll_interval: Duration::from_secs(u64::from(settings.get_refresh_interval()) * 60),
+        });
+
+        let mut loading = LoadingState::default();
+        for account in accounts.iter().filter(|account| !account.server.is_empty()) {
+            let _ = command_sender.send(AccountCommand::Connect(account.email.clone()));
+            loading.folders.insert(account.email.clone());
+        }
+
+        let mut composer = ComposerWindow::new();
+        let (composer_sender, composer_receiver) = std_mpsc::channel();
+        composer.set_sender(composer_sender);
         
         Self {
             current_account: 0,
             selected_folder: 0,
             selected_email: usize::MAX,
-            keyboard_handler: KeyboardHandler::new(),
+            keyboard_handler,
             vim_state: VimState::new(),
-            account_manager,
+            runtime,
+            command_sender,
             event_receiver: Some(event_receiver),
+            accounts,
+            loading,
+            viewed_account: 0,
+            viewed_folder: None,
+            viewed_email: None,
             status_message: "Ready".to_string(),
             show_help: false,
             help_vim_mode: false,
-            composer: ComposerWindow::new(),
+            composer,
+            composer_receiver,
             delete_confirmation: None,
+            pending_removals: HashMap::new(),
+            display: DisplayState::default(),
             search_state: SearchState::new(),
-            settings: SettingsWindow::new(),
+            settings,
+            folder_picker: FolderPicker::new(),
+            folder_dialog: FolderDialog::new(),
+            outbox: Vec::new(),
         }
     }
     
     pub fn get_accounts(&self) -> Vec<&Account> {
-        self.account_manager.get_accounts()
+        self.accounts.iter().collect()
+    }
+
+    fn send_command(&self, command: AccountCommand) {
+        if self.command_sender.send(command).is_err() {
+            error_log::report("Backend worker stopped; command dropped");
+        }
+    }
+
+    fn auto_refresh_command(&self) -> AccountCommand {
+        AccountCommand::ConfigureAutoRefresh {
+            enabled: self.settings.get_auto_refresh(),
+            poll_interval: Duration::from_secs(u64::from(self.settings.get_refresh_interval()) * 60),
+        }
+    }
+
+    /// Sets or clears a flag, showing the change right away. `FlagsFailed`
+    /// undoes it if the server refuses.
+    fn set_flag(&mut self, account_email: &str, folder: &str, uid_validity: u32, email_ids: Vec<usize>, flag: MessageFlag, value: bool) {
+        let updated: Vec<(usize, EmailFlags)> = self.get_current_emails()
+            .into_iter()
+            .filter(|e| email_ids.contains(&e.id))
+            .map(|e| {
+                let mut flags = e.flags;
+                flags.set(&flag, value);
+                (e.id, flags)
+            })
+            .collect();
+        self.apply_flags(account_email, folder, &updated);
+        self.send_command(AccountCommand::StoreFlag {
+            account_email: account_email.to_string(),
+            folder: folder.to_string(),
+            uid_validity,
+            email_ids,
+            flag,
+            value,
+        });
+    }
+
+    /// Flag change on the selected message; `None` toggles the flag.
+    fn set_current_flag(&mut self, flag: MessageFlag, value: Option<bool>) {
+        let (Some(email), Some((account_email, folder))) = (self.get_current_email(), self.viewed_folder.clone()) else {
+            self.status_message = "No email selected".to_string();
+            return;
+        };
+        let value = value.unwrap_or(!email.flags.contains(&flag));
+        self.set_flag(&account_email, &folder, email.uid_validity, vec![email.id], flag, value);
+    }
+
+    /// Updates flags in the viewed list, and the folder's unread count with
+    /// them.
+    fn apply_flags(&mut self, account_email: &str, folder: &str, flags: &[(usize, EmailFlags)]) {
+        if self.viewed_folder.as_ref() != Some(&(account_email.to_string(), folder.to_string())) {
+            return;
+        }
+        let mut unread = 0;
+        if let Some(account) = self.snapshot_account_mut(account_email) {
+            for (email_id, new_flags) in flags {
+                if let Some(email) = account.emails.iter_mut().find(|e| e.id == *email_id) {
+                    unread += unread_change(email.flags.seen, new_flags.seen);
+                    email.flags = new_flags.clone();
+                }
+            }
+        }
+        self.adjust_folder_count(account_email, folder, 0, unread);
+    }
+
+    fn adjust_folder_count(&mut self, account_email: &str, folder: &str, total: isize, unread: isize) {
+        if let Some(folder_obj) = self.snapshot_account_mut(account_email)
+            .and_then(|account| account.folders.iter_mut().find(|f| f.name == folder)) {
+            folder_obj.total = folder_obj.total.saturating_add_signed(total);
+            folder_obj.unread = folder_obj.unread.saturating_add_signed(unread);
+        }
+    }
+
+    /// Puts the cursor back on a message after the list changed under it.
+    fn reselect_email(&mut self, selected_id: Option<usize>) {
+        let emails = self.get_current_emails();
+        self.selected_email = selected_id
+            .and_then(|id| emails.iter().position(|e| e.id == id))
+            .unwrap_or(usize::MAX);
+        if selected_id.is_some() && self.selected_email == usize::MAX {
+            self.viewed_email = None;
+        }
+    }
+
+    fn snapshot_account_mut(&mut self, email: &str) -> Option<&mut Account> {
+        self.accounts.iter_mut().find(|account| account.email == email)
     }
     
-    #[allow(dead_code)] // Will be used when email data is available
     fn get_current_email(&self) -> Option<Email> {
-        // Mock email for testing compose functionality
-        Some(Email {
-            id: 1,
-            sender: "sender@example.com".to_string(),
-            recipient: "recipient@example.com".to_string(),
-            subject: "Test Email".to_string(),
-            body: "This is a test email body.".to_string(),
-            date: "2024-01-01".to_string(),
-            is_read: false,
-            is_selected: false,
-        })
+        self.accounts.get(self.current_account)?
+            .emails
+            .get(self.selected_email)
+            .cloned()
     }
 
     fn get_current_emails(&self) -> Vec<Email> {
-        // Mock emails for testing search functionality
-        vec![
-            Email {
-                id: 1,
-                sender: "alice@example.com".to_string(),
-                recipient: "me@example.com".to_string(),
-                subject: "Meeting tomorrow".to_string(),
-                body: "Don't forget about our meeting tomorrow at 10am.".to_string(),
-                date: "2024-01-15".to_string(),
-                is_read: false,
-                is_selected: false,
-            },
-            Email {
-                id: 2,
-                sender: "bob@company.com".to_string(),
-                recipient: "me@example.com".to_string(),
-                subject: "Project update".to_string(),
-                body: "The project is progressing well. Here's the latest update.".to_string(),
-                date: "2024-01-14".to_string(),
-                is_read: true,
-                is_selected: false,
-            },
-            Email {
-                id: 3,
-                sender: "newsletter@tech.com".to_string(),
-                recipient: "me@example.com".to_string(),
-                subject: "Weekly Tech News".to_string(),
-                body: "This week in technology: AI advances, new frameworks, and more.".to_string(),
-                date: "2024-01-13".to_string(),
-                is_read: false,
-                is_selected: false,
-            },
-        ]
+        self.accounts
+            .get(self.current_account)
+            .map(|account| account.emails.clone())
+            .unwrap_or_default()
+    }
+
+    /// Requests folder contents and message bodies when the selection moved
+    /// to something that has not been loaded yet.
+    fn sync_selection(&mut self) {
+        if self.viewed_account != self.current_account {
+            self.viewed_account = self.current_account;
+            self.selected_folder = 0;
+        }
+
+        let Some(account) = self.accounts.get(self.current_account) else {
+            return;
+        };
+        let account_email = account.email.clone();
+        // The backend serves stored mail while offline, so configured
+        // accounts are asked even when not connected
+        let reachable = account.is_connected || !account.server.is_empty();
+        let connected = account.is_connected;
+
+        if account.folders.is_empty() {
+            self.selected_folder = 0;
+        } else if self.selected_folder >= account.folders.len() {
+            self.selected_folder = account.folders.len() - 1;
+        }
+
+        let folder_key = account.folders
+            .get(self.selected_folder)
+            .map(|folder| (account_email.clone(), folder.name.clone()));
+        // \Noselect folders only hold other folders
+        let selectable = account.folders.get(self.selected_folder).is_none_or(|folder| folder.selectable);
+        if folder_key != self.viewed_folder {
+            self.viewed_folder = folder_key.clone();
+            self.selected_email = usize::MAX;
+            self.viewed_email = None;
+            if let Some(account) = self.snapshot_account_mut(&account_email) {
+                account.emails.clear();
+            }
+            if let (Some((_, folder)), true) = (folder_key, reachable && selectable) {
+                self.loading.emails.insert((account_email.clone(), folder.clone()));
+                self.send_command(AccountCommand::FetchEmails { account_email: account_email.clone(), folder: folder.clone(), limit: EMAIL_FETCH_LIMIT });
+                self.send_command(AccountCommand::WatchFolder { account_email: account_email.clone(), folder, limit: EMAIL_FETCH_LIMIT });
+            }
+        }
+
+        let email_key = self.get_current_email().map(|email| (account_email.clone(), email.id));
+        if email_key != self.viewed_email {
+            self.viewed_email = email_key;
+            if let (Some(email), true) = (self.get_current_email(), reachable) {
+                self.fetch_body(&account_email, &email);
+            }
+
+            // Moving the cursor to a message marks it read; the body fetch
+            // itself does not. List refreshes keep `viewed_email` on the same
+            // message (see `reselect_email`), so they never get here
+            let unread = self.get_current_email().filter(|email| !email.flags.seen);
+            if let (Some(email), Some((_, folder)), true) = (unread, self.viewed_folder.clone(), connected) {
+                self.set_flag(&account_email, &folder, email.uid_validity, vec![email.id], MessageFlag::Seen, true);
+            }
+        }
     }
     
+    /// Asks for the body of a message in the viewed folder unless it is
+    /// loaded or on its way.
+    fn fetch_body(&mut self, account_email: &str, email: &Email) {
+        let Some((_, folder)) = self.viewed_folder.clone() else {
+            return;
+        };
+        let key = (account_email.to_string(), email.id);
+        if email.body_loaded || self.loading.bodies.contains(&key) {
+            return;
+        }
+        self.loading.bodies.insert(key);
+        self.send_command(AccountCommand::FetchEmailBody {
+            account_email: account_email.to_string(),
+            folder,
+            uid_validity: email.uid_validity,
+            email_id: email.id,
+        });
+    }
+
+    /// Groups the viewed folder into conversations in conversation view,
+    /// with the server's threads when they cover the list.
+    fn refresh_threads(&mut self) {
+        if !self.settings.conversation_view {
+            self.display.threads = None;
+            return;
+        }
+        let emails = self.accounts.get(self.current_account).map(|account| account.emails.as_slice()).unwrap_or(&[]);
+        let ids: Vec<usize> = emails.iter().map(|email| email.id).collect();
+        let server = self.display.server_threads
+            .as_ref()
+            .filter(|((account, folder, uid_validity), _)| {
+                self.viewed_folder.as_ref().is_some_and(|(viewed_account, viewed)| viewed_account == account && viewed == folder)
+                    && emails.iter().all(|email| email.uid_validity == *uid_validity)
+            })
+            .map(|(_, nodes)| nodes);
+
+        let up_to_date = self.display.threads
+            .as_ref()
+            .is_some_and(|cache| cache.ids == ids && (cache.with_server || server.is_none()));
+        if up_to_date {
+            return;
+        }
+        let threads = server
+            .and_then(|nodes| EmailThread::from_server(emails, nodes))
+            .unwrap_or_else(|| EmailThread::build(emails));
+        self.display.threads = Some(ThreadCache { ids, with_server: server.is_some(), threads });
+    }
+
+    /// Moves the selection to the latest message of the next or previous
+    /// conversation.
+    fn step_conversation(&mut self, forward: bool) {
+        let emails = self.get_current_emails();
+        let Some(cache) = self.display.threads.as_ref().filter(|cache| !cache.threads.is_empty()) else {
+            self.status_message = "No emails in this folder".to_string();
+            return;
+        };
+        let current = cache.threads.iter().position(|thread| thread.contains(self.selected_email));
+        let target = match (current, forward) {
+            (None, _) => 0,
+            (Some(i), true) => (i + 1).min(cache.threads.len() - 1),
+            (Some(i), false) => i.saturating_sub(1),
+        };
+        if let Some(latest) = cache.threads[target].latest(&emails) {
+            self.selected_email = latest;
+        }
+        self.status_message = format!("Conversation {}", target + 1);
+    }
+
+    /// Lets the server thread the viewed folder, if it can, in conversation view.
+    fn request_server_threads(&mut self) {
+        let Some((account_email, folder)) = self.viewed_folder.clone() else {
+            return;
+        };
+        let Some(account) = self.accounts.get(self.current_account).filter(|account| account.is_connected) else {
+            return;
+        };
+        if !self.settings.conversation_view {
+            return;
+        }
+        let uid_validity = account.emails.first().map(|email| email.uid_validity).unwrap_or(0);
+        self.send_command(AccountCommand::FetchThreads { account_email, folder, uid_validity });
+    }
+
     fn process_events(&mut self) {
+        let mut events = Vec::new();
         if let Some(receiver) = &mut self.event_receiver {
             while let Ok(event) = receiver.try_recv() {
-                match event {
-                    AccountEvent::Connected(email) => {
-                        self.status_message = format!("Connected to {}", email);
+                events.push(event);
+            }
+        }
+
+        for event in events {
+            match event {
+                AccountEvent::Connected(email) => {
+                    if let Some(account) = self.snapshot_account_mut(&email) {
+                        account.is_connected = true;
+                    }
+                    self.status_message = format!("Connected to {}", email);
+                }
+                AccountEvent::Disconnected(email) => {
+                    if let Some(account) = self.snapshot_account_mut(&email) {
+                        account.is_connected = false;
+                    }
+                    self.loading.clear_account(&email);
+                    self.status_message = format!("Disconnected from {}", email);
+                }
+                AccountEvent::ConnectionFailed(email, error) => {
+                    self.loading.clear_account(&email);
+                    self.status_message = format!("Failed to connect to {}: {}", email, error);
+                }
+                AccountEvent::FoldersUpdated(email, folders) => {
+                    // Stay on the same folder if its position changed
+                    if let Some((account, folder)) = &self.viewed_folder
+                        && *account == email
+                        && let Some(index) = folders.iter().position(|f| f.name == *folder) {
+                        self.selected_folder = index;
+                    }
+                    if let Some(account) = self.snapshot_account_mut(&email) {
+                        account.folders = folders;
+                    }
+                    self.loading.folders.remove(&email);
+                    // Re-evaluate the selection against the new folder list
+                    if self.viewed_folder.as_ref().is_some_and(|(account, _)| *account == email) {
+                        self.viewed_folder = None;
+                    }
+                    self.status_message = format!("Folders updated for {}", email);
+                }
+                AccountEvent::EmailsUpdated(email, folder, emails) => {
+                    let key = (email.clone(), folder.clone());
+                    self.loading.emails.remove(&key);
+                    // Drop results for a folder the user has already left
+                    if self.viewed_folder.as_ref() == Some(&key) {
+                        let selected_id = self.get_current_email().map(|e| e.id);
+                        if let Some(account) = self.snapshot_account_mut(&email) {
+                            account.emails = emails;
+                        }
+                        self.reselect_email(selected_id);
+                        // The new list may not carry the open message's body
+                        if let Some(current) = self.get_current_email() {
+                            self.fetch_body(&email, &current);
+                        }
+                        self.request_server_threads();
+                    }
+                    self.status_message = format!("Emails updated for {}/{}", email, folder);
+                }
+                AccountEvent::EmailBodyLoaded(email, _folder, email_id, body) => {
+                    self.loading.bodies.remove(&(email.clone(), email_id));
+                    if let Some(message) = self.snapshot_account_mut(&email)
+                        .and_then(|account| account.emails.iter_mut().find(|e| e.id == email_id)) {
+                        message.body = body.text;
+                        message.body_loaded = true;
+                        message.html = body.html;
+                        message.attachments = body.attachments;
+                        message.inline_images = body.inline_images;
+                    }
+                }
+                AccountEvent::AttachmentLoaded(email, _folder, email_id, attachment, content) => {
+                    let key = (email, email_id);
+                    let target = self.loading.attachments.remove(&(key.0.clone(), email_id, attachment.part_id.clone()));
+                    self.status_message = match target {
+                        Some(AttachmentTarget::Inline) => {
+                            // Textures are made while rendering, where the context is at hand
+                            if let Some(rendered) = self.display.rendered_html.as_mut().filter(|rendered| rendered.key == key)
+                                && let Some(content_id) = attachment.content_id {
+                                rendered.pending_images.push((ImageSource::Inline(content_id), content));
+                            }
+                            continue;
+                        }
+                        Some(AttachmentTarget::Save) => match downloads::save(&attachment.filename, &content) {
+                            Ok(path) => format!("Saved {}", path.display()),
+                            Err(e) => format!("Failed to save {}: {}", attachment.filename, e),
+                        },
+                        Some(AttachmentTarget::Open) => match downloads::open(&attachment.filename, &content) {
+                            Ok(_) => format!("Opened {}", attachment.filename),
+                            Err(e) => format!("Failed to open {}: {}", attachment.filename, e),
+                        },
+                        None => continue,
+                    };
+                }
+                AccountEvent::AttachmentFailed(email, _folder, email_id, attachment, error) => {
+                    let target = self.loading.attachments.remove(&(email, email_id, attachment.part_id.clone()));
+                    if target == Some(AttachmentTarget::Inline) {
+                        continue;
+                    }
+                    self.status_message = format!("Failed to download {}: {}", attachment.filename, error);
+                }
+                AccountEvent::ThreadsLoaded(email, folder, uid_validity, threads) => {
+                    if self.viewed_folder.as_ref() == Some(&(email.clone(), folder.clone())) {
+                        self.display.server_threads = Some(((email, folder, uid_validity), threads));
+                        // Rebuilt with the server's threads on the next frame
+                        self.display.threads = None;
+                    }
+                }
+                AccountEvent::RemoteImageLoaded(url, content) => {
+                    let source = ImageSource::Remote(url);
+                    // Dropped if the message was closed in the meantime
+                    if let Some(rendered) = self.display.rendered_html.as_mut()
+                        && rendered.requested_images.contains(&source) {
+                        rendered.pending_images.push((source, content));
+                    }
+                }
+                AccountEvent::RemoteImageFailed(url, error) => {
+                    self.status_message = format!("Failed to load remote image {}: {}", url, error);
+                }
+                AccountEvent::EmailBodyFailed(email, _folder, email_id, error) => {
+                    self.loading.bodies.remove(&(email.clone(), email_id));
+                    self.status_message = format!("Failed to load message: {}", error);
+                }
+                AccountEvent::EmailDeleted(email, folder, email_id, trash) => {
+                    let deleted = self.pending_removals.remove(&(email.clone(), folder.clone(), email_id));
+                    let viewed = self.viewed_folder.as_ref() == Some(&(email.clone(), folder.clone()));
+                    if viewed && let Some(account) = self.snapshot_account_mut(&email) {
+                        account.emails.retain(|e| e.id != email_id);
+                    }
+                    let unread = deleted.as_ref().is_some_and(|e| !e.flags.seen) as isize;
+                    self.adjust_folder_count(&email, &folder, -1, -unread);
+                    if let Some(trash) = &trash {
+                        self.adjust_folder_count(&email, trash, 1, unread);
+                    }
+                    let subject = deleted.map(|e| e.subject).unwrap_or_else(|| format!("email {}", email_id));
+                    self.status_message = match trash {
+                        Some(trash) => format!("Moved \"{}\" to {}", subject, trash),
+                        None => format!("Permanently deleted \"{}\"", subject),
+                    };
+                }
+                AccountEvent::DeleteFailed(email, folder, email_id, error) => {
+                    let restored = self.restore_pending(&email, &folder, &[email_id]);
+                    let subject = restored.into_iter().next().map(|e| e.subject).unwrap_or_else(|| format!("email {}", email_id));
+                    self.status_message = format!("Failed to delete \"{}\": {}", subject, error);
+                }
+                AccountEvent::EmailsMoved(email, folder, email_ids, target_account, target_folder) => {
+                    let moved: Vec<Email> = email_ids
+                        .iter()
+                        .filter_map(|&id| self.pending_removals.remove(&(email.clone(), folder.clone(), id)))
+                        .collect();
+                    let unread = moved.iter().filter(|e| !e.flags.seen).count() as isize;
+                    self.adjust_folder_count(&email, &folder, -(email_ids.len() as isize), -unread);
+                    self.adjust_folder_count(&target_account, &target_folder, email_ids.len() as isize, unread);
+                    self.status_message = match moved.as_slice() {
+                        [single] => format!("Moved \"{}\" to {}", single.subject, target_folder),
+                        _ => format!("Moved {} emails to {}", email_ids.len(), target_folder),
+                    };
+                }
+                AccountEvent::EmailsCopied(email, folder, email_ids, target_account, target_folder) => {
+                    // A move that could not remove the originals is reported
+                    // as a copy while they are still off the list
+                    let pending = email_ids.iter().filter_map(|&id| self.pending_removals.get(&(email.clone(), folder.clone(), id)));
+                    let mut unread = pending.filter(|e| !e.flags.seen).count() as isize;
+                    if self.viewed_folder.as_ref() == Some(&(email, folder)) {
+                        unread += self.get_current_emails().iter().filter(|e| email_ids.contains(&e.id) && !e.flags.seen).count() as isize;
+                    }
+                    self.adjust_folder_count(&target_account, &target_folder, email_ids.len() as isize, unread);
+                    self.status_message = format!("Copied {} email(s) to {}", email_ids.len(), target_folder);
+                }
+                AccountEvent::TransferFailed(email, folder, email_ids, error) => {
+                    // Only moves took messages off the list
+                    self.restore_pending(&email, &folder, &email_ids);
+                    self.status_message = format!("Failed to transfer {} email(s): {}", email_ids.len(), error);
+                }
+                AccountEvent::FolderInvalidated(email, folder) => {
+                    // Forces sync_selection to fetch the folder again
+                    if self.viewed_folder.as_ref() == Some(&(email.clone(), folder.clone())) {
+                        self.viewed_folder = None;
+                    }
+                    self.status_message = format!("{} changed on the server, reloading", folder);
+                }
+                AccountEvent::MailboxChanged(email, folder, changes) => {
+                    let viewed = self.viewed_folder.as_ref() == Some(&(email.clone(), folder.clone()));
+                    let selected_id = self.get_current_email().map(|e| e.id);
+                    // Flags of removed and updated messages are only known for the viewed list
+                    let before = if viewed { self.get_current_emails() } else { Vec::new() };
+                    let mut unread = changes.added.iter().filter(|e| !e.flags.seen).count() as isize;
+                    for old in &before {
+                        if changes.removed.contains(&old.id) {
+                            unread -= !old.flags.seen as isize;
+                        } else if let Some(new) = changes.updated.iter().find(|e| e.id == old.id) {
+                            unread += unread_change(old.flags.seen, new.flags.seen);
+                        }
                     }
-                    AccountEvent::Disconnected(email) => {
-                        self.status_message = format!("Disconnected from {}", email);
+                    let total = changes.added.len() as isize - changes.removed.len() as isize;
+                    self.adjust_folder_count(&email, &folder, total, unread);
+                    if viewed && let Some(account) = self.snapshot_account_mut(&email) {
+                        changes.apply_to(&mut account.emails);
                     }
-                    AccountEvent::ConnectionFailed(email, error) => {
-                        self.status_message = format!("Failed to connect to {}: {}", email, error);
+                    if viewed {
+                        self.reselect_email(selected_id);
+                        if !changes.added.is_empty() || !changes.removed.is_empty() {
+                            self.request_server_threads();
+                        }
+                    }
+                    if !changes.added.is_empty() {
+                        self.status_message = format!("{} new in {}/{}", changes.added.len(), email, folder);
+                    }
+                }
+                AccountEvent::FlagsChanged(email, folder, flags) => {
+                    self.apply_flags(&email, &folder, &flags);
+                }
+                AccountEvent::FlagsFailed(email, folder, flags, error) => {
+                    self.apply_flags(&email, &folder, &flags);
+                    self.status_message = format!("Failed to update flags: {}", error);
+                }
+                AccountEvent::FolderOperationFailed(email, error) => {
+                    self.status_message = format!("Folder operation failed for {}: {}", email, error);
+                }
+                AccountEvent::FolderStatusFailed(email, folder, error) => {
+                    self.status_message = format!("Counts of {} unavailable for {}: {}", crate::backend::mailbox_name::decode(&folder), email, error);
+                }
+                AccountEvent::EmailSent(email, subject, replying_to, draft_id) => {
+                    if let Some(original) = replying_to {
+                        self.set_flag(&original.account_email, &original.folder, original.uid_validity, vec![original.uid], MessageFlag::Answered, true);
                     }
-                    AccountEvent::FoldersUpdated(email, _folders) => {
-                        self.status_message = format!("Folders updated for {}", email);
+                    if let Some(draft_id) = draft_id {
+                        self.send_command(AccountCommand::DeleteDraft { account_email: email.clone(), draft_id });
                     }
-                    AccountEvent::EmailsUpdated(email, folder, _emails) => {
-                        self.status_message = format!("Emails updated for {}/{}", email, folder);
+                    self.status_message = format!("Sent \"{}\" from {}", subject, email);
+                }
+                AccountEvent::DraftSaved(email, folder) => {
+                    self.status_message = format!("Draft saved to {}/{}", email, folder);
+                }
+                AccountEvent::DraftLoaded(email, content) => {
+                    // Still on the server if it cannot be opened now
+                    if !self.set_composer_aside() {
+                        continue;
                     }
-                    AccountEvent::EmailDeleted(email, email_id) => {
-                        self.status_message = format!("Deleted email {} from {}", email_id, email);
+                    let draft = ParsedMessage::parse(&content);
+                    let account_index = self.accounts.iter().position(|account| account.email == email).unwrap_or(0);
+                    self.composer.show_draft(&draft, account_index);
+                    self.status_message = "Editing draft".to_string();
+                }
+                AccountEvent::DraftFailed(email, error) => {
+                    self.status_message = format!("Draft not saved for {}: {}", email, error);
+                }
+                AccountEvent::SendFailed(email, error, will_retry) => {
+                    self.status_message = if will_retry {
+                        format!("Could not send from {} yet, will retry: {}", email, error)
+                    } else {
+                        format!("Failed to send from {}: {}", email, error)
+                    };
+                }
+                AccountEvent::OutboxChanged(outbox) => {
+                    self.outbox = outbox;
+                }
+                AccountEvent::OutboxItemOpened(entry, content) => {
+                    let message = ParsedMessage::parse(&content);
+                    // It is out of the outbox now, so it goes to Drafts if
+                    // the composer is busy with something that cannot be saved
+                    if !self.set_composer_aside() {
+                        self.status_message = match self.composer.save_unsent(&message, &entry.account_email, entry.replying_to, entry.draft_id) {
+                            Ok(()) => format!("Saved \"{}\" to Drafts: finish the message being composed first", entry.subject),
+                            Err(e) => format!("Could not save \"{}\" to Drafts: {}", entry.subject, e),
+                        };
+                        continue;
                     }
+                    let account_index = self.accounts.iter().position(|account| account.email == entry.account_email).unwrap_or(0);
+                    self.composer.show_unsent(&message, account_index, entry.replying_to, entry.draft_id, entry.scheduled.then_some(entry.send_at));
+                    self.status_message = "Editing message taken out of the outbox".to_string();
+                }
+                AccountEvent::OutboxItemTooLate(subject) => {
+                    self.status_message = match subject {
+                        Some(subject) => format!("Too late to undo: \"{}\" is being sent", subject),
+                        None => "Too late to undo: the message has been sent".to_string(),
+                    };
+                }
+                AccountEvent::SentCopyFailed(email, error) => {
+                    self.status_message = format!("Sent mail not yet copied to Sent for {}, will retry: {}", email, error);
+                }
+            }
+        }
+    }
+
+    fn process_composer_messages(&mut self) {
+        while let Ok(message) = self.composer_receiver.try_recv() {
+            match message {
+                ComposerMessage::SendEmail { message, replying_to, draft_id, send_at } => {
+                    let account_email = message.from.address.clone();
+                    let scheduled = send_at.is_some();
+                    let send_at = match send_at {
+                        Some(send_at) => {
+                            let when = chrono::DateTime::from_timestamp(send_at as i64, 0)
+                                .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                                .unwrap_or_default();
+                            self.status_message = format!("Scheduled \"{}\" for {}", message.subject, when);
+                            send_at
+                        }
+                        // Held for the undo window, counted down in the status bar
+                        None => unix_now() + u64::from(self.settings.get_undo_send_delay()),
+                    };
+                    self.send_command(AccountCommand::SendEmail { account_email, message: *message, replying_to, draft_id, send_at, scheduled });
+                }
+                ComposerMessage::SaveDraft { message, replying_to, draft_id } => {
+                    let account_email = message.from.address.clone();
+                    self.send_command(AccountCommand::SaveDraft { account_email, message: *message, draft_id, replying_to });
                 }
             }
         }
     }
 
     pub fn handle_keyboard_input(&mut self, ctx: &egui::Context) {
+        // Keys belong to the dialog's text box while one is open
+        if self.folder_picker.visible || self.folder_dialog.visible {
+            if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                self.folder_picker.hide();
+                self.folder_dialog.hide();
+                self.status_message = "Cancelled".to_string();
+            }
+            return;
+        }
+
         // Update keyboard handler with current vim mode state
         self.keyboard_handler.vim_mode = self.vim_state.mode != crate::input::vim::VimMode::Normal || self.keyboard_handler.vim_mode;
         
@@ -172,14 +909,25 @@ impl MailCrossApp {
             }
             
             // Navigation
+            KeyAction::NextItem if self.display.threads.is_some() => {
+                self.step_conversation(true);
+            }
+            KeyAction::PrevItem if self.display.threads.is_some() => {
+                self.step_conversation(false);
+            }
             KeyAction::NextItem => {
                 // Navigate to next email
-                if self.selected_email == usize::MAX {
-                    self.selected_email = 0; // Start from first email
+                let email_count = self.get_current_emails().len();
+                if email_count == 0 {
+                    self.status_message = "No emails in this folder".to_string();
                 } else {
-                    self.selected_email = (self.selected_email + 1).min(5); // Mock limit (6 emails total, 0-5)
+                    if self.selected_email == usize::MAX {
+                        self.selected_email = 0; // Start from first email
+                    } else {
+                        self.selected_email = (self.selected_email + 1).min(email_count - 1);
+                    }
+                    self.status_message = format!("Email {}", self.selected_email + 1);
                 }
-                self.status_message = format!("Email {}", self.selected_email + 1);
             }
             KeyAction::PrevItem => {
                 if self.selected_email == usize::MAX || self.selected_email == 0 {
@@ -201,12 +949,17 @@ impl MailCrossApp {
                 self.status_message = "Previous panel".to_string();
             }
             KeyAction::FirstItem => {
-                self.selected_email = 0;
-                self.status_message = "First email".to_string();
+                if !self.get_current_emails().is_empty() {
+                    self.selected_email = 0;
+                    self.status_message = "First email".to_string();
+                }
             }
             KeyAction::LastItem => {
-                self.selected_email = 5; // Mock limit (6 emails total, 0-5)
-                self.status_message = "Last email".to_string();
+                let email_count = self.get_current_emails().len();
+                if email_count > 0 {
+                    self.selected_email = email_count - 1;
+                    self.status_message = "Last email".to_string();
+                }
             }
             
             // Email operations  
@@ -215,19 +968,33 @@ impl MailCrossApp {
                 self.status_message = "Compose new email".to_string();
             }
             KeyAction::Reply => {
-                // Get current email (mock for now)
                 if let Some(email) = self.get_current_email() {
-                    self.composer.show_reply(&email, self.current_account);
+                    let replying_to = self.viewed_folder.clone().map(|(account_email, folder)| MessageRef {
+                        account_email,
+                        folder,
+                        uid_validity: email.uid_validity,
+                        uid: email.id,
+                    });
+                    self.composer.show_reply(&email, self.current_account, replying_to);
                     self.status_message = "Reply to email".to_string();
+                } else {
+                    self.status_message = "No email selected to reply to".to_string();
                 }
             }
             KeyAction::Forward => {
-                // Get current email (mock for now)
                 if let Some(email) = self.get_current_email() {
                     self.composer.show_forward(&email, self.current_account);
                     self.status_message = "Forward email".to_string();
+                } else {
+                    self.status_message = "No email selected to forward".to_string();
                 }
             }
+            KeyAction::MoveToFolder => {
+                self.show_folder_picker(TransferKind::Move);
+            }
+            KeyAction::CopyToFolder => {
+                self.show_folder_picker(TransferKind::Copy);
+            }
             KeyAction::Delete => {
                 if let Some(email) = self.get_current_email() {
                     self.show_delete_confirmation(email.id);
@@ -280,8 +1047,29 @@ impl MailCrossApp {
                 }
             }
             
+            KeyAction::MarkRead => {
+                self.set_current_flag(MessageFlag::Seen, Some(true));
+            }
+            KeyAction::MarkUnread => {
+                self.set_current_flag(MessageFlag::Seen, Some(false));
+            }
+            KeyAction::ToggleFlagged => {
+                self.set_current_flag(MessageFlag::Flagged, None);
+            }
+
             // View operations
+            KeyAction::ToggleRead => {
+                self.set_current_flag(MessageFlag::Seen, None);
+            }
             KeyAction::RefreshFolder => {
+                if let Some(account) = self.accounts.get(self.current_account) {
+                    let account_email = account.email.clone();
+                    let folder = account.folders.get(self.selected_folder).map(|f| f.name.clone());
+                    self.send_command(AccountCommand::RefreshFolders(account_email.clone()));
+                    if let Some(folder) = folder {
+                        self.send_command(AccountCommand::FetchEmails { account_email, folder, limit: EMAIL_FETCH_LIMIT });
+                    }
+                }
                 self.status_message = "Refreshing folder...".to_string();
             }
             
@@ -351,10 +1139,9 @@ impl MailCrossApp {
                         self.vim_state.reset();
                         self.status_message = "Command cancelled".to_string();
                     }
-                    egui::Event::Key { key: egui::Key::Backspace, pressed: true, .. } => {
-                        if self.vim_state.command_buffer.len() > 1 { // Keep the ':'
-                            self.vim_state.command_buffer.pop();
-                        }
+                    egui::Event::Key { key: egui::Key::Backspace, pressed: true, .. }
+                        if self.vim_state.command_buffer.len() > 1 => { // Keep the ':'
+                        self.vim_state.command_buffer.pop();
                     }
                     egui::Event::Text(text) => {
                         self.vim_state.command_buffer.push_str(text);
@@ -389,8 +1176,41 @@ impl MailCrossApp {
                 self.show_help = true;
                 self.help_vim_mode = true;
             }
-            VimCommand::Set(setting) => {
-                self.status_message = format!("Set: {}", setting);
+            VimCommand::Set(setting) => match setting.as_str() {
+                "subscribed" => self.set_subscribed_only(true),
+                "nosubscribed" => self.set_subscribed_only(false),
+                _ => self.status_message = format!("Set: {}", setting),
+            },
+            VimCommand::Move(folder) => {
+                self.transfer_to_named_folder(TransferKind::Move, &folder);
+            }
+            VimCommand::Copy(folder) => {
+                self.transfer_to_named_folder(TransferKind::Copy, &folder);
+            }
+            VimCommand::CreateFolder(path) => {
+                let path: Vec<String> = path.split('/').map(|level| level.trim().to_string()).collect();
+                self.handle_folder_dialog_action(FolderDialogAction::Create(path));
+            }
+            VimCommand::RenameFolder(path) => {
+                match self.current_folder().filter(|folder| !folder.is_inbox()).map(|folder| folder.name.clone()) {
+                    Some(folder) => {
+                        let new_path = path.split('/').map(|level| level.trim().to_string()).collect();
+                        self.handle_folder_dialog_action(FolderDialogAction::Rename { folder, new_path });
+                    }
+                    None => self.status_message = "This folder cannot be renamed".to_string(),
+                }
+            }
+            VimCommand::DeleteFolder => {
+                // Deleting takes the messages with it, so confirm first
+                match self.current_folder().filter(|folder| !folder.is_inbox()).cloned() {
+                    Some(folder) => self.folder_dialog.show_delete(&folder),
+                    None => self.status_message = "This folder cannot be deleted".to_string(),
+                }
+            }
+            VimCommand::Subscribe(subscribed) => {
+                if let Some(folder) = self.current_folder().map(|folder| folder.name.clone()) {
+                    self.set_subscribed(folder, subscribed);
+                }
             }
         }
     }
@@ -399,12 +1219,20 @@ impl MailCrossApp {
         match action {
             ComposerAction::Send => {
                 let account_email = self.get_accounts()[self.composer.from_account].email.clone();
-                self.status_message = format!("Sending email from {}", account_email);
-                self.composer.visible = false;
+                self.status_message = match self.composer.submit_send(&account_email) {
+                    Ok(()) => format!("Sending email from {}", account_email),
+                    Err(reason) => reason,
+                };
             }
             ComposerAction::Save => {
-                self.status_message = "Email saved as draft".to_string();
-                self.composer.visible = false;
+                let account_email = self.get_accounts()[self.composer.from_account].email.clone();
+                self.status_message = match self.composer.submit_draft(&account_email) {
+                    Ok(()) => {
+                        self.composer.visible = false;
+                        format!("Saving draft for {}", account_email)
+                    }
+                    Err(reason) => reason,
+                };
             }
             ComposerAction::Cancel => {
                 self.status_message = "Email composition cancelled".to_string();
@@ -413,9 +1241,391 @@ impl MailCrossApp {
         }
     }
 
+    /// Saves the message being composed as a draft before the composer
+    /// takes another. False, with the reason shown, if it could not be saved.
+    fn set_composer_aside(&mut self) -> bool {
+        let account_email = self.get_accounts()[self.composer.from_account].email.clone();
+        match self.composer.set_aside(&account_email) {
+            Ok(()) => true,
+            Err(reason) => {
+                self.status_message = format!("Finish or discard the message being composed first: {}", reason);
+                false
+            }
+        }
+    }
+
+    /// Saves the open message as a draft once edits have settled, and wakes
+    /// up again when they will have.
+    fn autosave_draft(&mut self, ctx: &egui::Context) {
+        match self.composer.autosave_due(Instant::now()) {
+            Some(wait) if wait.is_zero() => {
+                let account_email = self.get_accounts()[self.composer.from_account].email.clone();
+                if self.composer.autosave(&account_email) {
+                    self.status_message = "Autosaving draft...".to_string();
+                }
+            }
+            Some(wait) => ctx.request_repaint_after(wait),
+            None => {}
+        }
+    }
+
+    /// Takes messages off the viewed list while a request that removes them
+    /// is in flight.
+    fn remove_pending(&mut self, account_email: &str, folder: &str, email_ids: &[usize]) {
+        let Some(account) = self.snapshot_account_mut(account_email) else {
+            return;
+        };
+        let mut removed = Vec::new();
+        account.emails.retain(|e| {
+            if email_ids.contains(&e.id) {
+                removed.push(e.clone());
+                false
+            } else {
+                true
+            }
+        });
+        let remaining = account.emails.len();
+        for email in removed {
+            self.pending_removals.insert((account_email.to_string(), folder.to_string(), email.id), email);
+        }
+        // The cursor moves on to the next message
+        if self.selected_email >= remaining {
+            self.selected_email = remaining.checked_sub(1).unwrap_or(usize::MAX);
+        }
+    }
+
+    /// Puts messages back after the request that removed them failed.
+    /// Returns them, or nothing for ids that were not pending.
+    fn restore_pending(&mut self, account_email: &str, folder: &str, email_ids: &[usize]) -> Vec<Email> {
+        let restored: Vec<Email> = email_ids
+            .iter()
+            .filter_map(|&id| self.pending_removals.remove(&(account_email.to_string(), folder.to_string(), id)))
+            .collect();
+        if !restored.is_empty() && self.viewed_folder.as_ref() == Some(&(account_email.to_string(), folder.to_string())) {
+            let selected_id = self.get_current_email().map(|e| e.id);
+            if let Some(account) = self.snapshot_account_mut(account_email) {
+                account.emails.extend(restored.iter().cloned());
+                account.emails.sort_by_key(|e| std::cmp::Reverse(e.id));
+            }
+            self.reselect_email(selected_id);
+        }
+        restored
+    }
+
+    fn show_folder_picker(&mut self, kind: TransferKind) {
+        if self.get_current_email().is_some() {
+            self.folder_picker.show(kind, self.current_account);
+            self.status_message = format!("{} to folder", kind.display_name());
+        } else {
+            self.status_message = format!("No email selected to {}", kind.display_name().to_lowercase());
+        }
+    }
+
+    fn handle_folder_picker_action(&mut self, action: FolderPickerAction) {
+        match action {
+            FolderPickerAction::Pick { kind, account, folder } => {
+                self.transfer_current_email(kind, account, &folder);
+            }
+            FolderPickerAction::Cancel => {
+                self.status_message = "Cancelled".to_string();
+            }
+        }
+    }
+
+    /// Moves or copies the selected message to a folder, looked up
+    /// case-insensitively in the current account.
+    fn transfer_to_named_folder(&mut self, kind: TransferKind, name: &str) {
+        let folder = self.accounts.get(self.current_account).and_then(|account| {
+            account.folders
+                .iter()
+                .find(|f| f.name.eq_ignore_ascii_case(name))
+                .map(|f| f.name.clone())
+        });
+        match folder {
+            Some(folder) => self.transfer_current_email(kind, self.current_account, &folder),
+            None => self.status_message = format!("No folder named {}", name),
+        }
+    }
+
+    fn transfer_current_email(&mut self, kind: TransferKind, target_account: usize, target_folder: &str) {
+        let (Some(email), Some((account_email, folder))) = (self.get_current_email(), self.viewed_folder.clone()) else {
+            self.status_message = format!("No email selected to {}", kind.display_name().to_lowercase());
+            return;
+        };
+        let Some(target) = self.accounts.get(target_account) else {
+            return;
+        };
+        let target_email = target.email.clone();
+        if target_email == account_email && target_folder == folder {
+            self.status_message = format!("\"{}\" is already in {}", email.subject, folder);
+            return;
+        }
+
+        let (account_email, folder, uid_validity, email_ids, target_account, target_folder) =
+            (account_email, folder, email.uid_validity, vec![email.id], target_email, target_folder.to_string());
+        match kind {
+            TransferKind::Move => {
+                // Off the list right away; TransferFailed puts it back
+                self.remove_pending(&account_email, &folder, &email_ids);
+                self.send_command(AccountCommand::Move { account_email, folder, uid_validity, email_ids, target_account, target_folder: target_folder.clone() });
+                self.status_message = format!("Moving \"{}\" to {}", email.subject, target_folder);
+            }
+            TransferKind::Copy => {
+                self.send_command(AccountCommand::Copy { account_email, folder, uid_validity, email_ids, target_account, target_folder: target_folder.clone() });
+                self.status_message = format!("Copying \"{}\" to {}", email.subject, target_folder);
+            }
+        }
+    }
+
+    fn handle_preview_action(&mut self, action: PreviewAction) {
+        match action {
+            PreviewAction::SaveAttachment(attachment) => self.fetch_attachment(attachment, AttachmentTarget::Save),
+            PreviewAction::OpenAttachment(attachment) => self.fetch_attachment(attachment, AttachmentTarget::Open),
+            PreviewAction::LoadImages(missing) => self.load_images(missing),
+            PreviewAction::ShowPlainText(plain_text) => self.display.prefer_plain_text = plain_text,
+            PreviewAction::LoadRemoteImagesOnce => {
+                if let Some(rendered) = self.display.rendered_html.as_mut() {
+                    rendered.load_remote_once = true;
+                }
+            }
+            PreviewAction::SelectEmail(email_id) => self.reselect_email(Some(email_id)),
+            PreviewAction::LoadBody(email_id) => {
+                let account = self.accounts.get(self.current_account);
+                let email = account.and_then(|account| account.emails.iter().find(|email| email.id == email_id)).cloned();
+                if let (Some(account_email), Some(email)) = (account.map(|account| account.email.clone()), email) {
+                    self.fetch_body(&account_email, &email);
+                }
+            }
+            PreviewAction::EditDraft => {
+                if !self.set_composer_aside() {
+                    return;
+                }
+                if let (Some(email), Some((account_email, folder))) = (self.get_current_email(), self.viewed_folder.clone()) {
+                    self.send_command(AccountCommand::OpenDraft { account_email, folder, uid_validity: email.uid_validity, email_id: email.id });
+                    self.status_message = "Opening draft...".to_string();
+                }
+            }
+            PreviewAction::AllowRemoteImages(sender) => {
+                self.settings.allow_remote_images(&sender);
+                self.settings.save_to_config();
+                self.status_message = format!("Remote images allowed for {}", sender);
+            }
+        }
+    }
+
+    /// Downloads an attachment of the open message to save, open or show it.
+    fn fetch_attachment(&mut self, attachment: Attachment, target: AttachmentTarget) {
+        let (Some(email), Some((account_email, folder))) = (self.get_current_email(), self.viewed_folder.clone()) else {
+            return;
+        };
+        let key = (account_email.clone(), email.id, attachment.part_id.clone());
+        if self.loading.attachments.contains_key(&key) {
+            return;
+        }
+        self.loading.attachments.insert(key, target);
+        if target != AttachmentTarget::Inline {
+            self.status_message = format!("Downloading {}", attachment.filename);
+        }
+        self.send_command(AccountCommand::FetchAttachment {
+            account_email,
+            folder,
+            uid_validity: email.uid_validity,
+            email_id: email.id,
+            attachment,
+        });
+    }
+
+    /// Fetches the images the HTML part shows, once each: `cid:` images
+    /// from the message, remote ones from the web.
+    fn load_images(&mut self, missing: MissingImages) {
+        let (Some(email), Some(rendered)) = (self.get_current_email(), self.display.rendered_html.as_mut()) else {
+            return;
+        };
+        let mut urls = Vec::new();
+        for url in missing.urls {
+            if rendered.load_remote && rendered.requested_images.insert(ImageSource::Remote(url.clone())) {
+                urls.push(url);
+            }
+        }
+        let mut images = Vec::new();
+        for content_id in missing.content_ids {
+            if !rendered.requested_images.insert(ImageSource::Inline(content_id.clone())) {
+                continue;
+            }
+            images.extend(
+                email.inline_images
+                    .iter()
+                    .find(|image| image.content_id.as_deref() == Some(content_id.as_str()))
+                    .cloned(),
+            );
+        }
+        for url in urls {
+            self.send_command(AccountCommand::FetchRemoteImage(url));
+        }
+        for image in images {
+            self.fetch_attachment(image, AttachmentTarget::Inline);
+        }
+    }
+
+    /// Sanitizes the open message's HTML part when the selection changes and
+    /// turns downloaded images into textures.
+    fn refresh_rendered_html(&mut self, ctx: &egui::Context) {
+        let current = self.accounts.get(self.current_account).and_then(|account| {
+            let email = account.emails.get(self.selected_email)?;
+            Some(((account.email.clone(), email.id), email))
+        });
+        let Some((key, email)) = current.filter(|(_, email)| email.html.is_some()) else {
+            self.display.rendered_html = None;
+            return;
+        };
+        if self.display.rendered_html.as_ref().is_none_or(|rendered| rendered.key != key) {
+            let nodes = html::sanitize(email.html.as_deref().unwrap_or_default());
+            self.display.rendered_html = Some(RenderedHtml {
+                key,
+                sender: email.sender_address(),
+                remote_images: remote_content::remote_images(&nodes),
+                nodes,
+                inline_textures: HashMap::new(),
+                remote_textures: HashMap::new(),
+                pending_images: Vec::new(),
+                requested_images: HashSet::new(),
+                load_remote_once: false,
+                load_remote: false,
+            });
+        }
+
+        let Some(rendered) = self.display.rendered_html.as_mut() else {
+            return;
+        };
+        rendered.load_remote = rendered.load_remote_once
+            || rendered.sender.as_deref().is_some_and(|sender| self.settings.allows_remote_images(sender));
+        for (source, data) in std::mem::take(&mut rendered.pending_images) {
+            // Images that fail to decode stay requested and show as placeholders
+            match source {
+                ImageSource::Inline(content_id) => {
+                    let name = format!("cid:{}/{}", rendered.key.1, content_id);
+                    if let Some(texture) = html_view::load_texture(ctx, &name, &data) {
+                        rendered.inline_textures.insert(content_id, texture);
+                    }
+                }
+                ImageSource::Remote(url) => {
+                    if let Some(texture) = html_view::load_texture(ctx, &url, &data) {
+                        rendered.remote_textures.insert(url, texture);
+                    }
+                }
+            }
+        }
+    }
+
+    fn current_account_email(&self) -> Option<String> {
+        self.accounts.get(self.current_account).map(|account| account.email.clone())
+    }
+
+    fn current_folder(&self) -> Option<&Folder> {
+        self.accounts.get(self.current_account)?.folders.get(self.selected_folder)
+    }
+
+    fn handle_emails_action(&mut self, action: EmailsAction) {
+        match action {
+            EmailsAction::ToggleConversations => {
+                self.settings.conversation_view = !self.settings.conversation_view;
+                self.settings.save_to_config();
+                self.status_message = if self.settings.conversation_view {
+                    "Showing conversations".to_string()
+                } else {
+                    "Showing messages".to_string()
+                };
+                self.request_server_threads();
+            }
+        }
+    }
+
+    fn handle_folder_action(&mut self, action: FolderAction) {
+        let Some(account) = self.accounts.get(self.current_account) else {
+            return;
+        };
+        let folder = |index: usize| account.folders.get(index);
+        match action {
+            FolderAction::Create { parent } => {
+                self.folder_dialog.show_create(parent.and_then(folder));
+            }
+            FolderAction::Rename(index) => {
+                if let Some(folder) = folder(index) {
+                    self.folder_dialog.show_rename(folder);
+                }
+            }
+            FolderAction::Delete(index) => {
+                if let Some(folder) = folder(index) {
+                    self.folder_dialog.show_delete(folder);
+                }
+            }
+            FolderAction::SetSubscribed(index, subscribed) => {
+                if let Some(folder) = folder(index).map(|folder| folder.name.clone()) {
+                    self.set_subscribed(folder, subscribed);
+                }
+            }
+            FolderAction::ToggleSubscribedOnly => {
+                self.set_subscribed_only(!self.settings.subscribed_only);
+            }
+            FolderAction::EditOutboxItem(id) => {
+                if self.set_composer_aside() {
+                    self.send_command(AccountCommand::EditOutboxItem(id));
+                }
+            }
+            FolderAction::DeleteOutboxItem(id) => {
+                self.send_command(AccountCommand::DeleteOutboxItem(id));
+                self.status_message = "Message deleted from the outbox".to_string();
+            }
+        }
+    }
+
+    fn handle_folder_dialog_action(&mut self, action: FolderDialogAction) {
+        let Some(account_email) = self.current_account_email() else {
+            return;
+        };
+        match action {
+            FolderDialogAction::Create(path) => {
+                self.status_message = format!("Creating folder {}", path.join("/"));
+                self.send_command(AccountCommand::CreateFolder { account_email, path });
+            }
+            FolderDialogAction::Rename { folder, new_path } => {
+                self.status_message = format!("Renaming folder to {}", new_path.join("/"));
+                self.send_command(AccountCommand::RenameFolder { account_email, folder, new_path });
+            }
+            FolderDialogAction::Delete(folder) => {
+                self.status_message = format!("Deleting folder {}", crate::backend::mailbox_name::decode(&folder));
+                self.send_command(AccountCommand::DeleteFolder { account_email, folder });
+            }
+            FolderDialogAction::Cancel => {
+                self.status_message = "Cancelled".to_string();
+            }
+        }
+    }
+
+    fn set_subscribed(&mut self, folder: String, subscribed: bool) {
+        let Some(account_email) = self.current_account_email() else {
+            return;
+        };
+        let verb = if subscribed { "Subscribing to" } else { "Unsubscribing from" };
+        self.status_message = format!("{} {}", verb, crate::backend::mailbox_name::decode(&folder));
+        self.send_command(AccountCommand::SubscribeFolder { account_email, folder, subscribed });
+    }
+
+    fn set_subscribed_only(&mut self, subscribed_only: bool) {
+        self.settings.subscribed_only = subscribed_only;
+        self.settings.save_to_config();
+        self.status_message = if subscribed_only {
+            "Showing subscribed folders only".to_string()
+        } else {
+            "Showing all folders".to_string()
+        };
+    }
+
     fn show_delete_confirmation(&mut self, email_id: usize) {
-        if let Some(email) = self.get_current_email() {
+        if let (Some(email), Some((_, folder))) = (self.get_current_email(), self.viewed_folder.clone()) {
             self.delete_confirmation = Some(DeleteConfirmation {
+                folder,
+                uid_validity: email.uid_validity,
                 email_id,
                 email_subject: email.subject.clone(),
             });
@@ -424,10 +1634,21 @@ impl MailCrossApp {
     }
 
     fn handle_delete_confirmation(&mut self, confirmed: bool) {
-        if let Some(confirmation) = &self.delete_confirmation {
+        if let Some(confirmation) = self.delete_confirmation.clone() {
             if confirmed {
                 // Delete email through backend
-                self.account_manager.delete_email_by_index(self.current_account, confirmation.email_id);
+                if let Some(account) = self.accounts.get(self.current_account) {
+                    let account_email = account.email.clone();
+                    self.send_command(AccountCommand::DeleteEmail {
+                        account_email: account_email.clone(),
+                        folder: confirmation.folder.clone(),
+                        uid_validity: confirmation.uid_validity,
+                        email_id: confirmation.email_id,
+                    });
+
+                    // Take it off the list right away; DeleteFailed puts it back
+                    self.remove_pending(&account_email, &confirmation.folder, &[confirmation.email_id]);
+                }
                 self.status_message = format!("Deleting email: {}", confirmation.email_subject);
             } else {
                 self.status_message = "Deletion cancelled".to_string();
@@ -444,6 +1665,9 @@ impl MailCrossApp {
                 if !self.settings.get_vim_mode() {
                     self.vim_state.reset();
                 }
+                self.send_command(self.auto_refresh_command());
+                self.request_server_threads();
+                self.settings.save_to_config();
                 self.settings.hide();
                 self.status_message = "Settings applied".to_string();
             }
@@ -475,6 +1699,11 @@ impl MailCrossApp {
 
 }
 
+/// How the unread count changes when a message goes from `was_seen` to `seen`.
+fn unread_change(was_seen: bool, seen: bool) -> isize {
+    was_seen as isize - seen as isize
+}
+
 impl eframe::App for MailCrossApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Hide widget backgrounds and strokes to remove square icons
@@ -492,6 +1721,11 @@ impl eframe::App for MailCrossApp {
         
         // Process backend events
         self.process_events();
+        if let Some(error) = error_log::take().pop() {
+            self.status_message = error;
+        }
+        self.sync_selection();
+        self.refresh_rendered_html(ctx);
         
         // Handle keyboard input
         self.handle_keyboard_input(ctx);
@@ -503,15 +1737,37 @@ impl eframe::App for MailCrossApp {
         }
         
         // Handle composer window
-        let accounts: Vec<&Account> = self.account_manager.get_accounts();
+        let accounts: Vec<&Account> = self.accounts.iter().collect();
         if let Some(action) = self.composer.render(ctx, &accounts) {
             self.handle_composer_action(action);
         }
+        self.autosave_draft(ctx);
+        self.process_composer_messages();
+
+        // Keep undo countdowns and scheduled times ticking while messages are held
+        let now = unix_now();
+        if self.outbox.iter().any(|entry| entry.undo_remaining(now).is_some() || entry.is_scheduled_later(now)) {
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
 
         // Handle settings window
         if let Some(action) = self.settings.render(ctx) {
             self.handle_settings_action(action);
         }
+
+        // Handle move/copy folder picker
+        let accounts: Vec<&Account> = self.accounts.iter().collect();
+        if let Some(action) = self.folder_picker.render(ctx, &accounts) {
+            self.handle_folder_picker_action(action);
+        }
+
+        // Handle folder create/rename/delete dialog
+        if let Some(action) = self.folder_dialog.render(ctx) {
+            self.handle_folder_dialog_action(action);
+        }
+
+        // After everything above that can move messages out of the list
+        self.refresh_threads();
         
         // Top panel for account switching
         egui::TopBottomPanel::top("accounts")
@@ -544,19 +1800,27 @@ impl eframe::App for MailCrossApp {
             LayoutMode::MobilePane => 18.0,
         };
         
-        egui::TopBottomPanel::bottom("status")
+        let status_action = egui::TopBottomPanel::bottom("status")
             .resizable(false)
             .min_height(status_height)
             .show(ctx, |ui| {
                 match layout_mode {
                     LayoutMode::MobilePane | LayoutMode::CompactPane => {
-                        StatusPanel::render_minimal(ui, &self.status_message);
+                        StatusPanel::render_minimal(ui, &self.status_message, &self.outbox)
                     }
                     _ => {
-                        StatusPanel::render(ui, layout_mode.clone(), &self.status_message);
+                        StatusPanel::render(ui, layout_mode.clone(), &self.status_message, &self.outbox)
                     }
                 }
-            });
+            })
+            .inner;
+
+        if let Some(StatusAction::UndoSend(id)) = status_action
+            && self.set_composer_aside()
+        {
+            self.status_message = "Pulling message back into the composer...".to_string();
+            self.send_command(AccountCommand::EditOutboxItem(id));
+        }
 
         // Main content area with responsive layout
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -659,6 +1923,22 @@ impl eframe::App for MailCrossApp {
                                 columns[0].monospace(":set novim");
                                 columns[1].label("Disable vim mode");
                             });
+                            ui.columns(2, |columns| {
+                                columns[0].monospace(":move <folder>");
+                                columns[1].label("Move to folder");
+                            });
+                            ui.columns(2, |columns| {
+                                columns[0].monospace(":copy <folder>");
+                                columns[1].label("Copy to folder");
+                            });
+                            ui.columns(2, |columns| {
+                                columns[0].monospace(":mkdir/:rename <path>");
+                                columns[1].label("Create/rename folder");
+                            });
+                            ui.columns(2, |columns| {
+                                columns[0].monospace(":rmdir");
+                                columns[1].label("Delete current folder");
+                            });
                             ui.columns(2, |columns| {
                                 columns[0].monospace(":help");
                                 columns[1].label("Show this help");
@@ -737,6 +2017,11 @@ impl eframe::App for MailCrossApp {
 
 impl MailCrossApp {
     fn render_three_pane(&mut self, ui: &mut egui::Ui) {
+        let view = PanelView::new(&self.accounts, self.current_account, self.selected_folder, self.selected_email, &self.loading, &self.display, &self.outbox);
+        let subscribed_only = self.settings.subscribed_only;
+        let mut folder_action = None;
+        let mut emails_action = None;
+        let mut preview_action = None;
         ui.horizontal(|ui| {
             ui.spacing_mut().item_spacing.x = ResponsiveLayout::PANEL_SPACING;
             
@@ -749,29 +2034,43 @@ impl MailCrossApp {
             ui.vertical(|ui| {
                 ui.set_width(folder_width);
                 ui.add_space(ResponsiveLayout::INNER_PADDING);
-                FoldersPanel::render(ui, &mut self.selected_folder);
+                folder_action = FoldersPanel::render(ui, &mut self.selected_folder, view.folders, view.folders_loading, subscribed_only, &view.outbox);
             });
 
-            self.render_minimal_separator(ui);
+            Self::render_minimal_separator(ui);
 
             // Middle panel - Email list  
             ui.vertical(|ui| {
                 ui.set_width(email_width);
                 ui.add_space(ResponsiveLayout::INNER_PADDING);
-                EmailsPanel::render_with_search(ui, &mut self.selected_email, &self.search_state);
+                emails_action = EmailsPanel::render_with_search(ui, &mut self.selected_email, view.emails, view.emails_loading, &self.search_state, view.threads);
             });
 
-            self.render_minimal_separator(ui);
+            Self::render_minimal_separator(ui);
 
             // Right panel - Email content (remaining space)
             ui.vertical(|ui| {
                 ui.add_space(ResponsiveLayout::INNER_PADDING);
-                PreviewPanel::render(ui, self.selected_email);
+                preview_action = PreviewPanel::render(ui, view.current_email, &view.preview());
             });
         });
+        if let Some(action) = folder_action {
+            self.handle_folder_action(action);
+        }
+        if let Some(action) = emails_action {
+            self.handle_emails_action(action);
+        }
+        if let Some(action) = preview_action {
+            self.handle_preview_action(action);
+        }
     }
 
     fn render_two_pane(&mut self, ui: &mut egui::Ui) {
+        let view = PanelView::new(&self.accounts, self.current_account, self.selected_folder, self.selected_email, &self.loading, &self.display, &self.outbox);
+        let subscribed_only = self.settings.subscribed_only;
+        let mut folder_action = None;
+        let mut emails_action = None;
+        let mut preview_action = None;
         ui.horizontal(|ui| {
             ui.spacing_mut().item_spacing.x = ResponsiveLayout::PANEL_SPACING;
             
@@ -786,28 +2085,42 @@ impl MailCrossApp {
                 // Folders section (compact horizontal strip)
                 ui.horizontal(|ui| {
                     ui.set_height(80.0);
-                    FoldersPanel::render_compact(ui, &mut self.selected_folder);
+                    folder_action = FoldersPanel::render_compact(ui, &mut self.selected_folder, view.folders, view.folders_loading, subscribed_only, &view.outbox);
                 });
                 
                 ui.add_space(ResponsiveLayout::PANEL_SPACING);
-                self.render_horizontal_separator(ui);
+                Self::render_horizontal_separator(ui);
                 ui.add_space(ResponsiveLayout::PANEL_SPACING);
                 
                 // Emails section
-                EmailsPanel::render_with_search(ui, &mut self.selected_email, &self.search_state);
+                emails_action = EmailsPanel::render_with_search(ui, &mut self.selected_email, view.emails, view.emails_loading, &self.search_state, view.threads);
             });
 
-            self.render_minimal_separator(ui);
+            Self::render_minimal_separator(ui);
 
             // Right side - Email preview
             ui.vertical(|ui| {
                 ui.add_space(ResponsiveLayout::INNER_PADDING);
-                PreviewPanel::render(ui, self.selected_email);
+                preview_action = PreviewPanel::render(ui, view.current_email, &view.preview());
             });
         });
+        if let Some(action) = folder_action {
+            self.handle_folder_action(action);
+        }
+        if let Some(action) = emails_action {
+            self.handle_emails_action(action);
+        }
+        if let Some(action) = preview_action {
+            self.handle_preview_action(action);
+        }
     }
 
     fn render_compact_pane(&mut self, ui: &mut egui::Ui) {
+        let view = PanelView::new(&self.accounts, self.current_account, self.selected_folder, self.selected_email, &self.loading, &self.display, &self.outbox);
+        let subscribed_only = self.settings.subscribed_only;
+        let mut folder_action = None;
+        let mut emails_action = None;
+        let mut preview_action = None;
         // Half vertical layout - stack with balanced proportions
         ui.vertical(|ui| {
             ui.spacing_mut().item_spacing.y = ResponsiveLayout::PANEL_SPACING;
@@ -817,30 +2130,44 @@ impl MailCrossApp {
             ui.horizontal(|ui| {
                 ui.set_height(ResponsiveLayout::calculate_folder_height_compact());
                 ui.add_space(ResponsiveLayout::INNER_PADDING);
-                FoldersPanel::render_compact(ui, &mut self.selected_folder);
+                folder_action = FoldersPanel::render_compact(ui, &mut self.selected_folder, view.folders, view.folders_loading, subscribed_only, &view.outbox);
             });
             
-            self.render_horizontal_separator(ui);
+            Self::render_horizontal_separator(ui);
             
             // Middle: Email list (calculated height)
             ui.vertical(|ui| {
                 let email_height = ResponsiveLayout::calculate_email_height_compact(available_height);
                 ui.set_height(email_height);
                 ui.add_space(ResponsiveLayout::INNER_PADDING);
-                EmailsPanel::render_with_search(ui, &mut self.selected_email, &self.search_state);
+                emails_action = EmailsPanel::render_with_search(ui, &mut self.selected_email, view.emails, view.emails_loading, &self.search_state, view.threads);
             });
             
-            self.render_horizontal_separator(ui);
+            Self::render_horizontal_separator(ui);
             
             // Bottom: Email preview (remaining space)
             ui.vertical(|ui| {
                 ui.add_space(ResponsiveLayout::INNER_PADDING);
-                PreviewPanel::render(ui, self.selected_email);
+                preview_action = PreviewPanel::render(ui, view.current_email, &view.preview());
             });
         });
+        if let Some(action) = folder_action {
+            self.handle_folder_action(action);
+        }
+        if let Some(action) = emails_action {
+            self.handle_emails_action(action);
+        }
+        if let Some(action) = preview_action {
+            self.handle_preview_action(action);
+        }
     }
 
     fn render_mobile_pane(&mut self, ui: &mut egui::Ui) {
+        let view = PanelView::new(&self.accounts, self.current_account, self.selected_folder, self.selected_email, &self.loading, &self.display, &self.outbox);
+        let subscribed_only = self.settings.subscribed_only;
+        let mut folder_action = None;
+        let mut emails_action = None;
+        let mut preview_action = None;
         // Quarter/mobile layout - single column, minimal spacing
         ui.vertical(|ui| {
             ui.spacing_mut().item_spacing.y = ResponsiveLayout::PANEL_SPACING;
@@ -848,7 +2175,7 @@ impl MailCrossApp {
             // Compact folder strip
             ui.horizontal(|ui| {
                 ui.set_height(ResponsiveLayout::calculate_mobile_item_height());
-                FoldersPanel::render_mobile(ui, &mut self.selected_folder);
+                folder_action = FoldersPanel::render_mobile(ui, &mut self.selected_folder, view.folders, view.folders_loading, subscribed_only, &view.outbox);
             });
             
             // Minimal separator
@@ -858,22 +2185,31 @@ impl MailCrossApp {
             if self.search_state.active {
                 ui.vertical(|ui| {
                     ui.set_height(ui.available_height() * 0.4);
-                    EmailsPanel::render_mobile(ui, &mut self.selected_email, &self.search_state);
+                    emails_action = EmailsPanel::render_mobile(ui, &mut self.selected_email, view.emails, view.emails_loading, &self.search_state, view.threads);
                 });
                 
                 ui.add_space(2.0);
                 
                 // Preview takes remaining space
-                PreviewPanel::render_mobile(ui, self.selected_email);
+                preview_action = PreviewPanel::render_mobile(ui, view.current_email, &view.preview());
             } else {
                 // Focus on email list when not searching
-                EmailsPanel::render_mobile_full(ui, &mut self.selected_email);
+                emails_action = EmailsPanel::render_mobile_full(ui, &mut self.selected_email, view.emails, view.emails_loading, view.threads);
             }
         });
+        if let Some(action) = folder_action {
+            self.handle_folder_action(action);
+        }
+        if let Some(action) = emails_action {
+            self.handle_emails_action(action);
+        }
+        if let Some(action) = preview_action {
+            self.handle_preview_action(action);
+        }
     }
 
     // Helper methods for visual consistency
-    fn render_minimal_separator(&self, ui: &mut egui::Ui) {
+    fn render_minimal_separator(ui: &mut egui::Ui) {
         ui.allocate_space(egui::vec2(ResponsiveLayout::SEPARATOR_WIDTH, ui.available_height()));
         let rect = ui.min_rect();
         ui.painter().rect_filled(
@@ -883,7 +2219,7 @@ impl MailCrossApp {
         );
     }
 
-    fn render_horizontal_separator(&self, ui: &mut egui::Ui) {
+    fn render_horizontal_separator(ui: &mut egui::Ui) {
         ui.allocate_space(egui::vec2(ui.available_width(), ResponsiveLayout::SEPARATOR_WIDTH));
         let rect = ui.min_rect();
         ui.painter().rect_filled(
@@ -892,4 +2228,43 @@ impl MailCrossApp {
             ui.visuals().widgets.noninteractive.bg_stroke.color.gamma_multiply(0.3)
         );
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(id: usize, seen: bool) -> Email {
+        let mut email = Email::new(id, "a@example.com", "b@example.com", "Subject", "", "");
+        email.flags.seen = seen;
+        email
+    }
+
+    #[test]
+    fn refreshing_the_list_does_not_mark_other_messages_read() {
+        let mut app = MailCrossApp::new(egui::Context::default());
+        let (command_sender, mut commands) = mpsc::unbounded_channel();
+        let (events, event_receiver) = mpsc::unbounded_channel();
+        app.command_sender = command_sender;
+        app.event_receiver = Some(event_receiver);
+        app.accounts[0].is_connected = true;
+        let account_email = app.accounts[0].email.clone();
+
+        app.sync_selection();
+        events.send(AccountEvent::EmailsUpdated(account_email.clone(), "INBOX".to_string(), vec![email(1, true), email(2, false)])).unwrap();
+        app.process_events();
+        app.selected_email = 0;
+        app.sync_selection();
+        while commands.try_recv().is_ok() {}
+
+        // A new unread message arrives above the open one
+        events.send(AccountEvent::EmailsUpdated(account_email, "INBOX".to_string(), vec![email(3, false), email(1, true), email(2, false)])).unwrap();
+        app.process_events();
+        app.sync_selection();
+
+        assert_eq!(app.get_current_email().map(|e| e.id), Some(1));
+        while let Ok(command) = commands.try_recv() {
+            assert!(!matches!(command, AccountCommand::StoreFlag { .. }), "refresh changed flags");
+        }
+        assert!(app.get_current_emails().iter().any(|e| e.id == 3 && !e.flags.seen));
+    }
+}
@@ -1,34 +1,146 @@
-use crate::backend::{ImapClient, EmailCache, AccountCredentials};
-use crate::types::{Account, Email, Folder};
+use crate::backend::{ImapClient, ImapError, FolderListing, MailStore, FolderSync, StoredFolder, AccountCredentials, SmtpClient, SmtpCredentials, SmtpError, Envelope, TlsMode, MessageBuilder, REPLYING_TO_HEADER, encode_message_ref};
+use crate::backend::{Outbox, OutboxEntry, OutboxState, ParsedMessage, message_ids, parse_outgoing, unix_now};
+use crate::backend::{FolderChanged, MailboxChanges, MailboxWatcher};
+use crate::backend::remote_content;
+use crate::types::{Account, Attachment, Email, EmailBody, EmailFlags, Folder, MessageFlag, MessageRef, OutgoingEmail, ThreadNode};
 use std::collections::HashMap;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
-#[allow(dead_code)] // Will be used for async account operations
+/// NOOP interval for servers without IDLE until the UI configures one
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How often the outbox is checked for messages that are due; short, since
+/// undo windows last seconds
+const OUTBOX_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 pub enum AccountCommand {
     Connect(String), // email
+    #[allow(dead_code)] // Will be used when accounts can be removed
     Disconnect(String), // email  
     RefreshFolders(String), // email
     FetchEmails { account_email: String, folder: String, limit: usize },
+    FetchEmailBody { account_email: String, folder: String, uid_validity: u32, email_id: usize },
+    /// Downloads an attachment, from the store if it was fetched before
+    FetchAttachment { account_email: String, folder: String, uid_validity: u32, email_id: usize, attachment: Attachment },
+    /// Asks the server to thread a folder; answered only if it can
+    FetchThreads { account_email: String, folder: String, uid_validity: u32 },
+    /// Downloads an image an HTML message loads from the web
+    FetchRemoteImage(String), // URL
+    #[allow(dead_code)] // Will be used by the account setup UI
     StoreCredentials { email: String, password: String },
-    DeleteEmail { account_email: String, email_id: usize },
+    DeleteEmail { account_email: String, folder: String, uid_validity: u32, email_id: usize },
+    /// Queues a message in the outbox, held until `send_at` (Unix time):
+    /// the end of the undo window, or the time it is `scheduled` for.
+    /// `replying_to` and `draft_id` are reported back with `EmailSent`, so
+    /// the original can be marked answered and the draft removed
+    SendEmail { account_email: String, message: OutgoingEmail, replying_to: Option<MessageRef>, draft_id: Option<String>, send_at: u64, scheduled: bool },
+    /// Saves a draft under its Message-ID, replacing the versions saved before
+    SaveDraft { account_email: String, message: OutgoingEmail, draft_id: String, replying_to: Option<MessageRef> },
+    /// Downloads a draft whole to continue editing it
+    OpenDraft { account_email: String, folder: String, uid_validity: u32, email_id: usize },
+    DeleteDraft { account_email: String, draft_id: String },
+    /// Takes a message out of the outbox to edit it, answered with `OutboxItemOpened`
+    EditOutboxItem(String), // outbox id
+    DeleteOutboxItem(String), // outbox id
+    StoreFlag { account_email: String, folder: String, uid_validity: u32, email_ids: Vec<usize>, flag: MessageFlag, value: bool },
+    /// Files messages in another folder, possibly of another account
+    Move { account_email: String, folder: String, uid_validity: u32, email_ids: Vec<usize>, target_account: String, target_folder: String },
+    Copy { account_email: String, folder: String, uid_validity: u32, email_ids: Vec<usize>, target_account: String, target_folder: String },
+    /// `path` holds the decoded hierarchy levels of the new folder
+    CreateFolder { account_email: String, path: Vec<String> },
+    RenameFolder { account_email: String, folder: String, new_path: Vec<String> },
+    DeleteFolder { account_email: String, folder: String },
+    SubscribeFolder { account_email: String, folder: String, subscribed: bool },
+    /// Keep the folder shown for this account up to date as it changes on the server
+    WatchFolder { account_email: String, folder: String, limit: usize },
+    ConfigureAutoRefresh { enabled: bool, poll_interval: Duration },
 }
 
-#[allow(dead_code)] // Will be used for UI updates
 pub enum AccountEvent {
     Connected(String),
     Disconnected(String),
     ConnectionFailed(String, String), // email, error
     FoldersUpdated(String, Vec<Folder>), // email, folders
     EmailsUpdated(String, String, Vec<Email>), // email, folder, emails
-    EmailDeleted(String, usize), // account email, email id
+    EmailBodyLoaded(String, String, usize, EmailBody), // account email, folder, email id, body
+    EmailBodyFailed(String, String, usize, String), // account email, folder, email id, error
+    AttachmentLoaded(String, String, usize, Attachment, Vec<u8>), // account email, folder, email id, attachment, content
+    AttachmentFailed(String, String, usize, Attachment, String), // account email, folder, email id, attachment, error
+    ThreadsLoaded(String, String, u32, Vec<ThreadNode>), // account email, folder, UIDVALIDITY, threads by UID
+    RemoteImageLoaded(String, Vec<u8>), // URL, content
+    RemoteImageFailed(String, String), // URL, error
+    EmailDeleted(String, String, usize, Option<String>), // account email, folder, email id, Trash folder it was moved to
+    DeleteFailed(String, String, usize, String), // account email, folder, email id, error
+    FolderInvalidated(String, String), // account email, folder - its UIDVALIDITY changed
+    MailboxChanged(String, String, MailboxChanges), // account email, folder, changes pushed by the server
+    FlagsChanged(String, String, Vec<(usize, EmailFlags)>), // account email, folder, (email id, flags) as stored on the server
+    FlagsFailed(String, String, Vec<(usize, EmailFlags)>, String), // account email, folder, (email id, flags) to restore, error
+    EmailsMoved(String, String, Vec<usize>, String, String), // account email, folder, email ids, target account, target folder
+    EmailsCopied(String, String, Vec<usize>, String, String), // account email, folder, email ids, target account, target folder
+    TransferFailed(String, String, Vec<usize>, String), // account email, folder, email ids, error
+    FolderOperationFailed(String, String), // account email, error
+    /// A folder is listed, but its counts could not be read
+    FolderStatusFailed(String, String, String), // account email, folder, error
+    EmailSent(String, String, Option<MessageRef>, Option<String>), // account email, subject, message replied to, draft it was sent from
+    DraftSaved(String, String), // account email, Drafts folder
+    DraftLoaded(String, Vec<u8>), // account email, message
+    DraftFailed(String, String), // account email, error
+    SendFailed(String, String, bool), // account email, error, whether it is retried later
+    OutboxChanged(Vec<OutboxEntry>),
+    OutboxItemOpened(OutboxEntry, Vec<u8>), // entry, message
+    /// An outbox message could not be taken back: it is being or has been sent
+    OutboxItemTooLate(Option<String>), // subject, if it is still being sent
+    /// A sent message could not be copied to Sent; it stays queued
+    SentCopyFailed(String, String), // account email, error
 }
 
 #[allow(dead_code)] // Backend account management infrastructure
 pub struct AccountManager {
     accounts: HashMap<String, Account>,
     imap_client: ImapClient,
-    email_cache: EmailCache,
+    mail_store: MailStore,
     event_sender: Option<mpsc::UnboundedSender<AccountEvent>>,
+    /// Per account: the folder kept up to date, and its watcher while connected
+    watched_folders: HashMap<String, WatchedFolder>,
+    auto_refresh: bool,
+    poll_interval: Duration,
+    change_sender: Option<mpsc::UnboundedSender<FolderChanged>>,
+    outbox: Outbox,
+    outcome_sender: Option<mpsc::UnboundedSender<SendOutcome>>,
+}
+
+/// How an attempt to send an outbox message ended; on success, with the
+/// exact bytes the server accepted
+struct SendOutcome {
+    id: String,
+    result: Result<Vec<u8>, SmtpError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Transfer {
+    Move,
+    Copy,
+}
+
+/// The messages a transfer got through, and how: a move whose originals
+/// could not be removed ends up a copy
+struct TransferOutcome {
+    done: Vec<usize>,
+    transfer: Transfer,
+    result: Result<(), ImapError>,
+}
+
+impl TransferOutcome {
+    fn failed(transfer: Transfer, error: ImapError) -> Self {
+        Self { done: Vec::new(), transfer, result: Err(error) }
+    }
+}
+
+struct WatchedFolder {
+    folder: String,
+    limit: usize,
+    watcher: Option<MailboxWatcher>,
 }
 
 impl AccountManager {
@@ -36,8 +148,14 @@ impl AccountManager {
         Self {
             accounts: HashMap::new(),
             imap_client: ImapClient::new(),
-            email_cache: EmailCache::new(),
+            mail_store: MailStore::open_default(),
             event_sender: None,
+            watched_folders: HashMap::new(),
+            auto_refresh: true,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            change_sender: None,
+            outbox: Outbox::open_default(),
+            outcome_sender: None,
         }
     }
 
@@ -59,18 +177,48 @@ impl AccountManager {
         self.accounts.get_mut(email)
     }
 
-    pub fn get_accounts(&self) -> Vec<&Account> {
-        self.accounts.values().collect()
-    }
-
     #[allow(dead_code)] // Will be used for account removal
     pub fn remove_account(&mut self, email: &str) -> Option<Account> {
+        self.watched_folders.remove(email);
         self.imap_client.disconnect(email);
-        self.email_cache.clear_account(email);
+        self.mail_store.clear_account(email);
         self.accounts.remove(email)
     }
 
-    #[allow(dead_code)] // Will be used for command processing
+    /// Worker loop: processes commands until every sender is dropped. Meant
+    /// to be spawned on a multi-threaded tokio runtime.
+    pub async fn run(mut self, mut commands: mpsc::UnboundedReceiver<AccountCommand>) {
+        let (change_sender, mut changes) = mpsc::unbounded_channel();
+        self.change_sender = Some(change_sender);
+        let (outcome_sender, mut outcomes) = mpsc::unbounded_channel();
+        self.outcome_sender = Some(outcome_sender);
+        // Messages left in the outbox by the last run
+        self.report_outbox();
+        let mut outbox_timer = tokio::time::interval(OUTBOX_CHECK_INTERVAL);
+
+        loop {
+            // The imap crate is blocking; tell the runtime so other tasks keep
+            // being scheduled while a command waits on the network
+            tokio::select! {
+                command = commands.recv() => {
+                    let Some(command) = command else { break };
+                    tokio::task::block_in_place(|| {
+                        tokio::runtime::Handle::current().block_on(self.handle_command(command))
+                    });
+                }
+                Some(change) = changes.recv() => {
+                    tokio::task::block_in_place(|| self.sync_changed_folder(&change));
+                }
+                Some(outcome) = outcomes.recv() => {
+                    tokio::task::block_in_place(|| self.finish_sending(outcome));
+                }
+                _ = outbox_timer.tick() => {
+                    tokio::task::block_in_place(|| self.process_outbox());
+                }
+            }
+        }
+    }
+
     pub async fn handle_command(&mut self, command: AccountCommand) {
         match command {
             AccountCommand::Connect(email) => {
@@ -85,18 +233,91 @@ impl AccountManager {
             AccountCommand::FetchEmails { account_email, folder, limit } => {
                 self.fetch_emails(&account_email, &folder, limit).await;
             }
+            AccountCommand::FetchEmailBody { account_email, folder, uid_validity, email_id } => {
+                self.fetch_email_body(&account_email, &folder, uid_validity, email_id).await;
+            }
+            AccountCommand::FetchAttachment { account_email, folder, uid_validity, email_id, attachment } => {
+                self.fetch_attachment(&account_email, &folder, uid_validity, email_id, attachment).await;
+            }
+            AccountCommand::FetchThreads { account_email, folder, uid_validity } => {
+                self.fetch_threads(&account_email, &folder, uid_validity);
+            }
+            AccountCommand::FetchRemoteImage(url) => {
+                self.fetch_remote_image(url);
+            }
             AccountCommand::StoreCredentials { email, password } => {
                 if let Err(e) = self.imap_client.store_credentials(&email, &password) {
                     self.send_event(AccountEvent::ConnectionFailed(email, format!("Failed to store credentials: {:?}", e)));
                 }
             }
-            AccountCommand::DeleteEmail { account_email, email_id } => {
-                self.delete_email(&account_email, email_id).await;
+            AccountCommand::DeleteEmail { account_email, folder, uid_validity, email_id } => {
+                self.delete_email(&account_email, &folder, uid_validity, email_id).await;
+            }
+            AccountCommand::SendEmail { account_email, message, replying_to, draft_id, send_at, scheduled } => {
+                let entry = OutboxEntry::new(&account_email, &message.subject, &Self::recipients_line(&message))
+                    .hold_until(send_at, scheduled);
+                self.queue_email(entry, message, replying_to, draft_id);
+            }
+            AccountCommand::SaveDraft { account_email, message, draft_id, replying_to } => {
+                self.save_draft(&account_email, &message, &draft_id, replying_to.as_ref());
+            }
+            AccountCommand::OpenDraft { account_email, folder, uid_validity, email_id } => {
+                self.open_draft(&account_email, &folder, uid_validity, email_id);
+            }
+            AccountCommand::EditOutboxItem(id) => {
+                if let Some((entry, content)) = self.outbox.remove(&id) {
+                    self.report_outbox();
+                    self.send_event(AccountEvent::OutboxItemOpened(entry, content));
+                } else {
+                    self.report_too_late(&id);
+                }
+            }
+            AccountCommand::DeleteOutboxItem(id) => {
+                if self.outbox.remove(&id).is_some() {
+                    self.report_outbox();
+                } else {
+                    self.report_too_late(&id);
+                }
+            }
+            AccountCommand::DeleteDraft { account_email, draft_id } => {
+                if let Err(e) = self.imap_client.delete_draft(&account_email, &draft_id) {
+                    self.send_event(AccountEvent::DraftFailed(account_email, e.to_string()));
+                }
+            }
+            AccountCommand::StoreFlag { account_email, folder, uid_validity, email_ids, flag, value } => {
+                self.store_flag(&account_email, &folder, uid_validity, &email_ids, &flag, value);
+            }
+            AccountCommand::Move { account_email, folder, uid_validity, email_ids, target_account, target_folder } => {
+                self.transfer_emails(&account_email, &folder, uid_validity, &email_ids, &target_account, &target_folder, Transfer::Move);
+            }
+            AccountCommand::Copy { account_email, folder, uid_validity, email_ids, target_account, target_folder } => {
+                self.transfer_emails(&account_email, &folder, uid_validity, &email_ids, &target_account, &target_folder, Transfer::Copy);
+            }
+            AccountCommand::CreateFolder { account_email, path } => {
+                let result = self.imap_client.create_folder(&account_email, &path).map(|_| ());
+                self.finish_folder_operation(&account_email, result).await;
+            }
+            AccountCommand::RenameFolder { account_email, folder, new_path } => {
+                let result = self.imap_client.rename_folder(&account_email, &folder, &new_path).map(|_| ());
+                self.finish_folder_operation(&account_email, result).await;
+            }
+            AccountCommand::DeleteFolder { account_email, folder } => {
+                let result = self.imap_client.delete_folder(&account_email, &folder);
+                self.finish_folder_operation(&account_email, result).await;
+            }
+            AccountCommand::SubscribeFolder { account_email, folder, subscribed } => {
+                let result = self.imap_client.set_subscribed(&account_email, &folder, subscribed);
+                self.finish_folder_operation(&account_email, result).await;
+            }
+            AccountCommand::WatchFolder { account_email, folder, limit } => {
+                self.watch_folder(&account_email, &folder, limit);
+            }
+            AccountCommand::ConfigureAutoRefresh { enabled, poll_interval } => {
+                self.configure_auto_refresh(enabled, poll_interval);
             }
         }
     }
 
-    #[allow(dead_code)] // Will be used for account connections
     async fn connect_account(&mut self, email: &str) {
         if let Some(account) = self.accounts.get(email).cloned() {
             if account.server.is_empty() {
@@ -104,6 +325,16 @@ impl AccountManager {
                 return;
             }
 
+            // Show what is stored while the connection is being set up, and
+            // keep it available if the server cannot be reached
+            let stored_folders = self.mail_store.get_folders(email);
+            if !stored_folders.is_empty() {
+                if let Some(account) = self.accounts.get_mut(email) {
+                    account.folders = stored_folders.clone();
+                }
+                self.send_event(AccountEvent::FoldersUpdated(email.to_string(), stored_folders));
+            }
+
             let credentials = AccountCredentials {
                 email: account.email.clone(),
                 server: account.server.clone(),
@@ -119,16 +350,23 @@ impl AccountManager {
                     self.send_event(AccountEvent::Connected(email.to_string()));
                     // Auto-refresh folders after successful connection
                     self.refresh_folders(email).await;
+                    self.start_watcher(email);
+                    self.flush_sent_copies(email);
+                    // The network is back: no need to wait out the backoff
+                    self.outbox.retry_now();
+                    self.process_outbox();
                 }
                 Err(e) => {
-                    self.send_event(AccountEvent::ConnectionFailed(email.to_string(), format!("{:?}", e)));
+                    self.send_event(AccountEvent::ConnectionFailed(email.to_string(), e.to_string()));
                 }
             }
         }
     }
 
-    #[allow(dead_code)] // Will be used for disconnections
     fn disconnect_account(&mut self, email: &str) {
+        if let Some(watched) = self.watched_folders.get_mut(email) {
+            watched.watcher = None;
+        }
         self.imap_client.disconnect(email);
         if let Some(account) = self.accounts.get_mut(email) {
             account.is_connected = false;
@@ -136,19 +374,24 @@ impl AccountManager {
         self.send_event(AccountEvent::Disconnected(email.to_string()));
     }
 
-    #[allow(dead_code)] // Will be used for folder syncing
     async fn refresh_folders(&mut self, email: &str) {
         match self.imap_client.get_folders(email) {
-            Ok(folders) => {
+            Ok(FolderListing { folders, status_failures }) => {
+                for (folder, e) in status_failures {
+                    self.send_event(AccountEvent::FolderStatusFailed(email.to_string(), folder, e.to_string()));
+                }
+
                 // Update account folders
                 if let Some(account) = self.accounts.get_mut(email) {
                     account.folders = folders.clone();
                 }
-                
-                // Store in cache
+
                 for folder in &folders {
-                    self.email_cache.store_folder(email, folder.clone(), Vec::new());
+                    if self.mail_store.invalidate_if_uid_validity_changed(email, &folder.name, folder.uid_validity) {
+                        self.send_event(AccountEvent::FolderInvalidated(email.to_string(), folder.name.clone()));
+                    }
                 }
+                self.mail_store.store_folders(email, &folders);
 
                 self.send_event(AccountEvent::FoldersUpdated(email.to_string(), folders));
             }
@@ -158,56 +401,582 @@ impl AccountManager {
         }
     }
 
-    #[allow(dead_code)] // Will be used for email fetching
+    /// Reports the outcome of a folder operation. Success is reported
+    /// through the refreshed folder list.
+    async fn finish_folder_operation(&mut self, email: &str, result: Result<(), ImapError>) {
+        match result {
+            Ok(()) => self.refresh_folders(email).await,
+            Err(e) => self.send_event(AccountEvent::FolderOperationFailed(email.to_string(), e.to_string())),
+        }
+    }
+
     async fn fetch_emails(&mut self, email: &str, folder: &str, limit: usize) {
-        // Check cache first
-        if let Some(cached_emails) = self.email_cache.get_emails(email, folder) {
-            self.send_event(AccountEvent::EmailsUpdated(email.to_string(), folder.to_string(), cached_emails));
+        // Serve stored messages right away, then bring them up to date
+        let stored = self.mail_store.get_folder(email, folder).cloned();
+        if let Some(stored) = &stored {
+            if let Some(account) = self.accounts.get_mut(email) {
+                account.emails = stored.emails.clone();
+            }
+            self.send_event(AccountEvent::EmailsUpdated(email.to_string(), folder.to_string(), stored.emails.clone()));
+        }
+        if !self.imap_client.is_connected(email) {
+            if stored.is_none() {
+                self.send_event(AccountEvent::ConnectionFailed(email.to_string(), format!("{} is not available offline", folder)));
+            }
+            return;
+        }
+
+        match self.sync_and_store(email, folder, stored.as_ref(), limit) {
+            Ok(FolderSync { emails, .. }) => {
+                let changed = stored.as_ref().is_none_or(|stored| stored.emails != emails);
+                if changed {
+                    self.send_event(AccountEvent::EmailsUpdated(email.to_string(), folder.to_string(), emails));
+                }
+            }
+            Err(e) => {
+                self.send_event(AccountEvent::ConnectionFailed(email.to_string(), format!("Failed to fetch emails: {:?}", e)));
+            }
+        }
+    }
+
+    /// Brings a folder up to date from its stored state and persists the result.
+    fn sync_and_store(&mut self, email: &str, folder: &str, stored: Option<&StoredFolder>, limit: usize) -> Result<FolderSync, ImapError> {
+        let synced = self.imap_client.sync_folder(email, folder, stored, limit)?;
+
+        if let Some(account) = self.accounts.get_mut(email) {
+            account.emails = synced.emails.clone();
+            if let Some(folder_obj) = account.folders.iter_mut().find(|f| f.name == folder) {
+                folder_obj.uid_validity = synced.sync.uid_validity;
+            }
+        }
+
+        // Persist, replacing any messages from an older UIDVALIDITY
+        if let Some(folder_obj) = self.accounts.get(email)
+            .and_then(|acc| acc.folders.iter().find(|f| f.name == folder)) {
+            self.mail_store.store_synced_folder(email, folder_obj.clone(), synced.emails.clone(), synced.sync.clone());
+        }
+        Ok(synced)
+    }
+
+    /// Records the folder to keep up to date for an account and (re)starts
+    /// its watcher.
+    fn watch_folder(&mut self, email: &str, folder: &str, limit: usize) {
+        if let Some(watched) = self.watched_folders.get_mut(email)
+            && watched.folder == folder
+            && watched.watcher.is_some() {
+            watched.limit = limit;
+            return;
+        }
+        self.watched_folders.insert(email.to_string(), WatchedFolder {
+            folder: folder.to_string(),
+            limit,
+            watcher: None,
+        });
+        self.start_watcher(email);
+    }
+
+    fn configure_auto_refresh(&mut self, enabled: bool, poll_interval: Duration) {
+        if enabled == self.auto_refresh && poll_interval == self.poll_interval {
+            return;
+        }
+        self.auto_refresh = enabled;
+        self.poll_interval = poll_interval;
+
+        let accounts: Vec<String> = self.watched_folders.keys().cloned().collect();
+        for email in accounts {
+            if let Some(watched) = self.watched_folders.get_mut(&email) {
+                watched.watcher = None;
+            }
+            self.start_watcher(&email);
+        }
+    }
+
+    /// Starts watching the account's watched folder if auto-refresh is on and
+    /// the account is connected. Replaces any previous watcher.
+    fn start_watcher(&mut self, email: &str) {
+        let Some(watched) = self.watched_folders.get_mut(email) else {
+            return;
+        };
+        watched.watcher = None;
+
+        let Some(account) = self.accounts.get(email) else {
+            return;
+        };
+        let Some(change_sender) = self.change_sender.clone() else {
+            return;
+        };
+        if !self.auto_refresh || !self.imap_client.is_connected(email) {
+            return;
+        }
+
+        let credentials = AccountCredentials {
+            email: account.email.clone(),
+            server: account.server.clone(),
+            port: account.port,
+            use_tls: account.use_tls,
+        };
+        let folder = watched.folder.clone();
+        match self.imap_client.watch_folder(&credentials, &folder, self.poll_interval, change_sender) {
+            Ok(watcher) => {
+                if let Some(watched) = self.watched_folders.get_mut(email) {
+                    watched.watcher = Some(watcher);
+                }
+            }
+            Err(e) => {
+                self.send_event(AccountEvent::ConnectionFailed(email.to_string(), format!("Failed to watch {}: {}", folder, e)));
+            }
+        }
+    }
+
+    /// Called when a watcher reports a change: syncs the folder and tells the
+    /// UI what changed.
+    fn sync_changed_folder(&mut self, change: &FolderChanged) {
+        let Some(watched) = self.watched_folders.get(&change.account_email) else {
+            return;
+        };
+        // Notice from a watcher that has since been replaced
+        if watched.folder != change.folder || !self.imap_client.is_connected(&change.account_email) {
+            return;
+        }
+        let limit = watched.limit;
+        let (email, folder) = (change.account_email.as_str(), change.folder.as_str());
+
+        let stored = self.mail_store.get_folder(email, folder).cloned();
+        match self.sync_and_store(email, folder, stored.as_ref(), limit) {
+            Ok(FolderSync { emails, sync }) => {
+                let before = match &stored {
+                    // UIDs from before the change cannot be compared
+                    Some(stored) if stored.sync.uid_validity != sync.uid_validity => {
+                        self.send_event(AccountEvent::FolderInvalidated(email.to_string(), folder.to_string()));
+                        return;
+                    }
+                    Some(stored) => stored.emails.as_slice(),
+                    None => &[],
+                };
+                let changes = MailboxChanges::between(before, &emails);
+                if !changes.is_empty() {
+                    self.send_event(AccountEvent::MailboxChanged(email.to_string(), folder.to_string(), changes));
+                }
+            }
+            Err(e) => {
+                self.send_event(AccountEvent::ConnectionFailed(email.to_string(), format!("Failed to refresh {}: {}", folder, e)));
+            }
+        }
+    }
+
+    async fn fetch_email_body(&mut self, email: &str, folder: &str, uid_validity: u32, email_id: usize) {
+        if let Some(body) = self.mail_store.get_email_body(email, folder, uid_validity, email_id) {
+            self.send_event(AccountEvent::EmailBodyLoaded(email.to_string(), folder.to_string(), email_id, body));
             return;
         }
 
-        // Fetch from IMAP
-        match self.imap_client.get_emails(email, folder, limit) {
-            Ok(emails) => {
-                // Update account emails
+        match self.imap_client.get_email_body(email, folder, uid_validity, email_id) {
+            Ok(body) => {
+                if let Some(cached) = self.accounts.get_mut(email)
+                    .and_then(|acc| acc.emails.iter_mut().find(|e| e.id == email_id && e.uid_validity == uid_validity)) {
+                    cached.body = body.text.clone();
+                    cached.body_loaded = true;
+                    cached.attachments = body.attachments.clone();
+                }
+                self.mail_store.store_email_body(email, folder, uid_validity, email_id, &body);
+                self.send_event(AccountEvent::EmailBodyLoaded(email.to_string(), folder.to_string(), email_id, body));
+            }
+            Err(e) => {
+                self.invalidate_on_uid_validity_change(email, &e);
+                self.send_event(AccountEvent::EmailBodyFailed(email.to_string(), folder.to_string(), email_id, e.to_string()));
+            }
+        }
+    }
+
+    async fn fetch_attachment(&mut self, email: &str, folder: &str, uid_validity: u32, email_id: usize, attachment: Attachment) {
+        let section = attachment.part_id.as_str();
+        if let Some(content) = self.mail_store.get_email_part(email, folder, uid_validity, email_id, section) {
+            self.send_event(AccountEvent::AttachmentLoaded(email.to_string(), folder.to_string(), email_id, attachment, content));
+            return;
+        }
+
+        match self.imap_client.fetch_part(email, folder, uid_validity, email_id, section) {
+            Ok(content) => {
+                self.mail_store.store_email_part(email, folder, uid_validity, email_id, section, &content);
+                self.send_event(AccountEvent::AttachmentLoaded(email.to_string(), folder.to_string(), email_id, attachment, content));
+            }
+            Err(e) => {
+                self.invalidate_on_uid_validity_change(email, &e);
+                self.send_event(AccountEvent::AttachmentFailed(email.to_string(), folder.to_string(), email_id, attachment, e.to_string()));
+            }
+        }
+    }
+
+    /// Server-side threading is only a shortcut, so failures are not
+    /// reported; the UI threads the messages itself meanwhile.
+    fn fetch_threads(&mut self, email: &str, folder: &str, uid_validity: u32) {
+        match self.imap_client.fetch_threads(email, folder, uid_validity) {
+            Ok(Some(threads)) => {
+                self.send_event(AccountEvent::ThreadsLoaded(email.to_string(), folder.to_string(), uid_validity, threads));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                // The UI threads the folder itself without the server's answer
+                self.invalidate_on_uid_validity_change(email, &e);
+            }
+        }
+    }
+
+    async fn delete_email(&mut self, email: &str, folder: &str, uid_validity: u32, email_id: usize) {
+        match self.imap_client.delete_email(email, folder, uid_validity, email_id) {
+            Ok(trash) => {
                 if let Some(account) = self.accounts.get_mut(email) {
-                    account.emails = emails.clone();
+                    account.emails.retain(|e| !(e.id == email_id && e.uid_validity == uid_validity));
                 }
+                self.mail_store.remove_email(email, folder, uid_validity, email_id);
+                self.send_event(AccountEvent::EmailDeleted(email.to_string(), folder.to_string(), email_id, trash));
+            }
+            Err(e) => {
+                self.invalidate_on_uid_validity_change(email, &e);
+                self.send_event(AccountEvent::DeleteFailed(email.to_string(), folder.to_string(), email_id, e.to_string()));
+            }
+        }
+    }
 
-                // Store in cache
-                if let Some(folder_obj) = self.accounts.get(email)
-                    .and_then(|acc| acc.folders.iter().find(|f| f.name == folder)) {
-                    self.email_cache.store_folder(email, folder_obj.clone(), emails.clone());
+    fn store_flag(&mut self, email: &str, folder: &str, uid_validity: u32, email_ids: &[usize], flag: &MessageFlag, value: bool) {
+        match self.imap_client.store_flag(email, folder, uid_validity, email_ids, flag, value) {
+            Ok(updated) => {
+                if let Some(account) = self.accounts.get_mut(email) {
+                    for (email_id, flags) in &updated {
+                        if let Some(cached) = account.emails.iter_mut().find(|e| e.id == *email_id && e.uid_validity == uid_validity) {
+                            cached.flags = flags.clone();
+                        }
+                    }
                 }
+                self.mail_store.update_flags(email, folder, uid_validity, &updated);
+                self.send_event(AccountEvent::FlagsChanged(email.to_string(), folder.to_string(), updated));
+            }
+            Err(e) => {
+                self.invalidate_on_uid_validity_change(email, &e);
+                // The UI already shows the change; hand back what was stored
+                // before so it can undo it
+                let previous = self.mail_store.get_folder(email, folder)
+                    .filter(|stored| stored.sync.uid_validity == uid_validity)
+                    .map(|stored| {
+                        stored.emails.iter()
+                            .filter(|e| email_ids.contains(&e.id))
+                            .map(|e| (e.id, e.flags.clone()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                self.send_event(AccountEvent::FlagsFailed(email.to_string(), folder.to_string(), previous, e.to_string()));
+            }
+        }
+    }
 
-                self.send_event(AccountEvent::EmailsUpdated(email.to_string(), folder.to_string(), emails));
+    #[allow(clippy::too_many_arguments)]
+    fn transfer_emails(
+        &mut self,
+        email: &str,
+        folder: &str,
+        uid_validity: u32,
+        email_ids: &[usize],
+        target_account: &str,
+        target_folder: &str,
+        transfer: Transfer,
+    ) {
+        let outcome = if target_account != email {
+            self.transfer_across_accounts(email, folder, uid_validity, email_ids, target_account, target_folder, transfer)
+        } else {
+            let result = match transfer {
+                Transfer::Move => self.imap_client.move_emails(email, folder, uid_validity, email_ids, target_folder),
+                Transfer::Copy => self.imap_client.copy_emails(email, folder, uid_validity, email_ids, target_folder),
+            };
+            let done = if result.is_ok() { email_ids.to_vec() } else { Vec::new() };
+            TransferOutcome { done, transfer, result }
+        };
+
+        if !outcome.done.is_empty() {
+            self.finish_transfer(email, folder, uid_validity, &outcome.done, target_account, target_folder, outcome.transfer);
+        }
+        if let Err(e) = outcome.result {
+            self.invalidate_on_uid_validity_change(email, &e);
+            // A move that ended up a copy left every original in place
+            let failed: Vec<usize> = email_ids
+                .iter()
+                .copied()
+                .filter(|id| outcome.transfer != transfer || !outcome.done.contains(id))
+                .collect();
+            let error = if outcome.done.is_empty() || outcome.transfer != transfer {
+                e.to_string()
+            } else {
+                format!("{} of {} were transferred before: {}", outcome.done.len(), email_ids.len(), e)
+            };
+            self.send_event(AccountEvent::TransferFailed(email.to_string(), folder.to_string(), failed, error));
+        }
+    }
+
+    /// Updates the store and counts for transferred messages and reports them.
+    #[allow(clippy::too_many_arguments)]
+    fn finish_transfer(
+        &mut self,
+        email: &str,
+        folder: &str,
+        uid_validity: u32,
+        email_ids: &[usize],
+        target_account: &str,
+        target_folder: &str,
+        transfer: Transfer,
+    ) {
+        let moved = email_ids.len() as isize;
+        let unread = self.mail_store
+            .get_folder(email, folder)
+            .map(|stored| stored.emails.iter().filter(|e| email_ids.contains(&e.id) && !e.flags.seen).count())
+            .unwrap_or(0) as isize;
+        if transfer == Transfer::Move {
+            if let Some(account) = self.accounts.get_mut(email) {
+                account.emails.retain(|e| !(email_ids.contains(&e.id) && e.uid_validity == uid_validity));
+            }
+            for &email_id in email_ids {
+                self.mail_store.remove_email(email, folder, uid_validity, email_id);
+            }
+            self.adjust_folder_count(email, folder, -moved, -unread);
+        }
+        self.adjust_folder_count(target_account, target_folder, moved, unread);
+
+        let event = match transfer {
+            Transfer::Move => AccountEvent::EmailsMoved,
+            Transfer::Copy => AccountEvent::EmailsCopied,
+        };
+        self.send_event(event(email.to_string(), folder.to_string(), email_ids.to_vec(), target_account.to_string(), target_folder.to_string()));
+    }
+
+    /// FETCHes the messages whole and APPENDs them to the other account;
+    /// a move then removes the originals. If an APPEND fails, the messages
+    /// appended before it still count as transferred, and only those are
+    /// removed from the source.
+    #[allow(clippy::too_many_arguments)]
+    fn transfer_across_accounts(
+        &mut self,
+        email: &str,
+        folder: &str,
+        uid_validity: u32,
+        email_ids: &[usize],
+        target_account: &str,
+        target_folder: &str,
+        transfer: Transfer,
+    ) -> TransferOutcome {
+        if !self.imap_client.is_connected(target_account) {
+            return TransferOutcome::failed(transfer, ImapError::Connection(format!("{} is not connected", target_account)));
+        }
+        let messages = match self.imap_client.fetch_raw_emails(email, folder, uid_validity, email_ids) {
+            Ok(messages) => messages,
+            Err(e) => return TransferOutcome::failed(transfer, e),
+        };
+        let mut copied = Vec::new();
+        let mut result = Ok(());
+        for message in &messages {
+            if let Err(e) = self.imap_client.append_email(target_account, target_folder, message) {
+                result = Err(e);
+                break;
+            }
+            copied.push(message.uid);
+        }
+        settle_cross_account(transfer, email_ids.len(), target_folder, copied, result, |uids| {
+            self.imap_client.expunge_emails(email, folder, uid_validity, uids)
+        })
+    }
+
+    fn adjust_folder_count(&mut self, email: &str, folder: &str, total: isize, unread: isize) {
+        let Some(account) = self.accounts.get_mut(email) else {
+            return;
+        };
+        if let Some(folder_obj) = account.folders.iter_mut().find(|f| f.name == folder) {
+            folder_obj.total = folder_obj.total.saturating_add_signed(total);
+            folder_obj.unread = folder_obj.unread.saturating_add_signed(unread);
+            let folders = account.folders.clone();
+            self.mail_store.store_folders(email, &folders);
+        }
+    }
+
+    /// UIDs listed before a UIDVALIDITY change may now name other messages, so
+    /// the folder's stored messages are dropped and the UI told to reload it.
+    fn invalidate_on_uid_validity_change(&mut self, email: &str, error: &ImapError) {
+        if let ImapError::UidValidityChanged(folder) = error {
+            self.mail_store.clear_folder(email, folder);
+            self.send_event(AccountEvent::FolderInvalidated(email.to_string(), folder.clone()));
+        }
+    }
+
+    /// Puts a message in the outbox and sends it if it is due; the outcome
+    /// is reported as `EmailSent` or `SendFailed`.
+    fn queue_email(&mut self, mut entry: OutboxEntry, message: OutgoingEmail, replying_to: Option<MessageRef>, draft_id: Option<String>) {
+        entry.replying_to = replying_to;
+        entry.draft_id = draft_id;
+        // Kept with Bcc so that it can be edited again
+        let built = MessageBuilder::new(&message).include_bcc(true).build();
+        self.outbox.push(entry, &built.bytes);
+        self.report_outbox();
+        self.process_outbox();
+    }
+
+    fn recipients_line(message: &OutgoingEmail) -> String {
+        message.to.iter().map(|mailbox| mailbox.to_string()).collect::<Vec<_>>().join(", ")
+    }
+
+    fn report_too_late(&self, id: &str) {
+        let subject = self.outbox.get(id).map(|entry| entry.subject.clone());
+        self.send_event(AccountEvent::OutboxItemTooLate(subject));
+    }
+
+    /// Starts sending every outbox message that is due.
+    fn process_outbox(&mut self) {
+        for id in self.outbox.due(unix_now()) {
+            self.start_sending(&id);
+        }
+    }
+
+    /// Submits an outbox message over SMTP on a background thread, rebuilt
+    /// without Bcc and dated now; the outcome comes back to `finish_sending`.
+    fn start_sending(&mut self, id: &str) {
+        let (Some(entry), Some(content)) = (self.outbox.get(id).cloned(), self.outbox.content(id)) else {
+            return;
+        };
+        let parsed = ParsedMessage::parse(&content);
+        let message = match (self.accounts.get(&entry.account_email), parse_outgoing(&parsed)) {
+            (None, _) => Err("Unknown account".to_string()),
+            (Some(account), _) if account.smtp_server.is_empty() => Err("SMTP server not configured".to_string()),
+            (Some(_), Err(e)) => Err(e.to_string()),
+            (Some(account), Ok(message)) => Ok((account.clone(), message)),
+        };
+        let (account, message) = match message {
+            Ok(sendable) => sendable,
+            Err(error) => {
+                self.outbox.record_failure(id, &error, true, unix_now());
+                self.report_outbox();
+                self.send_event(AccountEvent::SendFailed(entry.account_email, error, false));
+                return;
+            }
+        };
+
+        let credentials = SmtpCredentials {
+            email: account.email.clone(),
+            server: account.smtp_server.clone(),
+            port: account.smtp_port,
+            tls_mode: TlsMode::for_smtp_port(account.smtp_port),
+        };
+        let envelope = Envelope {
+            from: account.email.clone(),
+            recipients: message.envelope_recipients(),
+        };
+        // The same Message-ID on every attempt, so that a message delivered
+        // twice is recognized as one
+        let mut builder = MessageBuilder::new(&message);
+        let message_id = parsed.message_id().and_then(|value| message_ids(&value).into_iter().next());
+        if let Some(message_id) = &message_id {
+            builder = builder.message_id(message_id);
+        }
+        let built = builder.build();
+
+        self.outbox.set_sending(id);
+        self.report_outbox();
+        let Some(outcome_sender) = self.outcome_sender.clone() else {
+            return;
+        };
+        let id = id.to_string();
+        std::thread::spawn(move || {
+            let result = SmtpClient::new().send(&credentials, &envelope, &built.bytes).map(|()| built.bytes);
+            let _ = outcome_sender.send(SendOutcome { id, result });
+        });
+    }
+
+    fn finish_sending(&mut self, outcome: SendOutcome) {
+        match outcome.result {
+            Ok(content) => {
+                let Some((entry, _)) = self.outbox.remove(&outcome.id) else {
+                    return;
+                };
+                self.report_outbox();
+                let save_sent_copy = self.accounts.get(&entry.account_email).is_some_and(|account| account.save_sent_copy);
+                self.send_event(AccountEvent::EmailSent(entry.account_email.clone(), entry.subject, entry.replying_to, entry.draft_id));
+                if save_sent_copy {
+                    self.file_sent_copy(&entry.account_email, &content);
+                }
             }
             Err(e) => {
-                self.send_event(AccountEvent::ConnectionFailed(email.to_string(), format!("Failed to fetch emails: {:?}", e)));
+                let Some(entry) = self.outbox.get(&outcome.id).cloned() else {
+                    return;
+                };
+                self.outbox.record_failure(&outcome.id, &e.to_string(), e.is_permanent(), unix_now());
+                let will_retry = self.outbox.get(&outcome.id).is_some_and(|entry| entry.state == OutboxState::Queued);
+                self.report_outbox();
+                self.send_event(AccountEvent::SendFailed(entry.account_email, e.to_string(), will_retry));
             }
         }
     }
 
-    #[allow(dead_code)] // Will be used for email deletion
-    async fn delete_email(&mut self, email: &str, email_id: usize) {
-        // For now, just simulate deletion by removing from local account
-        if let Some(account) = self.accounts.get_mut(email) {
-            account.emails.retain(|e| e.id != email_id);
-            self.send_event(AccountEvent::EmailDeleted(email.to_string(), email_id));
-        }
-        
-        // TODO: Implement actual IMAP deletion
-        // match self.imap_client.delete_email(email, email_id).await {
-        //     Ok(_) => {
-        //         self.send_event(AccountEvent::EmailDeleted(email.to_string(), email_id));
-        //     }
-        //     Err(e) => {
-        //         self.send_event(AccountEvent::ConnectionFailed(email.to_string(), format!("Failed to delete email: {:?}", e)));
-        //     }
-        // }
-    }
-
-    #[allow(dead_code)] // Helper for event dispatching
+    fn report_outbox(&self) {
+        self.send_event(AccountEvent::OutboxChanged(self.outbox.entries().to_vec()));
+    }
+
+    /// Queues a sent message on disk before appending it, so that a failed
+    /// append is tried again later.
+    fn file_sent_copy(&mut self, account_email: &str, content: &[u8]) {
+        self.mail_store.queue_sent_copy(account_email, content);
+        self.flush_sent_copies(account_email);
+    }
+
+    /// Appends queued sent messages to Sent, oldest first, stopping at the
+    /// first failure. Runs again after each send and on connecting.
+    fn flush_sent_copies(&mut self, account_email: &str) {
+        for (id, content) in self.mail_store.queued_sent_copies(account_email) {
+            match self.imap_client.append_sent(account_email, &content) {
+                Ok(_) => self.mail_store.remove_sent_copy(account_email, &id),
+                Err(e) => {
+                    self.send_event(AccountEvent::SentCopyFailed(account_email.to_string(), e.to_string()));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Saves a draft with its Bcc recipients and the message it replies to,
+    /// so that reopening it restores everything.
+    fn save_draft(&mut self, account_email: &str, message: &OutgoingEmail, draft_id: &str, replying_to: Option<&MessageRef>) {
+        let mut builder = MessageBuilder::new(message).message_id(draft_id).include_bcc(true);
+        if let Some(original) = replying_to {
+            builder = builder.header(REPLYING_TO_HEADER, &encode_message_ref(original));
+        }
+        let event = match self.imap_client.save_draft(account_email, &builder.build().bytes, draft_id) {
+            Ok(folder) => AccountEvent::DraftSaved(account_email.to_string(), folder),
+            Err(e) => AccountEvent::DraftFailed(account_email.to_string(), e.to_string()),
+        };
+        self.send_event(event);
+    }
+
+    fn open_draft(&mut self, account_email: &str, folder: &str, uid_validity: u32, email_id: usize) {
+        match self.imap_client.fetch_raw_emails(account_email, folder, uid_validity, &[email_id]) {
+            Ok(mut messages) if !messages.is_empty() => {
+                self.send_event(AccountEvent::DraftLoaded(account_email.to_string(), messages.remove(0).content));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                self.invalidate_on_uid_validity_change(account_email, &e);
+                self.send_event(AccountEvent::DraftFailed(account_email.to_string(), e.to_string()));
+            }
+        }
+    }
+
+    /// Downloads a remote image on its own thread, reported as
+    /// `RemoteImageLoaded` or `RemoteImageFailed`.
+    pub fn fetch_remote_image(&self, url: String) {
+        let event_sender = self.event_sender.clone();
+        std::thread::spawn(move || {
+            let event = match remote_content::fetch_image(&url) {
+                Ok(content) => AccountEvent::RemoteImageLoaded(url, content),
+                Err(e) => AccountEvent::RemoteImageFailed(url, e),
+            };
+            if let Some(sender) = event_sender {
+                let _ = sender.send(event);
+            }
+        });
+    }
+
     fn send_event(&self, event: AccountEvent) {
         if let Some(sender) = &self.event_sender {
             let _ = sender.send(event);
@@ -221,20 +990,7 @@ impl AccountManager {
 
     #[allow(dead_code)] // Will be used for maintenance
     pub fn cleanup_cache(&mut self) {
-        self.email_cache.clear_expired();
-    }
-
-    #[allow(dead_code)] // Will be used for email deletion from UI
-    pub fn delete_email_by_index(&mut self, account_index: usize, email_id: usize) {
-        let accounts: Vec<_> = self.accounts.values().collect();
-        if let Some(account) = accounts.get(account_index) {
-            let account_email = account.email.clone();
-            // For immediate UI feedback, remove from local account
-            if let Some(account) = self.accounts.get_mut(&account_email) {
-                account.emails.retain(|e| e.id != email_id);
-                self.send_event(AccountEvent::EmailDeleted(account_email, email_id));
-            }
-        }
+        self.mail_store.prune_orphaned_bodies();
     }
 }
 
@@ -242,4 +998,61 @@ impl Default for AccountManager {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+/// Settles a cross-account transfer once `copied` are in the target folder:
+/// a move then removes them from the source with `expunge`. If that fails,
+/// the messages were copied rather than moved, and only the removal is
+/// reported as failed.
+fn settle_cross_account(
+    transfer: Transfer,
+    requested: usize,
+    target_folder: &str,
+    copied: Vec<usize>,
+    result: Result<(), ImapError>,
+    expunge: impl FnOnce(&[usize]) -> Result<(), ImapError>,
+) -> TransferOutcome {
+    if transfer == Transfer::Move && !copied.is_empty() && let Err(e) = expunge(&copied) {
+        let error = ImapError::Operation(format!(
+            "{} of {} were copied to {}, but the originals could not be removed: {}",
+            copied.len(), requested, target_folder, e
+        ));
+        return TransferOutcome { done: copied, transfer: Transfer::Copy, result: Err(error) };
+    }
+    TransferOutcome { done: copied, transfer, result }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_removal_after_a_move_counts_as_a_copy() {
+        let outcome = settle_cross_account(Transfer::Move, 3, "Archive", vec![4, 5], Ok(()), |uids| {
+            assert_eq!(uids, [4, 5]);
+            Err(ImapError::Operation("EXPUNGE refused".to_string()))
+        });
+        assert_eq!(outcome.done, vec![4, 5]);
+        assert_eq!(outcome.transfer, Transfer::Copy);
+        let error = outcome.result.unwrap_err().to_string();
+        assert!(error.contains("2 of 3 were copied to Archive"), "{}", error);
+    }
+
+    #[test]
+    fn moves_remove_only_what_was_copied() {
+        let outcome = settle_cross_account(
+            Transfer::Move, 3, "Archive", vec![4],
+            Err(ImapError::Operation("APPEND failed".to_string())),
+            |uids| {
+                assert_eq!(uids, [4]);
+                Ok(())
+            },
+        );
+        assert_eq!(outcome.done, vec![4]);
+        assert_eq!(outcome.transfer, Transfer::Move);
+        assert!(outcome.result.is_err());
+
+        let copy = settle_cross_account(Transfer::Copy, 1, "Archive", vec![4], Ok(()), |_| panic!("copies keep the originals"));
+        assert_eq!(copy.transfer, Transfer::Copy);
+        assert!(copy.result.is_ok());
+    }
+}
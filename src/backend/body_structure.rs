@@ -0,0 +1,196 @@
+//! Message layout as reported by `FETCH BODYSTRUCTURE`.
+//!
+//! Knowing the layout up front lets the preview download only the part it
+//! displays, and attachments only when they are asked for, instead of the
+//! whole message.
+
+use crate::backend::mime_parser::{decode_charset, decode_transfer_encoding, parse_parameters, strip_html};
+use imap_proto::types::{BodyContentCommon, BodyContentSinglePart, BodyParams, BodyStructure, ContentEncoding};
+
+/// A leaf part of a message: a text, a file, or an attached message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodyPart {
+    /// IMAP section specifier ("1", "2.1", ...)
+    pub section: String,
+    /// Lowercased "type/subtype"
+    pub mime_type: String,
+    pub charset: Option<String>,
+    /// Content-Transfer-Encoding, lowercased
+    pub encoding: String,
+    pub filename: Option<String>,
+    /// Lowercased disposition type ("inline", "attachment"), if given
+    pub disposition: Option<String>,
+    /// Content-ID without the angle brackets
+    pub content_id: Option<String>,
+    /// Size of the encoded content in bytes
+    pub size: usize,
+}
+
+impl BodyPart {
+    fn from_imap(section: String, common: &BodyContentCommon, other: &BodyContentSinglePart) -> Self {
+        let type_params = parameters(&common.ty.params);
+        let disposition_params = common.disposition.as_ref().map(|d| parameters(&d.params)).unwrap_or_default();
+        let filename = find_parameter(&disposition_params, "filename")
+            .or_else(|| find_parameter(&type_params, "name"))
+            .filter(|name| !name.is_empty());
+
+        Self {
+            section,
+            mime_type: format!("{}/{}", common.ty.ty, common.ty.subtype).to_lowercase(),
+            charset: find_parameter(&type_params, "charset"),
+            encoding: encoding_name(&other.transfer_encoding),
+            filename,
+            disposition: common.disposition.as_ref().map(|d| d.ty.to_lowercase()),
+            content_id: other.id.map(|id| id.trim().trim_start_matches('<').trim_end_matches('>').to_string()),
+            size: other.octets as usize,
+        }
+    }
+
+    pub fn main_type(&self) -> &str {
+        self.mime_type.split('/').next().unwrap_or("")
+    }
+
+    pub fn subtype(&self) -> &str {
+        self.mime_type.split('/').nth(1).unwrap_or("")
+    }
+
+    /// Same rules as `MimePart::is_attachment`.
+    pub fn is_attachment(&self) -> bool {
+        match self.disposition.as_deref() {
+            Some("attachment") => true,
+            Some("inline") => false,
+            _ => self.filename.is_some() || self.main_type() != "text",
+        }
+    }
+
+    /// Section numbers as used in a FETCH response.
+    pub fn section_path(&self) -> Vec<u32> {
+        self.section.split('.').filter_map(|number| number.parse().ok()).collect()
+    }
+
+    /// Undoes the transfer encoding of the part as fetched from the server.
+    pub fn decode(&self, raw: &[u8]) -> Vec<u8> {
+        decode_transfer_encoding(raw, &self.encoding)
+    }
+
+    /// Decodes a text part fetched from the server, markup included.
+    pub fn decode_string(&self, raw: &[u8]) -> String {
+        decode_charset(&self.decode(raw), self.charset.as_deref().unwrap_or("us-ascii"))
+            .replace("\r\n", "\n")
+    }
+
+    /// Plain text to show in the preview, for a text part fetched from the server.
+    pub fn decode_text(&self, raw: &[u8]) -> String {
+        let text = self.decode_string(raw);
+        if self.subtype() == "html" {
+            strip_html(&text)
+        } else {
+            text
+        }
+    }
+}
+
+/// The leaf parts of a message in the order they appear.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageStructure {
+    pub parts: Vec<BodyPart>,
+}
+
+impl MessageStructure {
+    pub fn from_imap(structure: &BodyStructure) -> Self {
+        let mut parts = Vec::new();
+        match structure {
+            // The parts of a multipart message are numbered from 1
+            BodyStructure::Multipart { bodies, .. } => collect_parts(bodies, "", &mut parts),
+            // A single-part message is its own part 1
+            _ => collect_part(structure, "1".to_string(), &mut parts),
+        }
+        Self { parts }
+    }
+
+    /// The part the preview shows: the first text/plain part that is not an
+    /// attachment, else the first such text/html part.
+    pub fn text_part(&self) -> Option<&BodyPart> {
+        self.find_text("plain").or_else(|| self.find_text("html"))
+    }
+
+    /// The HTML alternative, if the message has one.
+    pub fn html_part(&self) -> Option<&BodyPart> {
+        self.find_text("html")
+    }
+
+    fn find_text(&self, subtype: &str) -> Option<&BodyPart> {
+        self.parts
+            .iter()
+            .find(|part| part.main_type() == "text" && part.subtype() == subtype && !part.is_attachment())
+    }
+
+    /// Parts meant to be saved rather than displayed.
+    pub fn attachments(&self) -> Vec<&BodyPart> {
+        self.parts.iter().filter(|part| part.is_attachment()).collect()
+    }
+
+    /// Images that HTML parts can refer to with a `cid:` URL.
+    pub fn inline_images(&self) -> Vec<&BodyPart> {
+        self.parts
+            .iter()
+            .filter(|part| part.main_type() == "image" && part.content_id.is_some())
+            .collect()
+    }
+
+    pub fn find(&self, section: &str) -> Option<&BodyPart> {
+        self.parts.iter().find(|part| part.section == section)
+    }
+}
+
+fn collect_parts(bodies: &[BodyStructure], parent: &str, parts: &mut Vec<BodyPart>) {
+    for (i, body) in bodies.iter().enumerate() {
+        let section = if parent.is_empty() {
+            (i + 1).to_string()
+        } else {
+            format!("{}.{}", parent, i + 1)
+        };
+        collect_part(body, section, parts);
+    }
+}
+
+fn collect_part(body: &BodyStructure, section: String, parts: &mut Vec<BodyPart>) {
+    match body {
+        BodyStructure::Multipart { bodies, .. } => collect_parts(bodies, &section, parts),
+        // Attached messages are kept whole rather than opened up
+        BodyStructure::Basic { common, other, .. }
+        | BodyStructure::Text { common, other, .. }
+        | BodyStructure::Message { common, other, .. } => parts.push(BodyPart::from_imap(section, common, other)),
+    }
+}
+
+/// Decodes a parameter list the same way as header parameters, so RFC 2231
+/// continuations and encoded-words in file names are handled.
+fn parameters(params: &BodyParams) -> Vec<(String, String)> {
+    let Some(params) = params else {
+        return Vec::new();
+    };
+    let joined: String = params
+        .iter()
+        .map(|(key, value)| format!("; {}=\"{}\"", key, value.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    parse_parameters(&joined)
+}
+
+fn find_parameter(params: &[(String, String)], name: &str) -> Option<String> {
+    params
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.clone())
+}
+
+fn encoding_name(encoding: &ContentEncoding) -> String {
+    match encoding {
+        ContentEncoding::SevenBit => "7bit".to_string(),
+        ContentEncoding::EightBit => "8bit".to_string(),
+        ContentEncoding::Binary => "binary".to_string(),
+        ContentEncoding::Base64 => "base64".to_string(),
+        ContentEncoding::QuotedPrintable => "quoted-printable".to_string(),
+        ContentEncoding::Other(name) => name.to_lowercase(),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::backend::imap_client::TlsMode;
 use keyring::{Entry, Result as KeyringResult};
 use std::collections::HashMap;
 
@@ -10,6 +11,15 @@ pub struct AccountCredentials {
     pub use_tls: bool,
 }
 
+/// Submission settings for an account's outgoing server.
+#[derive(Debug, Clone)]
+pub struct SmtpCredentials {
+    pub email: String,
+    pub server: String,
+    pub port: u16,
+    pub tls_mode: TlsMode,
+}
+
 #[allow(dead_code)] // Backend credential management
 pub struct CredentialsManager {
     service_name: String,
@@ -0,0 +1,112 @@
+//! Saving and opening downloaded attachments.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Writes an attachment to the user's download folder without overwriting
+/// anything there. Returns where it was saved.
+pub fn save(filename: &str, content: &[u8]) -> io::Result<PathBuf> {
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no download folder"))?;
+    write_unique(&dir, filename, content)
+}
+
+/// Writes an attachment to a temporary folder and hands it to the
+/// application the system associates with its type.
+pub fn open(filename: &str, content: &[u8]) -> io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join("mailcross-attachments");
+    let path = write_unique(&dir, filename, content)?;
+    open_with_system_handler(&path)?;
+    Ok(path)
+}
+
+fn write_unique(dir: &Path, filename: &str, content: &[u8]) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = unique_path(dir, &safe_file_name(filename));
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// Keeps a sender-chosen name from escaping the target folder. Only
+/// letters, digits and punctuation that no shell or file system treats
+/// specially are kept.
+fn safe_file_name(filename: &str) -> String {
+    let name: String = filename
+        .chars()
+        .map(|c| {
+            let allowed = c.is_alphanumeric()
+                || matches!(c, ' ' | '.' | '-' | '_' | '(' | ')' | '[' | ']' | '+' | ',' | '#' | '@' | '=' | '~');
+            if allowed { c } else { '_' }
+        })
+        .collect();
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() {
+        "attachment".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// "report.pdf", then "report (1).pdf", "report (2).pdf", ...
+fn unique_path(dir: &Path, filename: &str) -> PathBuf {
+    let candidate = dir.join(filename);
+    if !candidate.exists() {
+        return candidate;
+    }
+    let (stem, extension) = match filename.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (filename, String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap_or(candidate)
+}
+
+fn open_with_system_handler(path: &Path) -> io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    // Not through `cmd /C start`, which would parse the name as a command line
+    #[cfg(target_os = "windows")]
+    let mut command = Command::new("explorer.exe");
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = Command::new("xdg-open");
+
+    command.arg(path).spawn().map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleans_sender_chosen_names() {
+        assert_eq!(safe_file_name("report (final).pdf"), "report (final).pdf");
+        assert_eq!(safe_file_name("../../etc/passwd"), "_.._etc_passwd");
+        assert_eq!(safe_file_name("C:\\boot.ini"), "C__boot.ini");
+        assert_eq!(safe_file_name("a&calc|b^%x%>out.txt"), "a_calc_b__x__out.txt");
+        assert_eq!(safe_file_name("line\r\nbreak\0.txt"), "line__break_.txt");
+        assert_eq!(safe_file_name("Überweisung.pdf"), "Überweisung.pdf");
+        assert_eq!(safe_file_name(" ..hidden "), "hidden");
+        assert_eq!(safe_file_name(""), "attachment");
+    }
+
+    #[test]
+    fn numbers_names_already_taken() {
+        let dir = std::env::temp_dir().join(format!("mailcross-downloads-test-{}", std::process::id()));
+        let first = write_unique(&dir, "report.pdf", b"1").unwrap();
+        let second = write_unique(&dir, "report.pdf", b"2").unwrap();
+        let third = write_unique(&dir, "report.pdf", b"3").unwrap();
+        write_unique(&dir, "README", b"").unwrap();
+        let plain = unique_path(&dir, "README");
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(first, dir.join("report.pdf"));
+        assert_eq!(second, dir.join("report (1).pdf"));
+        assert_eq!(third, dir.join("report (2).pdf"));
+        assert_eq!(plain, dir.join("README (1)"));
+    }
+}
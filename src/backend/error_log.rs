@@ -0,0 +1,28 @@
+//! Errors from work nobody waits on, such as writing the mail store or the
+//! settings in the background. They are kept here for the status bar rather
+//! than printed.
+
+use std::sync::Mutex;
+
+/// Older errors are dropped beyond this, should nothing collect them
+const MAX_ERRORS: usize = 100;
+
+static ERRORS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Records an error to show to the user.
+pub fn report(message: impl Into<String>) {
+    if let Ok(mut errors) = ERRORS.lock() {
+        if errors.len() >= MAX_ERRORS {
+            errors.remove(0);
+        }
+        errors.push(message.into());
+    }
+}
+
+/// The errors reported since the last call, oldest first.
+pub fn take() -> Vec<String> {
+    ERRORS
+        .lock()
+        .map(|mut errors| std::mem::take(&mut *errors))
+        .unwrap_or_default()
+}
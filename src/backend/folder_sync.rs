@@ -0,0 +1,434 @@
+//! Incremental folder synchronisation.
+//!
+//! A folder is brought up to date from its stored state in as few round
+//! trips as the server allows:
+//!
+//! - CONDSTORE, HIGHESTMODSEQ unchanged: nothing to fetch beyond the SELECT.
+//! - QRESYNC: one `UID FETCH ... (CHANGEDSINCE m VANISHED)` returns changed
+//!   flags, new UIDs and expunged UIDs.
+//! - Otherwise: one `UID FETCH <lowest>:* (UID FLAGS)` over the stored window,
+//!   diffed against the stored UIDs.
+//!
+//! Headers are then fetched only for UIDs that are new to the store.
+
+use crate::backend::imap_client::{ImapError, ImapSession, ServerExtensions};
+use crate::backend::imap_stream::{SharedResponseLog, format_uid_set};
+use crate::backend::mail_store::{StoredFolder, SyncState};
+use crate::backend::mime_parser::ParsedMessage;
+use crate::types::{Email, EmailFlags};
+use imap::types::Flag;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::ops::RangeInclusive;
+
+/// The state of a folder after a sync: the newest `limit` messages.
+pub struct FolderSync {
+    pub emails: Vec<Email>,
+    /// `last_sync` is left for the store to fill in
+    pub sync: SyncState,
+}
+
+/// Difference between two states of a folder, used to update a list that is
+/// on screen without replacing it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MailboxChanges {
+    /// Messages new to the list, newest first
+    pub added: Vec<Email>,
+    /// UIDs no longer in the list
+    pub removed: Vec<usize>,
+    /// Messages whose flags changed, with their new flags
+    pub updated: Vec<Email>,
+}
+
+impl MailboxChanges {
+    pub fn between(before: &[Email], after: &[Email]) -> Self {
+        let previous: HashMap<usize, &Email> = before.iter().map(|email| (email.id, email)).collect();
+        let current: HashSet<usize> = after.iter().map(|email| email.id).collect();
+
+        let mut changes = Self::default();
+        for email in after {
+            match previous.get(&email.id) {
+                None => changes.added.push(email.clone()),
+                Some(old) if old.flags != email.flags => changes.updated.push(email.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.removed = before
+            .iter()
+            .map(|email| email.id)
+            .filter(|id| !current.contains(id))
+            .collect();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty()
+    }
+
+    /// Applies the changes to a list sorted newest first. Bodies already
+    /// loaded into the list are kept.
+    pub fn apply_to(&self, emails: &mut Vec<Email>) {
+        emails.retain(|email| !self.removed.contains(&email.id));
+        for updated in &self.updated {
+            if let Some(email) = emails.iter_mut().find(|email| email.id == updated.id) {
+                email.flags = updated.flags.clone();
+            }
+        }
+        emails.extend(self.added.iter().cloned());
+        emails.sort_by_key(|email| std::cmp::Reverse(email.id));
+    }
+}
+
+/// Flag state reported for one UID
+struct FlagUpdate {
+    uid: u32,
+    flags: EmailFlags,
+}
+
+pub fn sync_folder<S: Read + Write>(
+    session: &mut ImapSession<S>,
+    responses: &SharedResponseLog,
+    extensions: ServerExtensions,
+    folder: &str,
+    previous: Option<&StoredFolder>,
+    limit: usize,
+) -> Result<FolderSync, ImapError> {
+    if let Ok(mut log) = responses.lock() {
+        log.highest_modseq = None;
+        log.vanished.clear();
+    }
+
+    let mailbox = session.select(folder)?;
+    let uid_validity = mailbox.uid_validity.unwrap_or(0);
+    let highest_modseq = responses
+        .lock()
+        .ok()
+        .and_then(|log| log.highest_modseq)
+        .unwrap_or(0);
+
+    // Stored UIDs are worthless once UIDVALIDITY changed
+    let previous = previous.filter(|stored| {
+        uid_validity != 0 && stored.sync.uid_validity == uid_validity && !stored.emails.is_empty()
+    });
+
+    let emails = match previous {
+        _ if mailbox.exists == 0 => Vec::new(),
+        None => fetch_newest(session, uid_validity, limit)?,
+        Some(stored) if extensions.condstore && highest_modseq != 0 && stored.sync.highest_modseq == highest_modseq => {
+            stored.emails.clone()
+        }
+        Some(stored) if extensions.qresync && highest_modseq != 0 && stored.sync.highest_modseq != 0 => {
+            sync_changed_since(session, responses, uid_validity, stored, limit)?
+        }
+        Some(stored) => sync_by_uid_diff(session, uid_validity, stored, limit)?,
+    };
+
+    Ok(FolderSync {
+        emails,
+        sync: SyncState {
+            uid_validity,
+            highest_modseq,
+            last_sync: 0,
+        },
+    })
+}
+
+/// Initial download: headers of the `limit` highest UIDs.
+fn fetch_newest<S: Read + Write>(session: &mut ImapSession<S>, uid_validity: u32, limit: usize) -> Result<Vec<Email>, ImapError> {
+    let mut uids: Vec<u32> = session.uid_search("ALL")?.into_iter().collect();
+    uids.sort_unstable_by_key(|uid| std::cmp::Reverse(*uid));
+    uids.truncate(limit);
+    fetch_headers(session, &uids, uid_validity)
+}
+
+/// QRESYNC: changed flags, new messages and expunges in one round trip.
+fn sync_changed_since<S: Read + Write>(
+    session: &mut ImapSession<S>,
+    responses: &SharedResponseLog,
+    uid_validity: u32,
+    stored: &StoredFolder,
+    limit: usize,
+) -> Result<Vec<Email>, ImapError> {
+    let lowest = lowest_uid(stored);
+    let fetches = session.uid_fetch(
+        format!("{}:*", lowest),
+        format!("(UID FLAGS) (CHANGEDSINCE {} VANISHED)", stored.sync.highest_modseq),
+    )?;
+    let updates = flag_updates(fetches.iter(), lowest);
+    let vanished: Vec<RangeInclusive<u32>> = responses
+        .lock()
+        .map(|mut log| std::mem::take(&mut log.vanished))
+        .unwrap_or_default();
+
+    let is_vanished = |uid: u32| vanished.iter().any(|range| range.contains(&uid));
+    apply_changes(session, uid_validity, stored, updates, is_vanished, limit)
+}
+
+/// Fallback without QRESYNC: list every UID in the stored window with its
+/// flags and diff against the store.
+fn sync_by_uid_diff<S: Read + Write>(
+    session: &mut ImapSession<S>,
+    uid_validity: u32,
+    stored: &StoredFolder,
+    limit: usize,
+) -> Result<Vec<Email>, ImapError> {
+    let lowest = lowest_uid(stored);
+    let fetches = session.uid_fetch(format!("{}:*", lowest), "(UID FLAGS)")?;
+    let updates = flag_updates(fetches.iter(), lowest);
+
+    let present: HashSet<u32> = updates.iter().map(|update| update.uid).collect();
+    let is_vanished = |uid: u32| !present.contains(&uid);
+    apply_changes(session, uid_validity, stored, updates, is_vanished, limit)
+}
+
+fn apply_changes<S: Read + Write>(
+    session: &mut ImapSession<S>,
+    uid_validity: u32,
+    stored: &StoredFolder,
+    updates: Vec<FlagUpdate>,
+    is_vanished: impl Fn(u32) -> bool,
+    limit: usize,
+) -> Result<Vec<Email>, ImapError> {
+    let highest_stored = stored.emails.iter().map(|email| email.id as u32).max().unwrap_or(0);
+    let flags: HashMap<u32, &EmailFlags> = updates.iter().map(|update| (update.uid, &update.flags)).collect();
+
+    let mut emails: Vec<Email> = stored.emails
+        .iter()
+        .filter(|email| !is_vanished(email.id as u32))
+        .cloned()
+        .map(|mut email| {
+            if let Some(&flags) = flags.get(&(email.id as u32)) {
+                email.flags = flags.clone();
+            }
+            email
+        })
+        .collect();
+    let lost_messages = emails.len() < stored.emails.len();
+
+    let mut new_uids: Vec<u32> = updates
+        .iter()
+        .map(|update| update.uid)
+        .filter(|&uid| uid > highest_stored && !is_vanished(uid))
+        .collect();
+    new_uids.sort_unstable_by_key(|uid| std::cmp::Reverse(*uid));
+    new_uids.truncate(limit);
+    emails.extend(fetch_headers(session, &new_uids, uid_validity)?);
+
+    // Refill the window with older messages after expunges
+    let lowest = lowest_uid(stored);
+    if lost_messages && emails.len() < limit && lowest > 1 {
+        let mut older: Vec<u32> = session
+            .uid_search(format!("UID 1:{}", lowest - 1))?
+            .into_iter()
+            .filter(|&uid| uid < lowest)
+            .collect();
+        older.sort_unstable_by_key(|uid| std::cmp::Reverse(*uid));
+        older.truncate(limit - emails.len());
+        emails.extend(fetch_headers(session, &older, uid_validity)?);
+    }
+
+    emails.sort_by_key(|email| std::cmp::Reverse(email.id));
+    emails.truncate(limit);
+    Ok(emails)
+}
+
+/// Headers and flags of the given UIDs, newest first.
+fn fetch_headers<S: Read + Write>(session: &mut ImapSession<S>, uids: &[u32], uid_validity: u32) -> Result<Vec<Email>, ImapError> {
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let messages = session.uid_fetch(format_uid_set(uids), "(UID FLAGS RFC822.HEADER)")?;
+    let mut emails: Vec<Email> = messages
+        .iter()
+        .filter_map(|message| {
+            let uid = message.uid?;
+            let parsed = ParsedMessage::parse(message.header().unwrap_or_default());
+            let mut email = Email::from_parsed(uid as usize, &parsed);
+            email.uid_validity = uid_validity;
+            email.flags = email_flags(message.flags());
+            Some(email)
+        })
+        .collect();
+    // Servers may answer a UID FETCH in any order
+    emails.sort_by_key(|email| std::cmp::Reverse(email.id));
+    Ok(emails)
+}
+
+fn flag_updates<'a>(fetches: impl Iterator<Item = &'a imap::types::Fetch>, lowest: u32) -> Vec<FlagUpdate> {
+    fetches
+        .filter_map(|fetch| {
+            let uid = fetch.uid?;
+            // "n:*" also matches the highest UID when it is below n
+            (uid >= lowest).then(|| FlagUpdate {
+                uid,
+                flags: email_flags(fetch.flags()),
+            })
+        })
+        .collect()
+}
+
+pub fn email_flags(flags: &[Flag]) -> EmailFlags {
+    let mut result = EmailFlags::default();
+    for flag in flags {
+        match flag {
+            Flag::Seen => result.seen = true,
+            Flag::Flagged => result.flagged = true,
+            Flag::Answered => result.answered = true,
+            Flag::Draft => result.draft = true,
+            Flag::Custom(keyword) => result.keywords.push(keyword.to_string()),
+            Flag::Deleted | Flag::Recent | Flag::MayCreate => {}
+        }
+    }
+    result
+}
+
+/// Flags to set when APPENDing a message.
+pub fn imap_flags(flags: &EmailFlags) -> Vec<Flag<'_>> {
+    let mut result = Vec::new();
+    if flags.seen {
+        result.push(Flag::Seen);
+    }
+    if flags.flagged {
+        result.push(Flag::Flagged);
+    }
+    if flags.answered {
+        result.push(Flag::Answered);
+    }
+    if flags.draft {
+        result.push(Flag::Draft);
+    }
+    result.extend(flags.keywords.iter().map(|keyword| Flag::Custom(keyword.as_str().into())));
+    result
+}
+
+fn lowest_uid(stored: &StoredFolder) -> u32 {
+    stored.emails.iter().map(|email| email.id as u32).min().unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::imap_stream::ImapStream;
+    use std::cell::RefCell;
+    use std::io;
+    use std::rc::Rc;
+
+    /// Canned server responses, handed out a few bytes at a time so that
+    /// literals arrive in pieces; commands are recorded.
+    struct Server {
+        responses: Vec<u8>,
+        pos: usize,
+        commands: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Read for Server {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let count = 5.min(buf.len()).min(self.responses.len() - self.pos);
+            buf[..count].copy_from_slice(&self.responses[self.pos..self.pos + count]);
+            self.pos += count;
+            Ok(count)
+        }
+    }
+
+    impl Write for Server {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.commands.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn email(uid: usize, seen: bool) -> Email {
+        let mut email = Email::new(uid, "a@example.com", "b@example.com", "Stored", "", "");
+        email.uid_validity = 7;
+        email.flags.seen = seen;
+        email
+    }
+
+    fn stored(highest_modseq: u64) -> StoredFolder {
+        StoredFolder {
+            sync: SyncState { uid_validity: 7, highest_modseq, last_sync: 1 },
+            emails: vec![email(12, false), email(11, false), email(10, false)],
+        }
+    }
+
+    /// Syncs INBOX against a server that answers SELECT with `select` and
+    /// the flag fetch with `changes`, then sends the headers of UID 13.
+    fn sync(extensions: ServerExtensions, previous: &StoredFolder, select: &str, changes: &str) -> (FolderSync, String) {
+        let header = "Subject: New\r\nFrom: c@example.com\r\nMessage-ID: <new@example.com>\r\n\r\n";
+        let responses = format!(
+            "a1 OK Logged in\r\n\
+             {select}\
+             a2 OK [READ-WRITE] Select completed\r\n\
+             {changes}\
+             a3 OK Fetch completed\r\n\
+             * 3 FETCH (UID 13 FLAGS () RFC822.HEADER {{{}}}\r\n{header})\r\n\
+             a4 OK Fetch completed\r\n",
+            header.len()
+        );
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let server = Server { responses: responses.into_bytes(), pos: 0, commands: commands.clone() };
+        let stream = ImapStream::new(server);
+        let log = stream.log();
+        let mut session = imap::Client::new(stream).login("user", "secret").map_err(|(e, _)| e).unwrap();
+        let result = sync_folder(&mut session, &log, extensions, "INBOX", Some(previous), 3).unwrap();
+        let commands = String::from_utf8(commands.take()).unwrap();
+        (result, commands)
+    }
+
+    fn layout(emails: &[Email]) -> Vec<(usize, bool)> {
+        emails.iter().map(|email| (email.id, email.flags.seen)).collect()
+    }
+
+    #[test]
+    fn resyncs_with_qresync() {
+        let extensions = ServerExtensions { condstore: true, qresync: true, ..Default::default() };
+        let (result, commands) = sync(
+            extensions,
+            &stored(100),
+            "* 3 EXISTS\r\n* OK [UIDVALIDITY 7] Ok\r\n* OK [HIGHESTMODSEQ 120] Ok\r\n",
+            "* VANISHED (EARLIER) 11\r\n* 2 FETCH (UID 12 FLAGS (\\Seen))\r\n* 3 FETCH (UID 13 FLAGS ())\r\n",
+        );
+        assert_eq!(layout(&result.emails), vec![(13, false), (12, true), (10, false)]);
+        assert_eq!(result.emails[0].subject, "New");
+        assert_eq!(result.sync.highest_modseq, 120);
+        assert_eq!(result.sync.uid_validity, 7);
+        assert!(commands.contains("a3 UID FETCH 10:* (UID FLAGS) (CHANGEDSINCE 100 VANISHED)\r\n"), "{}", commands);
+        assert!(commands.contains("a4 UID FETCH 13 (UID FLAGS RFC822.HEADER)\r\n"), "{}", commands);
+    }
+
+    #[test]
+    fn resyncs_by_uid_diff_without_qresync() {
+        let (result, commands) = sync(
+            ServerExtensions::default(),
+            &stored(0),
+            "* 3 EXISTS\r\n* OK [UIDVALIDITY 7] Ok\r\n",
+            "* 1 FETCH (UID 10 FLAGS ())\r\n* 2 FETCH (UID 12 FLAGS (\\Seen))\r\n* 3 FETCH (UID 13 FLAGS ())\r\n",
+        );
+        assert_eq!(layout(&result.emails), vec![(13, false), (12, true), (10, false)]);
+        assert_eq!(result.sync.highest_modseq, 0);
+        assert!(commands.contains("a3 UID FETCH 10:* (UID FLAGS)\r\n"), "{}", commands);
+    }
+
+    #[test]
+    fn diffs_and_applies_list_changes() {
+        let before = vec![email(12, false), email(11, false), email(10, false)];
+        let mut loaded = before.clone();
+        loaded[2].body = "kept".to_string();
+        let after = vec![email(13, false), email(12, true), email(10, false)];
+
+        let changes = MailboxChanges::between(&before, &after);
+        assert_eq!(changes.added.iter().map(|email| email.id).collect::<Vec<_>>(), vec![13]);
+        assert_eq!(changes.removed, vec![11]);
+        assert_eq!(changes.updated.iter().map(|email| email.id).collect::<Vec<_>>(), vec![12]);
+
+        changes.apply_to(&mut loaded);
+        assert_eq!(layout(&loaded), layout(&after));
+        assert_eq!(loaded[2].body, "kept");
+        assert!(MailboxChanges::between(&after, &after).is_empty());
+    }
+}
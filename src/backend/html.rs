@@ -0,0 +1,601 @@
+//! HTML mail parsing and sanitizing.
+//!
+//! Mail HTML is rarely well formed, so the parser is forgiving: it never
+//! fails, closes elements the way browsers roughly do, and drops what it
+//! cannot make sense of. The sanitizer then keeps only the elements and
+//! attributes the preview knows how to draw. Scripts, styles, forms and
+//! embedded content are removed along with everything inside them, and
+//! links and image sources are limited to schemes that cannot run code.
+
+/// A node of a sanitized document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HtmlNode {
+    /// Entities decoded, whitespace as in the source
+    Text(String),
+    Element(HtmlElement),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlElement {
+    /// Lowercased tag name
+    pub tag: String,
+    /// Lowercased names, decoded values
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<HtmlNode>,
+}
+
+impl HtmlElement {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Elements without content or closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// Elements whose content is not markup
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title", "xmp", "noembed", "noframes"];
+
+/// Removed together with their content
+const DROPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "head", "title", "template", "iframe", "frame", "frameset", "object", "embed", "applet",
+    "param", "svg", "math", "canvas", "audio", "video", "source", "track", "input", "button", "select", "option",
+    "textarea", "meta", "link", "base", "noembed", "noframes", "xmp",
+];
+
+/// Kept with their allowed attributes; anything else is replaced by its content
+const ALLOWED_ELEMENTS: &[&str] = &[
+    "a", "abbr", "address", "article", "aside", "b", "big", "blockquote", "br", "caption", "center", "cite", "code",
+    "dd", "del", "dfn", "div", "dl", "dt", "em", "figcaption", "figure", "font", "footer", "h1", "h2", "h3", "h4",
+    "h5", "h6", "header", "hr", "i", "img", "ins", "kbd", "li", "main", "mark", "nav", "ol", "p", "pre", "q", "s",
+    "samp", "section", "small", "span", "strike", "strong", "sub", "sup", "table", "tbody", "td", "tfoot", "th",
+    "thead", "tr", "tt", "u", "ul", "var",
+];
+
+/// Parses and sanitizes a message's HTML.
+pub fn sanitize(html: &str) -> Vec<HtmlNode> {
+    sanitize_nodes(parse(html))
+}
+
+fn sanitize_nodes(nodes: Vec<HtmlNode>) -> Vec<HtmlNode> {
+    let mut out = Vec::new();
+    for node in nodes {
+        match node {
+            HtmlNode::Text(text) => push_text(&mut out, &text),
+            HtmlNode::Element(element) => {
+                if DROPPED_ELEMENTS.contains(&element.tag.as_str()) {
+                    continue;
+                }
+                let children = sanitize_nodes(element.children);
+                if !ALLOWED_ELEMENTS.contains(&element.tag.as_str()) {
+                    for child in children {
+                        match child {
+                            HtmlNode::Text(text) => push_text(&mut out, &text),
+                            element => out.push(element),
+                        }
+                    }
+                    continue;
+                }
+                let attributes = element.attributes
+                    .into_iter()
+                    .filter_map(|(name, value)| sanitize_attribute(&element.tag, name, value))
+                    .collect();
+                out.push(HtmlNode::Element(HtmlElement {
+                    tag: element.tag,
+                    attributes,
+                    children,
+                }));
+            }
+        }
+    }
+    out
+}
+
+fn sanitize_attribute(tag: &str, name: String, value: String) -> Option<(String, String)> {
+    let allowed = match (tag, name.as_str()) {
+        ("a", "href") => is_safe_url(&value, &["http", "https", "mailto"]),
+        ("img", "src") => is_safe_url(&value, &["http", "https", "cid"]) || is_image_data_url(&value),
+        ("img", "alt" | "width" | "height") => true,
+        ("td" | "th", "colspan" | "rowspan") => true,
+        ("ol", "start") => true,
+        (_, "title") => true,
+        _ => false,
+    };
+    allowed.then_some((name, value))
+}
+
+/// Whether `url` has one of `schemes`. Browsers ignore whitespace and
+/// control characters inside a scheme, so those are ignored here too.
+fn is_safe_url(url: &str, schemes: &[&str]) -> bool {
+    let compact: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    let Some((scheme, _)) = compact.split_once(':') else {
+        // Relative URLs have nothing to resolve against
+        return false;
+    };
+    schemes.iter().any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+}
+
+fn is_image_data_url(url: &str) -> bool {
+    let lower = url.trim().to_ascii_lowercase();
+    ["data:image/png;", "data:image/jpeg;", "data:image/gif;", "data:image/bmp;"]
+        .iter()
+        .any(|prefix| lower.starts_with(prefix))
+}
+
+fn push_text(out: &mut Vec<HtmlNode>, text: &str) {
+    if let Some(HtmlNode::Text(last)) = out.last_mut() {
+        last.push_str(text);
+    } else if !text.is_empty() {
+        out.push(HtmlNode::Text(text.to_string()));
+    }
+}
+
+/// Builds a tree without judging what it contains.
+fn parse(html: &str) -> Vec<HtmlNode> {
+    let mut builder = TreeBuilder::default();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            builder.text(rest);
+            break;
+        };
+        builder.text(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map(|end| &comment[end + 3..]).unwrap_or("");
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            // Doctype, CDATA, processing instructions
+            rest = rest.find('>').map(|end| &rest[end + 1..]).unwrap_or("");
+        } else if let Some((tag, consumed)) = parse_tag(rest) {
+            rest = &rest[consumed..];
+            match tag {
+                Tag::Close(name) => builder.close(&name),
+                Tag::Open { name, attributes, self_closing } => {
+                    if RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+                        let content_end = find_closing_tag(rest, &name);
+                        let content = &rest[..content_end];
+                        rest = &rest[content_end..];
+                        rest = rest.find('>').map(|end| &rest[end + 1..]).unwrap_or("");
+                        builder.open(name, attributes);
+                        builder.raw_text(content);
+                        builder.close_current();
+                    } else {
+                        let void = VOID_ELEMENTS.contains(&name.as_str());
+                        builder.open(name, attributes);
+                        if void || self_closing {
+                            builder.close_current();
+                        }
+                    }
+                }
+            }
+        } else {
+            // A stray '<' is text
+            builder.text("<");
+            rest = &rest[1..];
+        }
+    }
+    builder.finish()
+}
+
+enum Tag {
+    Open { name: String, attributes: Vec<(String, String)>, self_closing: bool },
+    Close(String),
+}
+
+/// Parses the tag at the start of `input`, returning it and its length.
+fn parse_tag(input: &str) -> Option<(Tag, usize)> {
+    let bytes = input.as_bytes();
+    let mut i = 1;
+    let closing = bytes.get(i) == Some(&b'/');
+    if closing {
+        i += 1;
+    }
+    if !bytes.get(i).is_some_and(u8::is_ascii_alphabetic) {
+        return None;
+    }
+    let name_start = i;
+    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' && bytes[i] != b'/' {
+        i += 1;
+    }
+    let name = input[name_start..i].to_ascii_lowercase();
+
+    let mut attributes = Vec::new();
+    let mut self_closing = false;
+    loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        match bytes.get(i) {
+            None => return None,
+            Some(b'>') => {
+                i += 1;
+                break;
+            }
+            Some(b'/') => {
+                self_closing = true;
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        self_closing = false;
+
+        let key_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'=' | b'>' | b'/') {
+            i += 1;
+        }
+        let key = input[key_start..i].to_ascii_lowercase();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let mut value = String::new();
+        if bytes.get(i) == Some(&b'=') {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            match bytes.get(i) {
+                Some(&quote) if quote == b'"' || quote == b'\'' => {
+                    let value_start = i + 1;
+                    let end = input[value_start..].find(quote as char)? + value_start;
+                    value = decode_entities(&input[value_start..end]);
+                    i = end + 1;
+                }
+                _ => {
+                    let value_start = i;
+                    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                        i += 1;
+                    }
+                    value = decode_entities(&input[value_start..i]);
+                }
+            }
+        }
+        if !key.is_empty() && !attributes.iter().any(|(existing, _): &(String, String)| *existing == key) {
+            attributes.push((key, value));
+        }
+    }
+
+    let tag = if closing {
+        Tag::Close(name)
+    } else {
+        Tag::Open { name, attributes, self_closing }
+    };
+    Some((tag, i))
+}
+
+/// Offset of `</name` in `input`, or its length if the element is never closed.
+fn find_closing_tag(input: &str, name: &str) -> usize {
+    let lower = input.to_ascii_lowercase();
+    let needle = format!("</{}", name);
+    let mut from = 0;
+    while let Some(pos) = lower[from..].find(&needle) {
+        let at = from + pos;
+        let next = lower.as_bytes().get(at + needle.len());
+        if next.is_none_or(|b| b.is_ascii_whitespace() || *b == b'>' || *b == b'/') {
+            return at;
+        }
+        from = at + needle.len();
+    }
+    input.len()
+}
+
+#[derive(Default)]
+struct TreeBuilder {
+    /// Open elements, outermost first; the document root is not included
+    stack: Vec<HtmlElement>,
+    root: Vec<HtmlNode>,
+}
+
+impl TreeBuilder {
+    fn current_children(&mut self) -> &mut Vec<HtmlNode> {
+        match self.stack.last_mut() {
+            Some(element) => &mut element.children,
+            None => &mut self.root,
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.raw_text(&decode_entities(text));
+        }
+    }
+
+    fn raw_text(&mut self, text: &str) {
+        push_text(self.current_children(), text);
+    }
+
+    fn open(&mut self, name: String, attributes: Vec<(String, String)>) {
+        // Elements that end an open sibling, as browsers do for unclosed <p> and <li>
+        let closes: &[&str] = match name.as_str() {
+            "p" | "div" | "ul" | "ol" | "table" | "blockquote" | "pre" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => &["p"],
+            "li" => &["li"],
+            "dt" | "dd" => &["dt", "dd"],
+            "tr" => &["tr", "td", "th"],
+            "td" | "th" => &["td", "th"],
+            "tbody" | "thead" | "tfoot" => &["tbody", "thead", "tfoot", "tr", "td", "th"],
+            _ => &[],
+        };
+        // Only within the nearest list or table
+        let scope: &[&str] = match name.as_str() {
+            "li" => &["ul", "ol"],
+            "dt" | "dd" => &["dl"],
+            "tr" | "td" | "th" | "tbody" | "thead" | "tfoot" => &["table"],
+            _ => &["td", "th", "li", "blockquote", "div"],
+        };
+        let boundary = self.stack
+            .iter()
+            .rposition(|open| scope.contains(&open.tag.as_str()))
+            .map_or(0, |position| position + 1);
+        if let Some(offset) = self.stack[boundary..].iter().position(|open| closes.contains(&open.tag.as_str())) {
+            while self.stack.len() > boundary + offset {
+                self.close_current();
+            }
+        }
+
+        self.stack.push(HtmlElement {
+            tag: name,
+            attributes,
+            children: Vec::new(),
+        });
+    }
+
+    fn close(&mut self, name: &str) {
+        // Unmatched closing tags are ignored
+        if let Some(position) = self.stack.iter().rposition(|open| open.tag == name) {
+            while self.stack.len() > position {
+                self.close_current();
+            }
+        } else if name == "p" {
+            // </p> without <p> is an empty paragraph
+            self.open("p".to_string(), Vec::new());
+            self.close_current();
+        }
+    }
+
+    fn close_current(&mut self) {
+        if let Some(element) = self.stack.pop() {
+            self.current_children().push(HtmlNode::Element(element));
+        }
+    }
+
+    fn finish(mut self) -> Vec<HtmlNode> {
+        while !self.stack.is_empty() {
+            self.close_current();
+        }
+        self.root
+    }
+}
+
+/// Decodes character references. Unknown named references are kept as written.
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest[1..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '#')
+            .map(|end| end + 1)
+            .unwrap_or(rest.len());
+        let name = &rest[1..end];
+        let decoded = match name.strip_prefix('#') {
+            Some(number) => {
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => number.parse().ok(),
+                };
+                code.map(|code| char::from_u32(code).filter(|c| *c != '\0').unwrap_or('\u{FFFD}'))
+            }
+            None => named_entity(name),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end..];
+                rest = rest.strip_prefix(';').unwrap_or(rest);
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    let c = match name {
+        "amp" | "AMP" => '&',
+        "lt" | "LT" => '<',
+        "gt" | "GT" => '>',
+        "quot" | "QUOT" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "shy" => '\u{ad}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "bull" => '•',
+        "middot" => '·',
+        "euro" => '€',
+        "pound" => '£',
+        "yen" => '¥',
+        "cent" => '¢',
+        "deg" => '°',
+        "times" => '×',
+        "divide" => '÷',
+        "zwnj" => '\u{200c}',
+        "zwj" => '\u{200d}',
+        _ => return None,
+    };
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(node: &HtmlNode) -> &HtmlElement {
+        match node {
+            HtmlNode::Element(element) => element,
+            HtmlNode::Text(text) => panic!("expected an element, found text {:?}", text),
+        }
+    }
+
+    /// All text of a sanitized document, to check what survived.
+    fn text_of(nodes: &[HtmlNode]) -> String {
+        nodes
+            .iter()
+            .map(|node| match node {
+                HtmlNode::Text(text) => text.clone(),
+                HtmlNode::Element(element) => text_of(&element.children),
+            })
+            .collect()
+    }
+
+    fn tags_of(nodes: &[HtmlNode], out: &mut Vec<String>) {
+        for node in nodes {
+            if let HtmlNode::Element(element) = node {
+                out.push(element.tag.clone());
+                tags_of(&element.children, out);
+            }
+        }
+    }
+
+    fn all_tags(nodes: &[HtmlNode]) -> Vec<String> {
+        let mut out = Vec::new();
+        tags_of(nodes, &mut out);
+        out
+    }
+
+    #[test]
+    fn scripts_and_styles_are_removed_with_their_content() {
+        let nodes = sanitize(
+            "<html><head><style>p { color: red }</style><title>T</title></head>\
+             <body><p>Hello</p><script>alert('x')</script><SCRIPT type=text/javascript>steal()</SCRIPT ></body></html>",
+        );
+        assert_eq!(text_of(&nodes), "Hello");
+        assert_eq!(all_tags(&nodes), ["p"]);
+    }
+
+    #[test]
+    fn script_content_containing_markup_does_not_leak() {
+        let nodes = sanitize("<script>document.write('<p>injected</p>')</script><p>kept</p>");
+        assert_eq!(text_of(&nodes), "kept");
+    }
+
+    #[test]
+    fn event_handlers_and_style_attributes_are_dropped() {
+        let nodes = sanitize(r#"<p onclick="evil()" style="display:none" class="x">Hi</p><img src="cid:logo" onerror="evil()">"#);
+        assert!(element(&nodes[0]).attributes.is_empty());
+        assert_eq!(element(&nodes[1]).attributes, [("src".to_string(), "cid:logo".to_string())]);
+    }
+
+    #[test]
+    fn javascript_links_are_dropped() {
+        for href in ["javascript:alert(1)", "JaVaScRiPt:alert(1)", "java\tscript:alert(1)", " javascript:x", "vbscript:x", "data:text/html,<script>x</script>"] {
+            let nodes = sanitize(&format!("<a href=\"{}\">link</a>", href));
+            let link = element(&nodes[0]);
+            assert_eq!(link.attribute("href"), None, "{} should be dropped", href);
+            assert_eq!(text_of(&nodes), "link");
+        }
+    }
+
+    #[test]
+    fn web_and_mail_links_are_kept() {
+        let nodes = sanitize(r#"<a href="https://example.com/?a=1&amp;b=2">web</a><a href="mailto:bob@example.com">mail</a><a href="/relative">rel</a>"#);
+        assert_eq!(element(&nodes[0]).attribute("href"), Some("https://example.com/?a=1&b=2"));
+        assert_eq!(element(&nodes[1]).attribute("href"), Some("mailto:bob@example.com"));
+        assert_eq!(element(&nodes[2]).attribute("href"), None);
+    }
+
+    #[test]
+    fn image_sources_are_limited() {
+        let srcs = ["cid:part1@example", "https://example.com/a.png", "data:image/png;base64,AAAA", "javascript:x", "file:///etc/passwd", "data:text/html;base64,AAAA"];
+        let html: String = srcs.iter().map(|src| format!("<img src=\"{}\">", src)).collect();
+        let kept: Vec<Option<&str>> = sanitize(&html)
+            .iter()
+            .map(|node| element(node).attribute("src").map(|_| ""))
+            .collect();
+        assert_eq!(kept, [Some(""), Some(""), Some(""), None, None, None]);
+    }
+
+    #[test]
+    fn embedded_content_and_forms_are_removed() {
+        let nodes = sanitize(
+            "<iframe src=\"https://evil\">x</iframe><object data=y>z</object><embed src=a>\
+             <form action=\"https://evil\"><input name=p value=secret><button>Go</button><p>In form</p></form>\
+             <svg><script>x</script></svg>",
+        );
+        assert_eq!(text_of(&nodes), "In form");
+        assert_eq!(all_tags(&nodes), ["p"]);
+    }
+
+    #[test]
+    fn unknown_elements_keep_their_content() {
+        let nodes = sanitize("<o:p>Office</o:p> <custom-tag>text</custom-tag>");
+        assert_eq!(text_of(&nodes), "Office text");
+        assert!(all_tags(&nodes).is_empty());
+    }
+
+    #[test]
+    fn comments_and_conditional_comments_are_skipped() {
+        let nodes = sanitize("<!--[if mso]><p>Outlook</p><![endif]-->Shown<!DOCTYPE html><!-- <script>x</script> -->");
+        assert_eq!(text_of(&nodes), "Shown");
+    }
+
+    #[test]
+    fn entities_are_decoded() {
+        let nodes = sanitize("Fish &amp; chips &lt;b&gt; &#8364;5 &#x20AC;5 &nbsp;&unknown; &copy");
+        assert_eq!(text_of(&nodes), "Fish & chips <b> €5 €5 \u{a0}&unknown; ©");
+    }
+
+    #[test]
+    fn unclosed_paragraphs_and_list_items_are_closed() {
+        let nodes = sanitize("<p>one<p>two<ul><li>a<li>b</ul>");
+        assert_eq!(all_tags(&nodes), ["p", "p", "ul", "li", "li"]);
+        let list = element(&nodes[2]);
+        assert_eq!(text_of(&list.children[1..]), "b");
+    }
+
+    #[test]
+    fn table_cells_close_each_other() {
+        let nodes = sanitize("<table><tr><td>1<td>2<tr><td>3</table>");
+        let table = element(&nodes[0]);
+        let rows: Vec<&HtmlElement> = table.children.iter().map(element).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].children.len(), 2);
+        assert_eq!(text_of(&rows[1].children), "3");
+    }
+
+    #[test]
+    fn stray_markup_does_not_break_parsing() {
+        let nodes = sanitize("a < b </div> c <b>bold</i> done");
+        assert_eq!(text_of(&nodes), "a < b  c bold done");
+        let nodes = sanitize("<p title=\"unterminated>text");
+        assert_eq!(text_of(&nodes), "<p title=\"unterminated>text");
+    }
+}
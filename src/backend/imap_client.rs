@@ -1,9 +1,45 @@
 use crate::backend::credentials::{AccountCredentials, CredentialsManager};
-use crate::types::{Email, Folder};
+use crate::backend::folder_sync::{self, FolderSync, email_flags, imap_flags};
+use crate::backend::imap_stream::{ImapStream, SharedResponseLog, format_uid_set};
+use crate::backend::mailbox_name;
+use crate::backend::mail_store::StoredFolder;
+use crate::backend::mailbox_watcher::{FolderChanged, MailboxWatcher};
+use crate::backend::body_structure::{BodyPart, MessageStructure};
+use crate::backend::mime_parser::ParsedMessage;
+use crate::types::{Attachment, Email, EmailBody, EmailFlags, Folder, MessageFlag, SpecialUse, ThreadNode};
 use imap::Client;
+use imap::types::{NameAttribute, StatusAttribute, UnsolicitedResponse};
+use imap_proto::types::SectionPath;
+use native_tls::{HandshakeError, TlsConnector, TlsStream};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::net::TcpStream;
+use std::time::Duration;
+use tokio::sync::mpsc;
 
-pub type ImapSession = imap::Session<TcpStream>;
+/// A message as stored on the server, for copying it to another account.
+pub struct RawMessage {
+    pub uid: usize,
+    pub content: Vec<u8>,
+    pub flags: EmailFlags,
+    /// Arrival time on the server, kept so the copy sorts the same way
+    pub internal_date: Option<chrono::DateTime<chrono::FixedOffset>>,
+}
+
+/// Counts requested for every folder
+const STATUS_ITEMS: &str = "(MESSAGES UNSEEN RECENT UIDNEXT UIDVALIDITY)";
+
+/// Transport used for real servers
+pub type SecureStream = ImapStream<TlsStream<TcpStream>>;
+
+/// An authenticated IMAP session. Generic over the transport so helpers can run
+/// against any stream, defaulting to the TLS stream used for real servers.
+pub type ImapSession<S = SecureStream> = imap::Session<S>;
+
+// Bounds how long a silent server can stall a read (e.g. waiting for a
+// plaintext greeting on an implicit TLS port)
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug)]
 #[allow(dead_code)] // Will be used when implementing email operations
@@ -12,6 +48,30 @@ pub enum ImapError {
     Authentication(String),
     Operation(String),
     Credentials(String),
+    Certificate(String),
+    TlsUnavailable(String),
+    /// The folder's UIDVALIDITY changed, so previously listed UIDs are void
+    UidValidityChanged(String), // folder
+}
+
+impl fmt::Display for ImapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImapError::Connection(msg) => write!(f, "Connection error: {}", msg),
+            ImapError::Authentication(msg) => write!(f, "Authentication failed: {}", msg),
+            ImapError::Operation(msg) => write!(f, "IMAP error: {}", msg),
+            ImapError::Credentials(msg) => write!(f, "Credential error: {}", msg),
+            ImapError::Certificate(msg) => write!(
+                f,
+                "The server's certificate could not be verified ({}). It may be self-signed, expired or issued for another host.",
+                msg
+            ),
+            ImapError::TlsUnavailable(msg) => write!(f, "No secure connection possible: {}", msg),
+            ImapError::UidValidityChanged(folder) => {
+                write!(f, "{} was rebuilt on the server; its messages have to be reloaded", folder)
+            }
+        }
+    }
 }
 
 impl From<imap::Error> for ImapError {
@@ -20,10 +80,82 @@ impl From<imap::Error> for ImapError {
     }
 }
 
+/// How the connection is secured before credentials are sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TlsMode {
+    /// TLS from the first byte (usually port 993)
+    Implicit,
+    /// Plaintext greeting, then upgrade with STARTTLS (usually port 143)
+    StartTls,
+}
+
+impl TlsMode {
+    /// IMAP: 993 is implicit TLS and 143 STARTTLS; other ports use implicit
+    /// TLS unless the account turns it off, and then STARTTLS. Plaintext
+    /// login is never attempted.
+    pub fn for_imap_port(port: u16, use_tls: bool) -> TlsMode {
+        match port {
+            993 => TlsMode::Implicit,
+            143 => TlsMode::StartTls,
+            _ if use_tls => TlsMode::Implicit,
+            _ => TlsMode::StartTls,
+        }
+    }
+
+    /// SMTP submission: 465 is implicit TLS, anything else (587) uses STARTTLS.
+    pub fn for_smtp_port(port: u16) -> TlsMode {
+        if port == 465 {
+            TlsMode::Implicit
+        } else {
+            TlsMode::StartTls
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            TlsMode::Implicit => "implicit TLS",
+            TlsMode::StartTls => "STARTTLS",
+        }
+    }
+}
+
+/// Capabilities that change how the client talks to a server.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServerExtensions {
+    pub condstore: bool,
+    /// QRESYNC was advertised and successfully ENABLEd
+    pub qresync: bool,
+    pub uidplus: bool,
+    /// MOVE (RFC 6851)
+    pub move_command: bool,
+    /// LIST-STATUS (RFC 5819)
+    pub list_status: bool,
+    /// THREAD=REFERENCES (RFC 5256)
+    pub thread_references: bool,
+}
+
+/// The folders of an account, as listed by `ImapClient::get_folders`.
+pub struct FolderListing {
+    pub folders: Vec<Folder>,
+    /// Folders whose STATUS failed, by name, with the error
+    pub status_failures: Vec<(String, ImapError)>,
+}
+
+struct ActiveSession {
+    session: ImapSession,
+    responses: SharedResponseLog,
+    extensions: ServerExtensions,
+    /// `None` until the folder list has been read, then the Trash folder if any
+    trash_folder: Option<Option<String>>,
+    /// `None` until the folder list has been read, then the folders with a
+    /// special use such as Drafts and Sent
+    special_folders: Option<HashMap<SpecialUse, String>>,
+}
+
 #[allow(dead_code)] // Backend infrastructure for IMAP operations
 pub struct ImapClient {
     credentials_manager: CredentialsManager,
-    active_sessions: std::collections::HashMap<String, ImapSession>,
+    active_sessions: std::collections::HashMap<String, ActiveSession>,
 }
 
 impl ImapClient {
@@ -40,104 +172,424 @@ impl ImapClient {
             .get_password(&account_creds.email)
             .map_err(|e| ImapError::Credentials(e.to_string()))?;
 
-        let address = format!("{}:{}", account_creds.server, account_creds.port);
-        let tcp_stream = TcpStream::connect(&address)
-            .map_err(|e| ImapError::Connection(format!("Failed to connect to {}: {}", address, e)))?;
-
-        // For now, create a basic client - TLS support will be added later
-        let client = Client::new(tcp_stream);
-
-        let session = client
-            .login(&account_creds.email, &password)
-            .map_err(|(e, _)| ImapError::Authentication(format!("Login failed: {}", e)))?;
+        let (mut session, responses, _socket) = open_session(account_creds, &password)?;
+        let extensions = negotiate_extensions(&mut session)?;
 
-        self.active_sessions.insert(account_creds.email.clone(), session);
+        self.active_sessions.insert(account_creds.email.clone(), ActiveSession {
+            session,
+            responses,
+            extensions,
+            trash_folder: None,
+            special_folders: None,
+        });
         Ok(())
     }
 
     #[allow(dead_code)] // Will be used for account management
     pub fn disconnect(&mut self, email: &str) {
-        if let Some(mut session) = self.active_sessions.remove(email) {
-            let _ = session.logout();
+        if let Some(mut active) = self.active_sessions.remove(email) {
+            let _ = active.session.logout();
         }
     }
 
-    #[allow(dead_code)] // Will be used for folder syncing
-    pub fn get_folders(&mut self, email: &str) -> Result<Vec<Folder>, ImapError> {
-        let session = self.active_sessions
+    pub fn is_connected(&self, email: &str) -> bool {
+        self.active_sessions.contains_key(email)
+    }
+
+    fn active_session(&mut self, email: &str) -> Result<&mut ActiveSession, ImapError> {
+        self.active_sessions
             .get_mut(email)
-            .ok_or_else(|| ImapError::Operation("No active session for this account".to_string()))?;
+            .ok_or_else(|| ImapError::Operation("No active session for this account".to_string()))
+    }
 
-        let folders = session.list(Some(""), Some("*"))?;
+    /// Lists the folders with their counts. The counts come from LIST-STATUS
+    /// where the server supports it, otherwise from one STATUS per folder;
+    /// neither changes the selected folder. A folder whose STATUS fails is
+    /// still listed, with unknown counts, and the failure is returned in
+    /// `status_failures`.
+    pub fn get_folders(&mut self, email: &str) -> Result<FolderListing, ImapError> {
+        let active = self.active_session(email)?;
+        let session = &mut active.session;
+
+        // STATUS data is delivered as unsolicited responses; drop stale ones
+        collect_statuses(session);
+        let pattern = if active.extensions.list_status {
+            format!("* RETURN (STATUS {})", STATUS_ITEMS)
+        } else {
+            "*".to_string()
+        };
+        let folders = session.list(Some(""), Some(&pattern))?;
+        let mut statuses = collect_statuses(session);
+        active.trash_folder = Some(find_trash(&folders));
+        active.special_folders = Some(find_special_folders(&folders));
+        let subscribed: HashSet<String> = session
+            .lsub(Some(""), Some("*"))?
+            .iter()
+            .map(|folder| folder.name().to_string())
+            .collect();
         
         let mut result = Vec::new();
-        for folder in folders.iter() {
-            let name = folder.name();
-            let icon = match name.to_uppercase().as_str() {
-                "INBOX" => "📥",
-                "SENT" | "SENT ITEMS" => "📤", 
-                "DRAFTS" => "📝",
-                "SPAM" | "JUNK" => "🗑️",
-                "TRASH" | "DELETED" => "🗑️",
-                _ => "📁",
-            };
-            
-            // Get message count for this folder  
-            let mailbox = session.select(name)?;
-            let count = mailbox.exists as usize;
-            result.push(Folder::new(name, icon, count));
+        let mut failures = Vec::new();
+        for listed in folders.iter() {
+            let mut folder = folder_from_list(listed);
+            folder.subscribed = subscribed.contains(&folder.name);
+            // \Noselect folders have no counts
+            if folder.selectable {
+                let status = match statuses.remove(&folder.name) {
+                    Some(status) => Ok(status),
+                    None => folder_status(session, &folder.name),
+                };
+                match status {
+                    Ok(status) => apply_status(&mut folder, &status),
+                    Err(e) => failures.push((folder.name.clone(), e)),
+                }
+            }
+            result.push(folder);
         }
+        // INBOX first, so that it is what an account opens on
+        result.sort_by_key(|folder| !folder.is_inbox());
 
-        Ok(result)
+        Ok(FolderListing { folders: result, status_failures: failures })
+    }
+
+    /// Creates a folder from decoded hierarchy levels and subscribes to it.
+    /// Returns its server name.
+    pub fn create_folder(&mut self, email: &str, path: &[String]) -> Result<String, ImapError> {
+        let session = &mut self.active_session(email)?.session;
+        let name = mailbox_name::join(path, hierarchy_delimiter(session)?).map_err(ImapError::Operation)?;
+        session.create(&name)?;
+        session.subscribe(&name)?;
+        Ok(name)
+    }
+
+    /// Renames a folder, and with it its subfolders, carrying the
+    /// subscription over. Returns the new server name.
+    pub fn rename_folder(&mut self, email: &str, folder: &str, new_path: &[String]) -> Result<String, ImapError> {
+        let active = self.active_session(email)?;
+        let session = &mut active.session;
+        let new_name = mailbox_name::join(new_path, hierarchy_delimiter(session)?).map_err(ImapError::Operation)?;
+        let subscribed = !session.lsub(Some(""), Some(&quote_mailbox(folder)))?.is_empty();
+
+        session.rename(folder, &new_name)?;
+        if subscribed {
+            // Not every server moves subscriptions along with the folder
+            let _ = session.unsubscribe(folder);
+            session.subscribe(&new_name)?;
+        }
+        active.trash_folder = None;
+        active.special_folders = None;
+        Ok(new_name)
+    }
+
+    pub fn delete_folder(&mut self, email: &str, folder: &str) -> Result<(), ImapError> {
+        let active = self.active_session(email)?;
+        let session = &mut active.session;
+        session.delete(folder)?;
+        let _ = session.unsubscribe(folder);
+        active.trash_folder = None;
+        active.special_folders = None;
+        Ok(())
+    }
+
+    pub fn set_subscribed(&mut self, email: &str, folder: &str, subscribed: bool) -> Result<(), ImapError> {
+        let session = &mut self.active_session(email)?.session;
+        if subscribed {
+            session.subscribe(folder)?;
+        } else {
+            session.unsubscribe(folder)?;
+        }
+        Ok(())
     }
 
     #[allow(dead_code)] // Helper for folder operations
-    fn get_folder_count(&mut self, session: &mut ImapSession, folder_name: &str) -> Result<usize, ImapError> {
-        let mailbox = session.select(folder_name)?;
-        // Get message count from mailbox exists field
-        Ok(mailbox.exists as usize)
+    fn get_folder_count<S: Read + Write>(&mut self, session: &mut ImapSession<S>, folder_name: &str) -> Result<usize, ImapError> {
+        let mut folder = Folder::new(folder_name, "", 0);
+        apply_status(&mut folder, &folder_status(session, folder_name)?);
+        Ok(folder.total)
     }
 
-    #[allow(dead_code)] // Will be used for email fetching
-    pub fn get_emails(&mut self, email: &str, folder: &str, limit: usize) -> Result<Vec<Email>, ImapError> {
-        let session = self.active_sessions
-            .get_mut(email)
-            .ok_or_else(|| ImapError::Operation("No active session for this account".to_string()))?;
+    /// Brings a folder up to date from its stored state; see `folder_sync`.
+    pub fn sync_folder(&mut self, email: &str, folder: &str, previous: Option<&StoredFolder>, limit: usize) -> Result<FolderSync, ImapError> {
+        let active = self.active_session(email)?;
+        folder_sync::sync_folder(&mut active.session, &active.responses, active.extensions, folder, previous, limit)
+    }
 
-        session.select(folder)?;
-        
-        // Get recent messages (up to limit)
-        let messages = session.search("ALL")?;
-        let mut message_vec: Vec<u32> = messages.into_iter().collect();
-        message_vec.sort();
-        message_vec.reverse(); // Get most recent first
-        let recent_messages: Vec<u32> = message_vec.into_iter().take(limit).collect();
-
-        let mut emails = Vec::new();
-        
-        for &msg_id in &recent_messages {
-            let messages = session.fetch(msg_id.to_string(), "RFC822.HEADER")?;
-            
-            for message in messages.iter() {
-                let header = message.header().unwrap_or_default();
-                let header_str = String::from_utf8_lossy(header);
-                
-                let subject = extract_header_value(&header_str, "Subject").unwrap_or("(No Subject)".to_string());
-                let from = extract_header_value(&header_str, "From").unwrap_or("Unknown Sender".to_string());
-                let date = extract_header_value(&header_str, "Date").unwrap_or("Unknown Date".to_string());
-                
-                emails.push(Email::new(
-                    msg_id as usize,
-                    &from,
-                    "unknown@example.com", // Recipient placeholder - will be extracted from headers
-                    &subject,
-                    &date,
-                    "(Email content preview...)" // Will be loaded on demand
-                ));
+    /// Fetches the text shown in the preview. Only the BODYSTRUCTURE and the
+    /// displayed part are downloaded, so attachments cost nothing until
+    /// they are opened.
+    pub fn get_email_body(&mut self, email: &str, folder: &str, uid_validity: u32, uid: usize) -> Result<EmailBody, ImapError> {
+        let session = &mut self.active_session(email)?.session;
+
+        select_checked(session, folder, uid_validity)?;
+
+        let messages = session.uid_fetch(uid.to_string(), "BODYSTRUCTURE")?;
+        let fetch = messages
+            .iter()
+            .next()
+            .ok_or_else(|| ImapError::Operation(format!("Message {} not found in {}", uid, folder)))?;
+        let Some(structure) = fetch.bodystructure().map(MessageStructure::from_imap) else {
+            // Server sent no usable BODYSTRUCTURE; decode the whole message instead.
+            // PEEK so that loading a preview does not mark the message as read.
+            let messages = session.uid_fetch(uid.to_string(), "BODY.PEEK[]")?;
+            let raw = messages
+                .iter()
+                .find_map(|message| message.body())
+                .ok_or_else(|| ImapError::Operation(format!("Message {} not found in {}", uid, folder)))?;
+            let message = Email::from_parsed(uid, &ParsedMessage::parse(raw));
+            return Ok(EmailBody {
+                text: message.body,
+                html: message.html,
+                attachments: message.attachments,
+                inline_images: message.inline_images,
+            });
+        };
+
+        // The plain text and the HTML alternative, when there are both
+        let mut parts: Vec<&BodyPart> = structure.text_part().into_iter().collect();
+        let html_part = structure.html_part();
+        if let Some(html_part) = html_part.filter(|html_part| !parts.contains(html_part)) {
+            parts.push(html_part);
+        }
+        let contents = fetch_sections(session, uid, &parts)?;
+        let text = match (structure.text_part(), contents.first()) {
+            (Some(part), Some(raw)) => part.decode_text(raw),
+            _ => String::new(),
+        };
+        let html = html_part.and_then(|html_part| {
+            let index = parts.iter().position(|part| *part == html_part)?;
+            Some(html_part.decode_string(contents.get(index)?))
+        });
+
+        Ok(EmailBody {
+            text,
+            html,
+            attachments: structure.attachments().into_iter().map(Attachment::from_body_part).collect(),
+            inline_images: structure.inline_images().into_iter().map(Attachment::from_body_part).collect(),
+        })
+    }
+
+    /// Downloads one part of a message, e.g. an attachment, and undoes its
+    /// transfer encoding.
+    pub fn fetch_part(&mut self, email: &str, folder: &str, uid_validity: u32, uid: usize, section: &str) -> Result<Vec<u8>, ImapError> {
+        let session = &mut self.active_session(email)?.session;
+
+        select_checked(session, folder, uid_validity)?;
+
+        let messages = session.uid_fetch(uid.to_string(), "BODYSTRUCTURE")?;
+        let part = messages
+            .iter()
+            .find_map(|message| message.bodystructure())
+            .map(MessageStructure::from_imap)
+            .and_then(|structure| structure.find(section).cloned())
+            .ok_or_else(|| ImapError::Operation(format!("Part {} of message {} not found in {}", section, uid, folder)))?;
+        let raw = fetch_section(session, uid, &part)?;
+        Ok(part.decode(&raw))
+    }
+
+    /// The conversations of a whole folder as the server threads them, by
+    /// UID. None when the server does not offer THREAD=REFERENCES.
+    pub fn fetch_threads(&mut self, email: &str, folder: &str, uid_validity: u32) -> Result<Option<Vec<ThreadNode>>, ImapError> {
+        let active = self.active_session(email)?;
+        if !active.extensions.thread_references {
+            return Ok(None);
+        }
+        let session = &mut active.session;
+
+        select_checked(session, folder, uid_validity)?;
+
+        let response = session.run_command_and_read_response("UID THREAD REFERENCES UTF-8 ALL")?;
+        Ok(Some(parse_thread_response(&String::from_utf8_lossy(&response))))
+    }
+
+    /// Deletes a message by UID: moves it to the account's Trash, or removes
+    /// it for good when it already is in Trash or there is no Trash. Returns
+    /// the folder the message was moved to.
+    pub fn delete_email(&mut self, email: &str, folder: &str, uid_validity: u32, uid: usize) -> Result<Option<String>, ImapError> {
+        let trash = self.trash_folder(email)?;
+        let active = self.active_session(email)?;
+        let session = &mut active.session;
+
+        select_checked(session, folder, uid_validity)?;
+
+        let uid_set = uid.to_string();
+        match trash.filter(|trash| trash != folder) {
+            Some(trash) => {
+                move_messages(session, active.extensions, &uid_set, &trash)?;
+                Ok(Some(trash))
+            }
+            None => {
+                remove_messages(session, active.extensions, &uid_set)?;
+                Ok(None)
             }
         }
+    }
+
+    /// Moves messages to another folder of the same account.
+    pub fn move_emails(&mut self, email: &str, folder: &str, uid_validity: u32, uids: &[usize], target: &str) -> Result<(), ImapError> {
+        let active = self.active_session(email)?;
+        select_checked(&mut active.session, folder, uid_validity)?;
+        move_messages(&mut active.session, active.extensions, &uid_set(uids), target)
+    }
+
+    /// Copies messages to another folder of the same account.
+    pub fn copy_emails(&mut self, email: &str, folder: &str, uid_validity: u32, uids: &[usize], target: &str) -> Result<(), ImapError> {
+        let session = &mut self.active_session(email)?.session;
+        select_checked(session, folder, uid_validity)?;
+        session.uid_copy(uid_set(uids), quote_mailbox(target))?;
+        Ok(())
+    }
+
+    /// Permanently removes messages, bypassing Trash.
+    pub fn expunge_emails(&mut self, email: &str, folder: &str, uid_validity: u32, uids: &[usize]) -> Result<(), ImapError> {
+        let active = self.active_session(email)?;
+        select_checked(&mut active.session, folder, uid_validity)?;
+        remove_messages(&mut active.session, active.extensions, &uid_set(uids))
+    }
+
+    /// Downloads messages whole, for appending them to another account.
+    pub fn fetch_raw_emails(&mut self, email: &str, folder: &str, uid_validity: u32, uids: &[usize]) -> Result<Vec<RawMessage>, ImapError> {
+        let session = &mut self.active_session(email)?.session;
+        select_checked(session, folder, uid_validity)?;
+
+        let fetches = session.uid_fetch(uid_set(uids), "(UID FLAGS INTERNALDATE BODY.PEEK[])")?;
+        let messages: Vec<RawMessage> = fetches
+            .iter()
+            .filter_map(|fetch| {
+                Some(RawMessage {
+                    uid: fetch.uid? as usize,
+                    content: fetch.body()?.to_vec(),
+                    flags: email_flags(fetch.flags()),
+                    internal_date: fetch.internal_date(),
+                })
+            })
+            .collect();
+        if messages.len() < uids.len() {
+            return Err(ImapError::Operation(format!("Some messages are no longer in {}", folder)));
+        }
+        Ok(messages)
+    }
+
+    pub fn append_email(&mut self, email: &str, folder: &str, message: &RawMessage) -> Result<(), ImapError> {
+        let session = &mut self.active_session(email)?.session;
+        session.append_with_flags_and_date(folder, &message.content, &imap_flags(&message.flags), message.internal_date)?;
+        Ok(())
+    }
+
+    /// Sets or clears a flag with UID STORE. Returns the flags the server
+    /// reports for the messages afterwards.
+    pub fn store_flag(
+        &mut self,
+        email: &str,
+        folder: &str,
+        uid_validity: u32,
+        uids: &[usize],
+        flag: &MessageFlag,
+        value: bool,
+    ) -> Result<Vec<(usize, EmailFlags)>, ImapError> {
+        let session = &mut self.active_session(email)?.session;
+
+        select_checked(session, folder, uid_validity)?;
+
+        let change = format!("{}FLAGS ({})", if value { "+" } else { "-" }, flag.imap_name());
+        let fetches = session.uid_store(uid_set(uids), change)?;
+        Ok(fetches
+            .iter()
+            .filter_map(|fetch| {
+                // Servers should include the UID in answers to UID STORE;
+                // when there is a single message it is known either way
+                let uid = fetch.uid.map(|uid| uid as usize).or_else(|| (uids.len() == 1).then(|| uids[0]))?;
+                Some((uid, email_flags(fetch.flags())))
+            })
+            .collect())
+    }
+
+    /// Saves a draft to the account's Drafts folder, flagged \Draft and
+    /// \Seen, and removes the versions saved before it, which share its
+    /// Message-ID. Returns the Drafts folder.
+    pub fn save_draft(&mut self, email: &str, content: &[u8], message_id: &str) -> Result<String, ImapError> {
+        let drafts = self
+            .special_folder(email, SpecialUse::Drafts)?
+            .ok_or_else(|| ImapError::Operation("The account has no Drafts folder".to_string()))?;
+        let active = self.active_session(email)?;
+        let draft_flags = EmailFlags { seen: true, draft: true, ..Default::default() };
+        active.session.append_with_flags(&drafts, content, &imap_flags(&draft_flags))?;
+
+        select_folder(&mut active.session, &drafts)?;
+        let mut uids: Vec<u32> = search_message_id(&mut active.session, message_id)?;
+        // UIDs only grow, so the copy just appended has the highest
+        uids.sort_unstable();
+        uids.pop();
+        if !uids.is_empty() {
+            remove_messages(&mut active.session, active.extensions, &format_uid_set(&uids))?;
+        }
+        Ok(drafts)
+    }
+
+    /// Removes a draft, in all its saved versions, once it has been sent.
+    pub fn delete_draft(&mut self, email: &str, message_id: &str) -> Result<(), ImapError> {
+        let Some(drafts) = self.special_folder(email, SpecialUse::Drafts)? else {
+            return Ok(());
+        };
+        let active = self.active_session(email)?;
+        select_folder(&mut active.session, &drafts)?;
+        let uids = search_message_id(&mut active.session, message_id)?;
+        if !uids.is_empty() {
+            remove_messages(&mut active.session, active.extensions, &format_uid_set(&uids))?;
+        }
+        Ok(())
+    }
+
+    /// Files a copy of a sent message in the account's Sent folder, marked
+    /// \Seen. Returns the Sent folder.
+    pub fn append_sent(&mut self, email: &str, content: &[u8]) -> Result<String, ImapError> {
+        let sent = self
+            .special_folder(email, SpecialUse::Sent)?
+            .ok_or_else(|| ImapError::Operation("The account has no Sent folder".to_string()))?;
+        let active = self.active_session(email)?;
+        let sent_flags = EmailFlags { seen: true, ..Default::default() };
+        active.session.append_with_flags(&sent, content, &imap_flags(&sent_flags))?;
+        Ok(sent)
+    }
+
+    /// The account's folder for a special use, listing folders first if not
+    /// done yet.
+    fn special_folder(&mut self, email: &str, special_use: SpecialUse) -> Result<Option<String>, ImapError> {
+        let active = self.active_session(email)?;
+        if let Some(special_folders) = &active.special_folders {
+            return Ok(special_folders.get(&special_use).cloned());
+        }
+        let folders = active.session.list(Some(""), Some("*"))?;
+        let special_folders = find_special_folders(&folders);
+        let folder = special_folders.get(&special_use).cloned();
+        active.special_folders = Some(special_folders);
+        Ok(folder)
+    }
 
-        Ok(emails)
+    /// The account's Trash folder, listing folders first if not done yet.
+    fn trash_folder(&mut self, email: &str) -> Result<Option<String>, ImapError> {
+        let active = self.active_session(email)?;
+        if let Some(trash) = &active.trash_folder {
+            return Ok(trash.clone());
+        }
+        let folders = active.session.list(Some(""), Some("*"))?;
+        let trash = find_trash(&folders);
+        active.trash_folder = Some(trash.clone());
+        Ok(trash)
+    }
+
+    /// Starts watching a folder for changes on a connection of its own; see
+    /// `MailboxWatcher`.
+    pub fn watch_folder(
+        &mut self,
+        account_creds: &AccountCredentials,
+        folder: &str,
+        poll_interval: Duration,
+        changes: mpsc::UnboundedSender<FolderChanged>,
+    ) -> Result<MailboxWatcher, ImapError> {
+        let password = self.credentials_manager
+            .get_password(&account_creds.email)
+            .map_err(|e| ImapError::Credentials(e.to_string()))?;
+        Ok(MailboxWatcher::spawn(account_creds.clone(), password, folder, poll_interval, changes))
     }
 
     #[allow(dead_code)] // Will be used for credential management
@@ -159,17 +611,404 @@ impl Default for ImapClient {
     }
 }
 
-#[allow(dead_code)] // Helper function for email parsing
-fn extract_header_value(header: &str, field: &str) -> Option<String> {
-    header
-        .lines()
-        .find(|line| line.to_lowercase().starts_with(&format!("{}:", field.to_lowercase())))
-        .map(|line| {
-            line.split(':')
-                .skip(1)
-                .collect::<Vec<_>>()
-                .join(":")
-                .trim()
-                .to_string()
+/// Fetches the encoded content of one part without setting \Seen.
+fn fetch_section<S: Read + Write>(session: &mut ImapSession<S>, uid: usize, part: &BodyPart) -> Result<Vec<u8>, ImapError> {
+    Ok(fetch_sections(session, uid, &[part])?.remove(0))
+}
+
+/// Fetches several parts of a message in one request, in the given order.
+fn fetch_sections<S: Read + Write>(session: &mut ImapSession<S>, uid: usize, parts: &[&BodyPart]) -> Result<Vec<Vec<u8>>, ImapError> {
+    if parts.is_empty() {
+        return Ok(Vec::new());
+    }
+    let items: Vec<String> = parts.iter().map(|part| format!("BODY.PEEK[{}]", part.section)).collect();
+    let messages = session.uid_fetch(uid.to_string(), format!("({})", items.join(" ")))?;
+    parts
+        .iter()
+        .map(|part| {
+            let path = SectionPath::Part(part.section_path(), None);
+            messages
+                .iter()
+                .find_map(|message| message.section(&path))
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| ImapError::Operation(format!("Part {} of message {} not returned", part.section, uid)))
+        })
+        .collect()
+}
+
+/// Moves messages to another folder of the same account, with UID MOVE where
+/// available and COPY, STORE \Deleted and EXPUNGE otherwise.
+fn move_messages<S: Read + Write>(session: &mut ImapSession<S>, extensions: ServerExtensions, uid_set: &str, target: &str) -> Result<(), ImapError> {
+    if extensions.move_command {
+        session.uid_mv(uid_set, target)?;
+        return Ok(());
+    }
+    session.uid_copy(uid_set, quote_mailbox(target))?;
+    remove_messages(session, extensions, uid_set)
+}
+
+/// Reads the `* THREAD` lines of a THREAD response. Each parenthesized
+/// list is a thread: "(1 2 (3)(4 5))" is 1, answered by 2, answered in turn
+/// by 3 and by 4, which 5 answers. A list without a number of its own,
+/// "((6)(7))", holds replies to a message the server does not have.
+fn parse_thread_response(response: &str) -> Vec<ThreadNode> {
+    fn parse_list(chars: &mut std::iter::Peekable<std::str::Chars>) -> ThreadNode {
+        let mut uids = Vec::new();
+        let mut nested = Vec::new();
+        while let Some(&c) = chars.peek() {
+            match c {
+                '(' => {
+                    chars.next();
+                    nested.push(parse_list(chars));
+                }
+                ')' => {
+                    chars.next();
+                    break;
+                }
+                '0'..='9' => {
+                    let mut number = String::new();
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        number.push(digit);
+                    }
+                    uids.extend(number.parse::<usize>().ok());
+                }
+                _ => {
+                    chars.next();
+                }
+            }
+        }
+        // The numbers form a chain, each answering the one before; the
+        // nested lists answer the last of them
+        let mut node = ThreadNode { uid: None, children: nested };
+        for uid in uids.into_iter().rev() {
+            let children = if node.uid.is_some() { vec![node] } else { node.children };
+            node = ThreadNode { uid: Some(uid), children };
+        }
+        node
+    }
+
+    let mut threads = Vec::new();
+    for line in response.lines() {
+        let Some(rest) = line.strip_prefix("* THREAD") else {
+            continue;
+        };
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '(' {
+                threads.push(parse_list(&mut chars));
+            }
+        }
+    }
+    threads
+}
+
+fn uid_set(uids: &[usize]) -> String {
+    format_uid_set(&uids.iter().map(|&uid| uid as u32).collect::<Vec<_>>())
+}
+
+/// Flags messages \Deleted and expunges them.
+fn remove_messages<S: Read + Write>(session: &mut ImapSession<S>, extensions: ServerExtensions, uid_set: &str) -> Result<(), ImapError> {
+    session.uid_store(uid_set, "+FLAGS.SILENT (\\Deleted)")?;
+    expunge_messages(session, extensions, uid_set)
+}
+
+/// Expunges messages already flagged \Deleted.
+fn expunge_messages<S: Read + Write>(session: &mut ImapSession<S>, extensions: ServerExtensions, uid_set: &str) -> Result<(), ImapError> {
+    if extensions.uidplus {
+        session.uid_expunge(uid_set)?;
+    } else {
+        // Without UIDPLUS this also expunges anything else already flagged
+        // \Deleted in the folder, which is what other clients do
+        session.expunge()?;
+    }
+    Ok(())
+}
+
+/// STATUS of one folder (RFC 3501 6.3.10).
+fn folder_status<S: Read + Write>(session: &mut ImapSession<S>, folder: &str) -> Result<Vec<StatusAttribute>, ImapError> {
+    // The imap crate passes the STATUS response on as unsolicited rather
+    // than returning it
+    session.status(folder, STATUS_ITEMS)?;
+    Ok(collect_statuses(session).remove(folder).unwrap_or_default())
+}
+
+/// Takes the STATUS responses received so far, by folder name.
+fn collect_statuses<S: Read + Write>(session: &mut ImapSession<S>) -> HashMap<String, Vec<StatusAttribute>> {
+    session
+        .unsolicited_responses
+        .try_iter()
+        .filter_map(|response| match response {
+            UnsolicitedResponse::Status { mailbox, attributes } => Some((mailbox, attributes)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn apply_status(folder: &mut Folder, status: &[StatusAttribute]) {
+    for attribute in status {
+        match *attribute {
+            StatusAttribute::Messages(messages) => folder.total = messages as usize,
+            StatusAttribute::Unseen(unseen) => folder.unread = unseen as usize,
+            StatusAttribute::Recent(recent) => folder.recent = recent as usize,
+            StatusAttribute::UidNext(uid_next) => folder.uid_next = uid_next,
+            StatusAttribute::UidValidity(uid_validity) => folder.uid_validity = uid_validity,
+            StatusAttribute::HighestModSeq(_) => {}
+        }
+    }
+}
+
+/// A folder as described by LIST: its hierarchy delimiter and attributes
+/// (RFC 3501 `\Noselect`, RFC 3348 `\HasChildren`, RFC 6154 SPECIAL-USE).
+fn folder_from_list(listed: &imap::types::Name) -> Folder {
+    let mut folder = Folder::new(listed.name(), "", 0);
+    folder.delimiter = folder_delimiter(listed.delimiter());
+
+    let mut special_use = None;
+    for attribute in listed.attributes() {
+        match attribute {
+            NameAttribute::NoSelect => folder.selectable = false,
+            NameAttribute::Custom(name) if name.eq_ignore_ascii_case("\\NonExistent") => folder.selectable = false,
+            NameAttribute::Custom(name) if name.eq_ignore_ascii_case("\\HasChildren") => folder.has_children = true,
+            NameAttribute::Custom(name) => special_use = special_use.or(SpecialUse::from_attribute(name)),
+            _ => {}
+        }
+    }
+    folder.special_use = if folder.is_inbox() {
+        Some(SpecialUse::Inbox)
+    } else {
+        special_use.or_else(|| SpecialUse::from_name(&folder.leaf_name()))
+    };
+    folder.icon = folder.special_use.map(|special_use| special_use.icon()).unwrap_or("📁").to_string();
+    folder
+}
+
+/// The server's hierarchy delimiter, from `LIST "" ""` (RFC 3501 6.3.8).
+fn hierarchy_delimiter<S: Read + Write>(session: &mut ImapSession<S>) -> Result<Option<char>, ImapError> {
+    let names = session.list(Some(""), Some("\"\""))?;
+    Ok(folder_delimiter(names.iter().next().and_then(|name| name.delimiter())))
+}
+
+fn folder_delimiter(delimiter: Option<&str>) -> Option<char> {
+    delimiter.and_then(|delimiter| delimiter.chars().next())
+}
+
+/// Quotes a mailbox name for commands the imap crate passes through verbatim.
+fn quote_mailbox(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Finds the Trash folder: the one marked \Trash (RFC 6154 SPECIAL-USE), or
+/// failing that one with a conventional name.
+fn find_trash(folders: &[imap::types::Name]) -> Option<String> {
+    let marked = folders.iter().find(|folder| {
+        folder.attributes().iter().any(|attribute| {
+            matches!(attribute, imap::types::NameAttribute::Custom(name) if name.eq_ignore_ascii_case("\\Trash"))
         })
-}
\ No newline at end of file
+    });
+    let named = || {
+        folders.iter().find(|folder| {
+            let leaf = folder
+                .delimiter()
+                .and_then(|delimiter| folder.name().rsplit(delimiter).next())
+                .unwrap_or(folder.name());
+            ["Trash", "Deleted Items", "Deleted Messages", "Deleted"]
+                .iter()
+                .any(|name| leaf.eq_ignore_ascii_case(name))
+        })
+    };
+    marked.or_else(named).map(|folder| folder.name().to_string())
+}
+
+/// The folder for each special use: the one marked with the SPECIAL-USE
+/// attribute, or failing that the first with a conventional name.
+fn find_special_folders(folders: &[imap::types::Name]) -> HashMap<SpecialUse, String> {
+    let mut found = HashMap::new();
+    for folder in folders {
+        for attribute in folder.attributes() {
+            if let NameAttribute::Custom(name) = attribute
+                && let Some(special_use) = SpecialUse::from_attribute(name)
+            {
+                found.entry(special_use).or_insert_with(|| folder.name().to_string());
+            }
+        }
+    }
+    for folder in folders {
+        if let Some(special_use) = folder_from_list(folder).special_use {
+            found.entry(special_use).or_insert_with(|| folder.name().to_string());
+        }
+    }
+    found
+}
+
+/// UIDs of the messages in the selected folder with a Message-ID (given
+/// without angle brackets).
+fn search_message_id<S: Read + Write>(session: &mut ImapSession<S>, message_id: &str) -> Result<Vec<u32>, ImapError> {
+    let query = format!("HEADER Message-ID \"<{}>\"", message_id.replace('\\', "\\\\").replace('"', "\\\""));
+    Ok(session.uid_search(query)?.into_iter().collect())
+}
+
+/// Connects and logs in with the TLS mode the port calls for. Also returns a
+/// handle to the socket so that another thread can shut the connection down.
+pub(crate) fn open_session(account_creds: &AccountCredentials, password: &str) -> Result<(ImapSession, SharedResponseLog, TcpStream), ImapError> {
+    let connector = TlsConnector::new()
+        .map_err(|e| ImapError::Connection(format!("Failed to initialize TLS: {}", e)))?;
+
+    // No fallback to the other mode: on the wrong port it would only wait
+    // for a greeting that never comes
+    let mode = TlsMode::for_imap_port(account_creds.port, account_creds.use_tls);
+    let (client, responses, socket) = open_secure_client(&account_creds.server, account_creds.port, mode, &connector)
+        .map_err(|e| match e {
+            ImapError::TlsUnavailable(msg) => ImapError::TlsUnavailable(format!(
+                "{}:{} over {}: {}",
+                account_creds.server,
+                account_creds.port,
+                mode.display_name(),
+                msg
+            )),
+            e => e,
+        })?;
+
+    let session = client
+        .login(&account_creds.email, password)
+        .map_err(|(e, _)| ImapError::Authentication(format!("Login failed: {}", e)))?;
+    Ok((session, responses, socket))
+}
+
+/// Selects a folder and returns its UIDVALIDITY.
+fn select_folder<S: Read + Write>(session: &mut ImapSession<S>, folder: &str) -> Result<u32, ImapError> {
+    let mailbox = session.select(folder)?;
+    Ok(mailbox.uid_validity.unwrap_or(0))
+}
+
+/// Selects a folder, failing if UIDs read under `uid_validity` no longer
+/// refer to the same messages.
+fn select_checked<S: Read + Write>(session: &mut ImapSession<S>, folder: &str, uid_validity: u32) -> Result<(), ImapError> {
+    let current = select_folder(session, folder)?;
+    if uid_validity != 0 && current != uid_validity {
+        return Err(ImapError::UidValidityChanged(folder.to_string()));
+    }
+    Ok(())
+}
+
+/// Opens a TCP connection and secures it with the given mode, returning a
+/// client that is ready for LOGIN together with its response log and socket.
+fn open_secure_client(
+    server: &str,
+    port: u16,
+    mode: TlsMode,
+    connector: &TlsConnector,
+) -> Result<(Client<SecureStream>, SharedResponseLog, TcpStream), ImapError> {
+    let address = format!("{}:{}", server, port);
+    let mut tcp_stream = TcpStream::connect(&address)
+        .map_err(|e| ImapError::Connection(format!("Failed to connect to {}: {}", address, e)))?;
+    tcp_stream
+        .set_read_timeout(Some(IO_TIMEOUT))
+        .and_then(|_| tcp_stream.set_write_timeout(Some(IO_TIMEOUT)))
+        .map_err(|e| ImapError::Connection(format!("Failed to configure socket: {}", e)))?;
+    let socket = tcp_stream
+        .try_clone()
+        .map_err(|e| ImapError::Connection(format!("Failed to configure socket: {}", e)))?;
+
+    if mode == TlsMode::StartTls {
+        negotiate_starttls(&mut tcp_stream)?;
+    }
+
+    let tls_stream = connector
+        .connect(server, tcp_stream)
+        .map_err(handshake_error)?;
+    let stream = ImapStream::new(tls_stream);
+    let responses = stream.log();
+    let mut client = Client::new(stream);
+    // After STARTTLS the server does not greet again
+    if mode == TlsMode::Implicit {
+        client
+            .read_greeting()
+            .map_err(|e| ImapError::TlsUnavailable(format!("No greeting after TLS handshake: {}", e)))?;
+    }
+    Ok((client, responses, socket))
+}
+
+/// Reads the plaintext greeting and issues STARTTLS. Done by hand rather than
+/// with `Client::secure` so the upgraded stream can be wrapped in `ImapStream`.
+fn negotiate_starttls(tcp_stream: &mut TcpStream) -> Result<(), ImapError> {
+    let mut reader = BufReader::new(&*tcp_stream);
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|e| ImapError::TlsUnavailable(format!("No plaintext greeting: {}", e)))?;
+    if !line.starts_with("* OK") {
+        return Err(ImapError::TlsUnavailable(format!("Unexpected greeting: {}", line.trim_end())));
+    }
+
+    (&*tcp_stream)
+        .write_all(b"a0 STARTTLS\r\n")
+        .map_err(|e| ImapError::TlsUnavailable(format!("Failed to request STARTTLS: {}", e)))?;
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|e| ImapError::TlsUnavailable(format!("No answer to STARTTLS: {}", e)))?;
+        if read == 0 {
+            return Err(ImapError::TlsUnavailable("Connection closed before STARTTLS".to_string()));
+        }
+        if let Some(status) = line.strip_prefix("a0 ") {
+            return if status.to_uppercase().starts_with("OK") {
+                Ok(())
+            } else {
+                Err(ImapError::TlsUnavailable(format!("STARTTLS rejected: {}", status.trim_end())))
+            };
+        }
+    }
+}
+
+/// Reads the capabilities that matter for syncing and enables QRESYNC when
+/// offered.
+fn negotiate_extensions<S: Read + Write>(session: &mut ImapSession<S>) -> Result<ServerExtensions, ImapError> {
+    let capabilities = session.capabilities()?;
+    let mut extensions = ServerExtensions {
+        condstore: capabilities.has_str("CONDSTORE"),
+        qresync: capabilities.has_str("QRESYNC"),
+        uidplus: capabilities.has_str("UIDPLUS"),
+        move_command: capabilities.has_str("MOVE"),
+        list_status: capabilities.has_str("LIST-STATUS"),
+        thread_references: capabilities.has_str("THREAD=REFERENCES"),
+    };
+    drop(capabilities);
+
+    if extensions.qresync {
+        // ENABLE QRESYNC implies CONDSTORE; without it VANISHED is never sent
+        extensions.qresync = session.run_command_and_check_ok("ENABLE QRESYNC").is_ok();
+        extensions.condstore |= extensions.qresync;
+    }
+    Ok(extensions)
+}
+
+fn handshake_error(error: HandshakeError<TcpStream>) -> ImapError {
+    let message = error.to_string();
+    if is_certificate_error(&message) {
+        ImapError::Certificate(message)
+    } else {
+        ImapError::TlsUnavailable(format!("TLS handshake failed: {}", message))
+    }
+}
+
+// native-tls does not expose a structured error kind, so certificate failures
+// are recognized by the messages of the platform backends (OpenSSL, Schannel,
+// Security.framework)
+pub(crate) fn is_certificate_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("certificate") || message.contains("cert verify") || message.contains("trust")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_one_tls_mode_per_port() {
+        assert_eq!(TlsMode::for_imap_port(993, false), TlsMode::Implicit);
+        assert_eq!(TlsMode::for_imap_port(143, true), TlsMode::StartTls);
+        assert_eq!(TlsMode::for_imap_port(1993, true), TlsMode::Implicit);
+        assert_eq!(TlsMode::for_imap_port(1143, false), TlsMode::StartTls);
+    }
+}
@@ -0,0 +1,280 @@
+use std::io::{self, Read, Write};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Extension data seen on the wire that the imap crate does not expose.
+#[derive(Debug, Default)]
+pub struct ResponseLog {
+    /// UID ranges from `* VANISHED` responses (QRESYNC), in arrival order
+    pub vanished: Vec<RangeInclusive<u32>>,
+    /// Last `[HIGHESTMODSEQ n]` response code (CONDSTORE)
+    pub highest_modseq: Option<u64>,
+}
+
+pub type SharedResponseLog = Arc<Mutex<ResponseLog>>;
+
+/// Transport wrapper between the TLS stream and the imap crate.
+///
+/// imap-proto 0.10 fails on untagged responses it does not know, which turns
+/// a perfectly good QRESYNC session into a parse error. This stream removes
+/// those lines (`VANISHED`, `ENABLED`) before the crate sees them and records
+/// what the sync engine needs in a shared `ResponseLog`. Literals are passed
+/// through untouched, so message contents are never inspected.
+pub struct ImapStream<S> {
+    inner: S,
+    log: SharedResponseLog,
+    /// Bytes ready to be handed to the reader
+    ready: Vec<u8>,
+    ready_pos: usize,
+    /// Incomplete response line
+    line: Vec<u8>,
+    /// Bytes of a literal still to pass through unchanged
+    literal_remaining: usize,
+}
+
+impl<S> ImapStream<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            log: SharedResponseLog::default(),
+            ready: Vec::new(),
+            ready_pos: 0,
+            line: Vec::new(),
+            literal_remaining: 0,
+        }
+    }
+
+    pub fn log(&self) -> SharedResponseLog {
+        self.log.clone()
+    }
+
+    fn process(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.literal_remaining > 0 {
+                let take = self.literal_remaining.min(data.len());
+                self.ready.extend_from_slice(&data[..take]);
+                self.literal_remaining -= take;
+                data = &data[take..];
+                continue;
+            }
+
+            match data.iter().position(|&b| b == b'\n') {
+                Some(pos) => {
+                    self.line.extend_from_slice(&data[..=pos]);
+                    data = &data[pos + 1..];
+                    let line = std::mem::take(&mut self.line);
+                    self.literal_remaining = literal_length(&line).unwrap_or(0);
+                    if self.inspect(&line) {
+                        self.ready.extend_from_slice(&line);
+                    }
+                }
+                None => {
+                    self.line.extend_from_slice(data);
+                    data = &[];
+                }
+            }
+        }
+    }
+
+    /// Records interesting responses; returns whether the line is kept.
+    fn inspect(&mut self, line: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(line);
+        let upper = text.to_ascii_uppercase();
+
+        if let Some(rest) = upper.strip_prefix("* VANISHED ") {
+            let uids = rest.trim_start_matches("(EARLIER)").trim();
+            if let Ok(mut log) = self.log.lock() {
+                log.vanished.extend(parse_uid_set(uids));
+            }
+            return false;
+        }
+        if upper.starts_with("* ENABLED") {
+            return false;
+        }
+        if let Some(start) = upper.find("[HIGHESTMODSEQ ") {
+            let value = &upper[start + "[HIGHESTMODSEQ ".len()..];
+            let digits: String = value.chars().take_while(char::is_ascii_digit).collect();
+            if let (Ok(modseq), Ok(mut log)) = (digits.parse(), self.log.lock()) {
+                log.highest_modseq = Some(modseq);
+            }
+        }
+        true
+    }
+}
+
+impl<S: Read> Read for ImapStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.ready_pos >= self.ready.len() {
+            self.ready.clear();
+            self.ready_pos = 0;
+
+            let mut chunk = [0u8; 8192];
+            let read = self.inner.read(&mut chunk)?;
+            if read == 0 {
+                // Hand out whatever is left of an unterminated line
+                let rest = std::mem::take(&mut self.line);
+                if rest.is_empty() {
+                    return Ok(0);
+                }
+                self.ready = rest;
+                break;
+            }
+            self.process(&chunk[..read]);
+        }
+
+        let available = &self.ready[self.ready_pos..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.ready_pos += count;
+        Ok(count)
+    }
+}
+
+impl<S: Write> Write for ImapStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: imap::extensions::idle::SetReadTimeout> imap::extensions::idle::SetReadTimeout for ImapStream<S> {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> imap::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+}
+
+/// Length of the literal announced at the end of a line (`{123}\r\n` or the
+/// non-synchronizing `{123+}\r\n`).
+fn literal_length(line: &[u8]) -> Option<usize> {
+    let line = line.strip_suffix(b"\n")?;
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let line = line.strip_suffix(b"}")?;
+    let start = line.iter().rposition(|&b| b == b'{')?;
+    let digits = &line[start + 1..];
+    let digits = digits.strip_suffix(b"+").unwrap_or(digits);
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+/// Parses a UID set such as `1:3,7,9:10` into ranges. Ranges are not
+/// expanded since servers may report huge ones (`VANISHED (EARLIER) 1:90000`).
+/// Unbounded ranges (`*`) are not expected in server responses and are skipped.
+pub fn parse_uid_set(set: &str) -> Vec<RangeInclusive<u32>> {
+    let mut ranges = Vec::new();
+    for item in set.split(',') {
+        let item = item.trim();
+        match item.split_once(':') {
+            Some((start, end)) => {
+                if let (Ok(start), Ok(end)) = (start.parse::<u32>(), end.parse::<u32>()) {
+                    ranges.push(start.min(end)..=start.max(end));
+                }
+            }
+            None => {
+                if let Ok(uid) = item.parse() {
+                    ranges.push(uid..=uid);
+                }
+            }
+        }
+    }
+    ranges
+}
+
+/// Formats UIDs as a compact set, merging consecutive runs (`1:3,7`).
+pub fn format_uid_set(uids: &[u32]) -> String {
+    let mut sorted = uids.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut ranges: Vec<String> = Vec::new();
+    let mut iter = sorted.into_iter().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while iter.peek() == Some(&(end + 1)) {
+            end += 1;
+            iter.next();
+        }
+        ranges.push(if start == end {
+            start.to_string()
+        } else {
+            format!("{}:{}", start, end)
+        });
+    }
+    ranges.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A server that answers in reads of at most `chunk` bytes.
+    struct Chunked {
+        data: Vec<u8>,
+        pos: usize,
+        chunk: usize,
+    }
+
+    impl Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let count = self.chunk.min(buf.len()).min(self.data.len() - self.pos);
+            buf[..count].copy_from_slice(&self.data[self.pos..self.pos + count]);
+            self.pos += count;
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn parses_uid_sets() {
+        assert_eq!(parse_uid_set("1:3,7, 9:8"), vec![1..=3, 7..=7, 8..=9]);
+        assert_eq!(parse_uid_set("1:90000"), vec![1..=90000]);
+        assert_eq!(parse_uid_set("4:*,x,,5"), vec![5..=5]);
+        assert_eq!(format_uid_set(&[9, 7, 3, 1, 2, 3]), "1:3,7,9");
+        assert_eq!(format_uid_set(&[]), "");
+    }
+
+    #[test]
+    fn finds_announced_literals() {
+        assert_eq!(literal_length(b"* 1 FETCH (UID 5 BODY[] {120}\r\n"), Some(120));
+        assert_eq!(literal_length(b"A1 APPEND INBOX {7+}\n"), Some(7));
+        assert_eq!(literal_length(b"* OK {not a literal}\r\n"), None);
+        assert_eq!(literal_length(b"* 1 FETCH (BODY[] {12}"), None);
+        assert_eq!(literal_length(b"* OK done\r\n"), None);
+    }
+
+    #[test]
+    fn removes_extension_responses_outside_literals() {
+        let body = "Subject: Quoting\r\n\r\n* VANISHED 99\r\n* ENABLED\r\n";
+        let wire = format!(
+            "* ENABLED QRESYNC\r\n\
+             * OK [HIGHESTMODSEQ 715194045007] Highest\r\n\
+             * VANISHED (EARLIER) 1:3,5\r\n\
+             * 1 FETCH (UID 6 BODY[] {{{}}}\r\n{})\r\n\
+             a1 OK Fetch completed\r\n",
+            body.len(),
+            body
+        );
+        let expected = format!(
+            "* OK [HIGHESTMODSEQ 715194045007] Highest\r\n\
+             * 1 FETCH (UID 6 BODY[] {{{}}}\r\n{})\r\n\
+             a1 OK Fetch completed\r\n",
+            body.len(),
+            body
+        );
+
+        // Every split, including inside the literal and its announcement
+        for chunk in [1, 2, 7, 50, 8192] {
+            let server = Chunked { data: wire.clone().into_bytes(), pos: 0, chunk };
+            let mut stream = ImapStream::new(server);
+            let log = stream.log();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).unwrap();
+
+            assert_eq!(String::from_utf8(received).unwrap(), expected, "chunk {}", chunk);
+            let log = log.lock().unwrap();
+            assert_eq!(log.vanished, vec![1..=3, 5..=5]);
+            assert_eq!(log.highest_modseq, Some(715194045007));
+        }
+    }
+}
@@ -0,0 +1,820 @@
+use crate::backend::error_log;
+use crate::types::{Email, EmailBody, EmailFlags, Folder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bumped whenever the on-disk layout changes; see `MailStore::migrate`
+pub const SCHEMA_VERSION: u32 = 3;
+
+const META_FILE: &str = "store.json";
+const ACCOUNT_FILE: &str = "account.json";
+const MESSAGES_DIR: &str = "messages";
+const BODIES_DIR: &str = "bodies";
+const SENT_QUEUE_DIR: &str = "sent-queue";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreMeta {
+    schema_version: u32,
+}
+
+/// Bookkeeping needed to resume synchronising a folder.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncState {
+    /// UIDVALIDITY the stored UIDs belong to
+    pub uid_validity: u32,
+    /// HIGHESTMODSEQ at the last sync, 0 when the server lacks CONDSTORE
+    pub highest_modseq: u64,
+    /// Unix time of the last successful fetch, 0 if never fetched
+    pub last_sync: u64,
+}
+
+/// Message headers and flags of one folder. Bodies are stored separately.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StoredFolder {
+    pub sync: SyncState,
+    pub emails: Vec<Email>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct StoredAccount {
+    email: String,
+    folders: Vec<Folder>,
+    #[serde(skip)]
+    messages: HashMap<String, StoredFolder>,
+    /// Sent messages waiting to be copied to Sent, when there is no
+    /// directory to keep them in
+    #[serde(skip)]
+    sent_queue: Vec<(String, Vec<u8>)>,
+}
+
+/// Persistent mail store under the XDG data directory. Everything except
+/// message bodies is mirrored in memory; every change is written through
+/// with an atomic replace, so a crash leaves either the old or the new file.
+///
+/// Layout:
+/// ```text
+/// store.json                              schema version
+/// <account>/account.json                  folder list
+/// <account>/messages/<folder>.json        sync state, headers and flags
+/// <account>/bodies/<folder>/<uidvalidity>-<uid>.txt
+/// <account>/sent-queue/<time>.eml         sent messages not yet copied to Sent
+/// ```
+pub struct MailStore {
+    /// None when running without a usable data directory (memory only)
+    root: Option<PathBuf>,
+    accounts: HashMap<String, StoredAccount>,
+}
+
+impl MailStore {
+    /// Opens the store in `$XDG_DATA_HOME/mailcross/store`.
+    pub fn open_default() -> Self {
+        match dirs::data_dir() {
+            Some(dir) => Self::open(&dir.join("mailcross").join("store")),
+            None => {
+                error_log::report("No data directory available; mail will not be stored on disk");
+                Self::in_memory()
+            }
+        }
+    }
+
+    pub fn open(root: &Path) -> Self {
+        let mut store = Self::in_memory();
+        match store.prepare_root(root) {
+            Ok(()) => {
+                store.root = Some(root.to_path_buf());
+                store.load_accounts();
+            }
+            Err(e) => error_log::report(format!("Mail store at {} unavailable, keeping mail in memory: {}", root.display(), e)),
+        }
+        store
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            root: None,
+            accounts: HashMap::new(),
+        }
+    }
+
+    /// Creates the root or brings an existing one up to `SCHEMA_VERSION`.
+    fn prepare_root(&self, root: &Path) -> io::Result<()> {
+        fs::create_dir_all(root)?;
+        let meta_path = root.join(META_FILE);
+
+        let version = match fs::read(&meta_path) {
+            Ok(bytes) => match serde_json::from_slice::<StoreMeta>(&bytes) {
+                Ok(meta) => Some(meta.schema_version),
+                Err(_) => None,
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                // Fresh store
+                Some(SCHEMA_VERSION)
+            }
+            Err(e) => return Err(e),
+        };
+
+        match version {
+            Some(SCHEMA_VERSION) => {}
+            Some(version) if version > SCHEMA_VERSION => {
+                // Written by a newer release; leave it untouched
+                return Err(io::Error::other(format!(
+                    "schema version {} is newer than supported version {}",
+                    version, SCHEMA_VERSION
+                )));
+            }
+            Some(_) => Self::migrate(root)?,
+            // Unreadable metadata: the cache can be fetched again
+            None => Self::discard_cache(root)?,
+        }
+
+        write_json_atomic(&meta_path, &StoreMeta { schema_version: SCHEMA_VERSION })
+    }
+
+    /// Upgrades an older layout in place. Version 1 only kept the read state
+    /// of messages and version 2 lacked the threading headers, so their
+    /// listings are discarded and fetched again.
+    fn migrate(root: &Path) -> io::Result<()> {
+        Self::discard_cache(root)
+    }
+
+    /// Deletes everything fetched from the servers: folder lists, message
+    /// listings and bodies. Sent messages not yet copied to Sent are not
+    /// cache and are kept.
+    fn discard_cache(root: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(root)?.flatten() {
+            let dir = entry.path();
+            if !dir.is_dir() {
+                continue;
+            }
+            for name in [MESSAGES_DIR, BODIES_DIR] {
+                match fs::remove_dir_all(dir.join(name)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            match fs::remove_file(dir.join(ACCOUNT_FILE)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn load_accounts(&mut self) {
+        let Some(root) = &self.root else {
+            return;
+        };
+        let Ok(entries) = fs::read_dir(root) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            if !dir.is_dir() {
+                continue;
+            }
+            let Some(mut account) = read_json::<StoredAccount>(&dir.join(ACCOUNT_FILE)) else {
+                continue;
+            };
+
+            for folder in &account.folders {
+                let path = dir.join(MESSAGES_DIR).join(format!("{}.json", encode_component(&folder.name)));
+                // A missing or damaged file only means the folder is fetched again
+                if let Some(messages) = read_json::<StoredFolder>(&path) {
+                    account.messages.insert(folder.name.clone(), messages);
+                }
+            }
+            remove_temp_files(&dir.join(MESSAGES_DIR));
+            self.accounts.insert(account.email.clone(), account);
+        }
+    }
+
+    fn account_dir(&self, account: &str) -> Option<PathBuf> {
+        self.root.as_ref().map(|root| root.join(encode_component(account)))
+    }
+
+    fn messages_path(&self, account: &str, folder_name: &str) -> Option<PathBuf> {
+        self.account_dir(account)
+            .map(|dir| dir.join(MESSAGES_DIR).join(format!("{}.json", encode_component(folder_name))))
+    }
+
+    fn bodies_dir(&self, account: &str, folder_name: &str) -> Option<PathBuf> {
+        self.account_dir(account)
+            .map(|dir| dir.join(BODIES_DIR).join(encode_component(folder_name)))
+    }
+
+    fn body_path(&self, account: &str, folder_name: &str, uid_validity: u32, email_id: usize) -> Option<PathBuf> {
+        self.bodies_dir(account, folder_name)
+            .map(|dir| dir.join(format!("{}-{}.txt", uid_validity, email_id)))
+    }
+
+    fn html_path(&self, account: &str, folder_name: &str, uid_validity: u32, email_id: usize) -> Option<PathBuf> {
+        self.bodies_dir(account, folder_name)
+            .map(|dir| dir.join(format!("{}-{}.html", uid_validity, email_id)))
+    }
+
+    /// Downloaded attachments and other parts sit next to the body file.
+    fn part_path(&self, account: &str, folder_name: &str, uid_validity: u32, email_id: usize, section: &str) -> Option<PathBuf> {
+        self.bodies_dir(account, folder_name)
+            .map(|dir| dir.join(format!("{}-{}.{}.part", uid_validity, email_id, section)))
+    }
+
+    /// Deletes the body and any downloaded parts of a message.
+    fn remove_message_files(&self, account: &str, folder_name: &str, uid_validity: u32, email_id: usize) {
+        let Some(dir) = self.bodies_dir(account, folder_name) else {
+            return;
+        };
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if message_file_owner(&name) == Some((uid_validity, email_id)) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    fn account_mut(&mut self, account: &str) -> &mut StoredAccount {
+        self.accounts
+            .entry(account.to_string())
+            .or_insert_with(|| StoredAccount {
+                email: account.to_string(),
+                ..Default::default()
+            })
+    }
+
+    fn persist_account(&self, account: &str) {
+        let (Some(dir), Some(stored)) = (self.account_dir(account), self.accounts.get(account)) else {
+            return;
+        };
+        report_write_error(write_json_atomic(&dir.join(ACCOUNT_FILE), stored));
+    }
+
+    fn persist_messages(&self, account: &str, folder_name: &str) {
+        let Some(path) = self.messages_path(account, folder_name) else {
+            return;
+        };
+        match self.accounts.get(account).and_then(|stored| stored.messages.get(folder_name)) {
+            Some(messages) => report_write_error(write_json_atomic(&path, messages)),
+            None => {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    fn sent_queue_dir(&self, account: &str) -> Option<PathBuf> {
+        self.account_dir(account).map(|dir| dir.join(SENT_QUEUE_DIR))
+    }
+
+    /// Keeps the exact bytes of a sent message until they have been
+    /// appended to the Sent folder. Returns the id to remove them by.
+    pub fn queue_sent_copy(&mut self, account: &str, content: &[u8]) -> String {
+        let id = time_id();
+        match self.sent_queue_dir(account) {
+            Some(dir) => {
+                let path = dir.join(format!("{}.eml", id));
+                report_write_error(fs::create_dir_all(&dir).and_then(|_| write_atomic(&path, content)));
+            }
+            None => self.account_mut(account).sent_queue.push((id.clone(), content.to_vec())),
+        }
+        id
+    }
+
+    /// Sent messages still to be copied to Sent, oldest first, by id.
+    pub fn queued_sent_copies(&self, account: &str) -> Vec<(String, Vec<u8>)> {
+        let Some(dir) = self.sent_queue_dir(account) else {
+            return self.accounts.get(account).map(|stored| stored.sent_queue.clone()).unwrap_or_default();
+        };
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return Vec::new();
+        };
+        let mut queued: Vec<(String, Vec<u8>)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                let id = path.file_name()?.to_str()?.strip_suffix(".eml")?.to_string();
+                Some((id, fs::read(&path).ok()?))
+            })
+            .collect();
+        queued.sort_by(|a, b| a.0.cmp(&b.0));
+        queued
+    }
+
+    pub fn remove_sent_copy(&mut self, account: &str, id: &str) {
+        match self.sent_queue_dir(account) {
+            Some(dir) => {
+                let _ = fs::remove_file(dir.join(format!("{}.eml", id)));
+            }
+            None => {
+                if let Some(stored) = self.accounts.get_mut(account) {
+                    stored.sent_queue.retain(|(queued_id, _)| queued_id != id);
+                }
+            }
+        }
+    }
+
+    /// Records the folder list of an account, keeping stored messages of
+    /// folders that still exist.
+    pub fn store_folders(&mut self, account: &str, folders: &[Folder]) {
+        let stored = self.account_mut(account);
+        stored.folders = folders.to_vec();
+        let removed: Vec<String> = stored.messages
+            .keys()
+            .filter(|name| !folders.iter().any(|folder| &folder.name == *name))
+            .cloned()
+            .collect();
+
+        self.persist_account(account);
+        for folder_name in removed {
+            self.clear_folder(account, &folder_name);
+        }
+    }
+
+    /// Stores the result of a folder sync. Bodies of messages that are no
+    /// longer listed are deleted.
+    pub fn store_synced_folder(&mut self, account: &str, folder: Folder, mut emails: Vec<Email>, sync: SyncState) {
+        let folder_name = folder.name.clone();
+        let uid_validity = sync.uid_validity;
+        match self.get_stored(account, &folder_name) {
+            Some(stored) if stored.sync.uid_validity != uid_validity => self.clear_folder(account, &folder_name),
+            Some(stored) => {
+                // Headers are refetched without attachments; keep those
+                // already learnt from loaded bodies
+                for email in emails.iter_mut().filter(|email| email.attachments.is_empty() && email.inline_images.is_empty()) {
+                    if let Some(old) = stored.emails.iter().find(|old| old.id == email.id) {
+                        email.attachments = old.attachments.clone();
+                        email.inline_images = old.inline_images.clone();
+                    }
+                }
+                let dropped: Vec<usize> = stored.emails
+                    .iter()
+                    .filter(|old| !emails.iter().any(|email| email.id == old.id))
+                    .map(|old| old.id)
+                    .collect();
+                for email_id in dropped {
+                    self.remove_message_files(account, &folder_name, uid_validity, email_id);
+                }
+            }
+            None => {}
+        }
+
+        let stored = self.account_mut(account);
+        match stored.folders.iter_mut().find(|f| f.name == folder_name) {
+            Some(existing) => *existing = folder,
+            None => stored.folders.push(folder),
+        }
+
+        // Bodies live in their own files; keep the index small
+        let emails = emails
+            .into_iter()
+            .map(|mut email| {
+                email.body.clear();
+                email.body_loaded = false;
+                email.html = None;
+                email
+            })
+            .collect();
+        stored.messages.insert(folder_name.clone(), StoredFolder {
+            sync: SyncState {
+                last_sync: unix_now(),
+                ..sync
+            },
+            emails,
+        });
+
+        self.persist_account(account);
+        self.persist_messages(account, &folder_name);
+    }
+
+    fn get_stored(&self, account: &str, folder_name: &str) -> Option<&StoredFolder> {
+        self.accounts.get(account)?.messages.get(folder_name)
+    }
+
+    /// Stored contents of a folder that has been fetched at least once.
+    pub fn get_folder(&self, account: &str, folder_name: &str) -> Option<&StoredFolder> {
+        self.get_stored(account, folder_name)
+            .filter(|stored| stored.sync.last_sync > 0)
+    }
+
+    pub fn get_folders(&self, account: &str) -> Vec<Folder> {
+        self.accounts
+            .get(account)
+            .map(|stored| stored.folders.clone())
+            .unwrap_or_default()
+    }
+
+    /// Keeps a loaded body. The text and HTML go to their own files; the
+    /// attachment lists are small and stay in the index.
+    pub fn store_email_body(&mut self, account: &str, folder_name: &str, uid_validity: u32, email_id: usize, body: &EmailBody) {
+        if self.find_email(account, folder_name, uid_validity, email_id).is_none() {
+            return;
+        }
+        let body_file = self.bodies_dir(account, folder_name)
+            .zip(self.body_path(account, folder_name, uid_validity, email_id));
+        if let Some((dir, path)) = &body_file {
+            report_write_error(fs::create_dir_all(dir).and_then(|_| write_atomic(path, body.text.as_bytes())));
+            if let (Some(html), Some(html_path)) = (&body.html, self.html_path(account, folder_name, uid_validity, email_id)) {
+                report_write_error(write_atomic(&html_path, html.as_bytes()));
+            }
+        }
+        if let Some(email) = self.accounts.get_mut(account)
+            .and_then(|stored| stored.messages.get_mut(folder_name))
+            .and_then(|stored| stored.emails.iter_mut().find(|e| e.id == email_id))
+        {
+            email.attachments = body.attachments.clone();
+            email.inline_images = body.inline_images.clone();
+            if body_file.is_none() {
+                // Memory-only store: keep the body in the index instead
+                email.body = body.text.clone();
+                email.html = body.html.clone();
+                email.body_loaded = true;
+            }
+        }
+        self.persist_messages(account, folder_name);
+    }
+
+    pub fn get_email_body(&self, account: &str, folder_name: &str, uid_validity: u32, email_id: usize) -> Option<EmailBody> {
+        let email = self.find_email(account, folder_name, uid_validity, email_id)?;
+        let (text, html) = if email.body_loaded {
+            (email.body.clone(), email.html.clone())
+        } else {
+            let text = fs::read_to_string(self.body_path(account, folder_name, uid_validity, email_id)?).ok()?;
+            let html = self.html_path(account, folder_name, uid_validity, email_id)
+                .and_then(|path| fs::read_to_string(path).ok());
+            (text, html)
+        };
+        Some(EmailBody {
+            text,
+            html,
+            attachments: email.attachments.clone(),
+            inline_images: email.inline_images.clone(),
+        })
+    }
+
+    /// Keeps a downloaded part of a message so it is fetched only once.
+    /// Memory-only stores keep nothing; parts can be large.
+    pub fn store_email_part(&mut self, account: &str, folder_name: &str, uid_validity: u32, email_id: usize, section: &str, content: &[u8]) {
+        if self.find_email(account, folder_name, uid_validity, email_id).is_none() {
+            return;
+        }
+        if let (Some(dir), Some(path)) = (
+            self.bodies_dir(account, folder_name),
+            self.part_path(account, folder_name, uid_validity, email_id, section),
+        ) {
+            report_write_error(fs::create_dir_all(&dir).and_then(|_| write_atomic(&path, content)));
+        }
+    }
+
+    pub fn get_email_part(&self, account: &str, folder_name: &str, uid_validity: u32, email_id: usize, section: &str) -> Option<Vec<u8>> {
+        self.find_email(account, folder_name, uid_validity, email_id)?;
+        fs::read(self.part_path(account, folder_name, uid_validity, email_id, section)?).ok()
+    }
+
+    fn find_email(&self, account: &str, folder_name: &str, uid_validity: u32, email_id: usize) -> Option<&Email> {
+        self.get_stored(account, folder_name)
+            .filter(|stored| stored.sync.uid_validity == uid_validity)?
+            .emails
+            .iter()
+            .find(|e| e.id == email_id)
+    }
+
+    pub fn remove_email(&mut self, account: &str, folder_name: &str, uid_validity: u32, email_id: usize) {
+        let removed = match self.accounts.get_mut(account)
+            .and_then(|stored| stored.messages.get_mut(folder_name))
+            .filter(|stored| stored.sync.uid_validity == uid_validity)
+        {
+            Some(stored) => {
+                stored.emails.retain(|e| e.id != email_id);
+                true
+            }
+            None => false,
+        };
+        if removed {
+            self.persist_messages(account, folder_name);
+            self.remove_message_files(account, folder_name, uid_validity, email_id);
+        }
+    }
+
+    /// Records flags reported by the server for stored messages.
+    pub fn update_flags(&mut self, account: &str, folder_name: &str, uid_validity: u32, flags: &[(usize, EmailFlags)]) {
+        let Some(stored) = self.accounts.get_mut(account)
+            .and_then(|stored| stored.messages.get_mut(folder_name))
+            .filter(|stored| stored.sync.uid_validity == uid_validity)
+        else {
+            return;
+        };
+        for (email_id, new_flags) in flags {
+            if let Some(email) = stored.emails.iter_mut().find(|e| e.id == *email_id) {
+                email.flags = new_flags.clone();
+            }
+        }
+        self.persist_messages(account, folder_name);
+    }
+
+    /// Drops a stored folder whose UIDVALIDITY differs from the server's, as
+    /// its UIDs may now refer to different messages. Returns whether
+    /// anything was dropped.
+    pub fn invalidate_if_uid_validity_changed(&mut self, account: &str, folder_name: &str, uid_validity: u32) -> bool {
+        let stale = uid_validity != 0
+            && self.get_stored(account, folder_name)
+                .is_some_and(|stored| stored.sync.uid_validity != uid_validity);
+        if stale {
+            self.clear_folder(account, folder_name);
+        }
+        stale
+    }
+
+    /// Forgets the messages and bodies of a folder; the folder itself stays listed.
+    pub fn clear_folder(&mut self, account: &str, folder_name: &str) {
+        if let Some(stored) = self.accounts.get_mut(account) {
+            stored.messages.remove(folder_name);
+        }
+        self.persist_messages(account, folder_name);
+        if let Some(dir) = self.bodies_dir(account, folder_name) {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+
+    #[allow(dead_code)] // Will be used for account removal
+    pub fn clear_account(&mut self, account: &str) {
+        self.accounts.remove(account);
+        if let Some(dir) = self.account_dir(account) {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+
+    /// Deletes body and part files of messages that are no longer listed.
+    #[allow(dead_code)] // Will be used for maintenance
+    pub fn prune_orphaned_bodies(&self) {
+        for (account, stored) in &self.accounts {
+            for (folder_name, messages) in &stored.messages {
+                let Some(dir) = self.bodies_dir(account, folder_name) else {
+                    continue;
+                };
+                let Ok(entries) = fs::read_dir(&dir) else {
+                    continue;
+                };
+                for entry in entries.flatten() {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    let listed = message_file_owner(&name).is_some_and(|(uid_validity, email_id)| {
+                        uid_validity == messages.sync.uid_validity
+                            && messages.emails.iter().any(|email| email.id == email_id)
+                    });
+                    if !listed {
+                        let _ = fs::remove_file(entry.path());
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for MailStore {
+    fn default() -> Self {
+        Self::open_default()
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// A file name that sorts in the order the names were made: the current
+/// time in nanoseconds, zero-padded.
+pub(crate) fn time_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+    format!("{:020}", nanos)
+}
+
+/// UIDVALIDITY and UID of the message a body or part file belongs to.
+fn message_file_owner(file_name: &str) -> Option<(u32, usize)> {
+    let stem = file_name.split('.').next()?;
+    let (uid_validity, email_id) = stem.split_once('-')?;
+    Some((uid_validity.parse().ok()?, email_id.parse().ok()?))
+}
+
+/// Makes an account or folder name safe to use as a single path component.
+fn encode_component(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for (i, byte) in name.bytes().enumerate() {
+        let plain = byte.is_ascii_alphanumeric()
+            || matches!(byte, b'@' | b'_' | b'-')
+            || (byte == b'.' && i > 0);
+        if plain {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    if out.is_empty() {
+        out.push('%');
+    }
+    out
+}
+
+pub(crate) fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Option<T> {
+    let bytes = fs::read(path).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+pub(crate) fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let bytes = serde_json::to_vec(value).map_err(io::Error::other)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(path, &bytes)
+}
+
+/// Writes to a temporary file, syncs it and renames it over `path`, so
+/// readers never observe a partially written file.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = PathBuf::from(temp_name);
+
+    let mut file = File::create(&temp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&temp_path, path)?;
+    // Make the rename itself durable
+    if let Some(dir) = path.parent()
+        && let Ok(dir) = File::open(dir)
+    {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Leftovers of writes interrupted by a crash.
+pub(crate) fn remove_temp_files(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.path().extension().is_some_and(|ext| ext == "tmp") {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+fn report_write_error(result: io::Result<()>) {
+    if let Err(e) = result {
+        error_log::report(format!("Failed to write mail store: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("mailcross-store-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    fn folder(name: &str, uid_validity: u32) -> Folder {
+        let mut folder = Folder::new(name, "", 0);
+        folder.uid_validity = uid_validity;
+        folder
+    }
+
+    fn store_inbox(store: &mut MailStore, uid_validity: u32, ids: &[usize]) {
+        let emails = ids.iter().map(|&id| Email::new(id, "a@example.com", "b@example.com", "Hi", "", "")).collect();
+        let sync = SyncState { uid_validity, highest_modseq: 7, last_sync: 0 };
+        store.store_synced_folder("me@example.com", folder("INBOX", uid_validity), emails, sync);
+    }
+
+    fn files_named(dir: &Path, suffix: &str) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                found.extend(files_named(&path, suffix));
+            } else if path.to_string_lossy().ends_with(suffix) {
+                found.push(path);
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn reloads_what_was_stored() {
+        let root = temp_root("round-trip");
+        let mut store = MailStore::open(&root);
+        store_inbox(&mut store, 5, &[1, 2]);
+        let body = EmailBody { text: "Hello".to_string(), html: Some("<p>Hello</p>".to_string()), ..Default::default() };
+        store.store_email_body("me@example.com", "INBOX", 5, 2, &body);
+        store.store_email_part("me@example.com", "INBOX", 5, 2, "2", b"part");
+        store.update_flags("me@example.com", "INBOX", 5, &[(1, EmailFlags { seen: true, ..Default::default() })]);
+
+        let reopened = MailStore::open(&root);
+        let stored = reopened.get_folder("me@example.com", "INBOX").cloned();
+        let body = reopened.get_email_body("me@example.com", "INBOX", 5, 2);
+        let part = reopened.get_email_part("me@example.com", "INBOX", 5, 2, "2");
+        let folders = reopened.get_folders("me@example.com");
+        let _ = fs::remove_dir_all(&root);
+
+        let stored = stored.unwrap();
+        assert_eq!(stored.sync.highest_modseq, 7);
+        assert_eq!(stored.emails.iter().map(|email| email.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(stored.emails[0].flags.seen);
+        let body = body.unwrap();
+        assert_eq!(body.text, "Hello");
+        assert_eq!(body.html.as_deref(), Some("<p>Hello</p>"));
+        assert_eq!(part.as_deref(), Some(&b"part"[..]));
+        assert_eq!(folders.len(), 1);
+        // Another UIDVALIDITY means other messages
+        assert!(reopened.get_email_body("me@example.com", "INBOX", 6, 2).is_none());
+    }
+
+    #[test]
+    fn drops_folders_whose_uid_validity_changed() {
+        let root = temp_root("uid-validity");
+        let mut store = MailStore::open(&root);
+        store_inbox(&mut store, 5, &[1]);
+        store.store_email_body("me@example.com", "INBOX", 5, 1, &EmailBody { text: "old".to_string(), ..Default::default() });
+
+        assert!(!store.invalidate_if_uid_validity_changed("me@example.com", "INBOX", 5));
+        assert!(!store.invalidate_if_uid_validity_changed("me@example.com", "INBOX", 0));
+        assert!(store.invalidate_if_uid_validity_changed("me@example.com", "INBOX", 9));
+        let cleared = store.get_folder("me@example.com", "INBOX").is_none();
+        let bodies = files_named(&root, ".txt");
+
+        // Storing under a new UIDVALIDITY replaces the old listing too
+        store_inbox(&mut store, 5, &[1]);
+        store.store_email_body("me@example.com", "INBOX", 5, 1, &EmailBody { text: "old".to_string(), ..Default::default() });
+        store_inbox(&mut store, 9, &[3]);
+        let replaced = store.get_email_body("me@example.com", "INBOX", 5, 1).is_none();
+        let remaining = files_named(&root, ".txt");
+        let _ = fs::remove_dir_all(&root);
+
+        assert!(cleared);
+        assert!(bodies.is_empty());
+        assert!(replaced);
+        assert!(remaining.is_empty());
+    }
+
+    #[test]
+    fn leaves_no_partial_files() {
+        let root = temp_root("atomic");
+        let mut store = MailStore::open(&root);
+        store_inbox(&mut store, 5, &[1, 2, 3]);
+        let left_over = files_named(&root, ".tmp");
+
+        // A write interrupted by a crash
+        let messages = root.join(encode_component("me@example.com")).join(MESSAGES_DIR);
+        fs::write(messages.join("INBOX.json.tmp"), b"{\"sync\":").unwrap();
+        let reopened = MailStore::open(&root);
+        let cleaned = files_named(&root, ".tmp");
+        let stored = reopened.get_folder("me@example.com", "INBOX").map(|stored| stored.emails.len());
+        let _ = fs::remove_dir_all(&root);
+
+        assert!(left_over.is_empty());
+        assert!(cleaned.is_empty());
+        assert_eq!(stored, Some(3));
+    }
+
+    #[test]
+    fn discards_the_cache_of_other_schema_versions() {
+        let root = temp_root("schema");
+        let mut store = MailStore::open(&root);
+        store_inbox(&mut store, 5, &[1]);
+        let queued = store.queue_sent_copy("me@example.com", b"sent");
+
+        // An older layout: the cache goes, unsent copies stay
+        write_json_atomic(&root.join(META_FILE), &StoreMeta { schema_version: 1 }).unwrap();
+        let migrated = MailStore::open(&root);
+        let after_migration = migrated.get_folder("me@example.com", "INBOX").is_none();
+        let kept = migrated.queued_sent_copies("me@example.com");
+        let version = read_json::<StoreMeta>(&root.join(META_FILE)).map(|meta| meta.schema_version);
+
+        // A newer one is left alone and not used
+        write_json_atomic(&root.join(META_FILE), &StoreMeta { schema_version: SCHEMA_VERSION + 1 }).unwrap();
+        let newer = MailStore::open(&root);
+        let untouched = read_json::<StoreMeta>(&root.join(META_FILE)).map(|meta| meta.schema_version);
+        let _ = fs::remove_dir_all(&root);
+
+        assert!(after_migration);
+        assert_eq!(kept, vec![(queued, b"sent".to_vec())]);
+        assert_eq!(version, Some(SCHEMA_VERSION));
+        assert!(newer.root.is_none());
+        assert_eq!(untouched, Some(SCHEMA_VERSION + 1));
+    }
+}
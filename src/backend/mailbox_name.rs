@@ -0,0 +1,144 @@
+//! Mailbox names as they travel over IMAP.
+//!
+//! Servers name mailboxes in modified UTF-7 (RFC 3501 section 5.1.3): printable
+//! ASCII stands for itself, `&` is written `&-`, and anything else is UTF-16
+//! in base64 between `&` and `-`. Names are kept in that form everywhere
+//! they identify a mailbox and only decoded for display, so that a name the
+//! server encoded unusually still round-trips.
+
+use base64::Engine;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+
+const MUTF7: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::IMAP_MUTF7,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::RequireNone),
+);
+
+/// Decodes a modified UTF-7 name, returning it unchanged if it is malformed.
+pub fn decode(name: &str) -> String {
+    try_decode(name).unwrap_or_else(|| name.to_string())
+}
+
+fn try_decode(name: &str) -> Option<String> {
+    let mut result = String::new();
+    let mut rest = name;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let shifted = &rest[start + 1..];
+        let end = shifted.find('-')?;
+        if end == 0 {
+            result.push('&');
+        } else {
+            let bytes = MUTF7.decode(&shifted[..end]).ok()?;
+            if bytes.len() % 2 != 0 {
+                return None;
+            }
+            let units: Vec<u16> = bytes.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            result.push_str(&String::from_utf16(&units).ok()?);
+        }
+        rest = &shifted[end + 1..];
+    }
+    result.push_str(rest);
+    Some(result)
+}
+
+/// Encodes a name typed by the user for the server.
+pub fn encode(name: &str) -> String {
+    let mut result = String::new();
+    let mut pending: Vec<u16> = Vec::new();
+    for c in name.chars() {
+        if (' '..='~').contains(&c) {
+            flush_shifted(&mut result, &mut pending);
+            if c == '&' {
+                result.push_str("&-");
+            } else {
+                result.push(c);
+            }
+        } else {
+            let mut buffer = [0u16; 2];
+            pending.extend_from_slice(c.encode_utf16(&mut buffer));
+        }
+    }
+    flush_shifted(&mut result, &mut pending);
+    result
+}
+
+fn flush_shifted(result: &mut String, pending: &mut Vec<u16>) {
+    if pending.is_empty() {
+        return;
+    }
+    let bytes: Vec<u8> = pending.iter().flat_map(|unit| unit.to_be_bytes()).collect();
+    result.push('&');
+    result.push_str(&MUTF7.encode(bytes));
+    result.push('-');
+    pending.clear();
+}
+
+/// Splits a name into its decoded hierarchy levels.
+pub fn components(name: &str, delimiter: Option<char>) -> Vec<String> {
+    match delimiter {
+        Some(delimiter) => name.split(delimiter).map(decode).collect(),
+        None => vec![decode(name)],
+    }
+}
+
+/// Builds the server name of a mailbox from decoded hierarchy levels.
+pub fn join(components: &[String], delimiter: Option<char>) -> Result<String, String> {
+    if components.is_empty() || components.iter().any(|component| component.trim().is_empty()) {
+        return Err("Folder names cannot be empty".to_string());
+    }
+    match delimiter {
+        Some(delimiter) => {
+            if let Some(component) = components.iter().find(|component| component.contains(delimiter)) {
+                return Err(format!("\"{}\" contains the hierarchy delimiter {}", component, delimiter));
+            }
+            let encoded: Vec<String> = components.iter().map(|component| encode(component)).collect();
+            Ok(encoded.join(&delimiter.to_string()))
+        }
+        None if components.len() > 1 => Err("This server does not support subfolders".to_string()),
+        None => Ok(encode(&components[0])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(decoded: &str, encoded: &str) {
+        assert_eq!(encode(decoded), encoded);
+        assert_eq!(decode(encoded), decoded);
+    }
+
+    #[test]
+    fn round_trips_names() {
+        round_trip("Entwürfe", "Entw&APw-rfe");
+        round_trip("Tom & Jerry", "Tom &- Jerry");
+        round_trip("&", "&-");
+        round_trip("INBOX", "INBOX");
+        round_trip("~peter/mail/台北", "~peter/mail/&U,BTFw-");
+        // Outside the BMP, as a surrogate pair
+        round_trip("📁 Files", "&2D3cwQ- Files");
+    }
+
+    #[test]
+    fn leaves_malformed_names_alone() {
+        for name in ["&APw", "Entw&APw", "&!!-", "&AA-", "&2D0-"] {
+            assert_eq!(decode(name), name);
+        }
+    }
+
+    #[test]
+    fn joins_levels_with_the_delimiter() {
+        let levels = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        assert_eq!(join(&levels(&["Archiv", "Entwürfe"]), Some('/')), Ok("Archiv/Entw&APw-rfe".to_string()));
+        assert_eq!(join(&levels(&["INBOX", "A&B"]), Some('.')), Ok("INBOX.A&-B".to_string()));
+        assert!(join(&levels(&["INBOX", "v1.2"]), Some('.')).is_err());
+        assert!(join(&levels(&["a/b"]), Some('/')).is_err());
+        assert!(join(&levels(&["INBOX", " "]), Some('.')).is_err());
+        assert!(join(&levels(&["a", "b"]), None).is_err());
+        assert_eq!(components("INBOX.Entw&APw-rfe", Some('.')), levels(&["INBOX", "Entwürfe"]));
+    }
+}
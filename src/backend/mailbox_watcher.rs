@@ -0,0 +1,187 @@
+//! Change notifications for one folder.
+//!
+//! The watcher keeps a connection of its own so that waiting for the server
+//! never blocks the worker's session. With IDLE the server pushes changes as
+//! they happen; otherwise the folder is polled with NOOP. Either way the
+//! watcher only reports *that* the folder changed - the worker then runs the
+//! regular incremental sync to find out what changed.
+
+use crate::backend::credentials::AccountCredentials;
+use crate::backend::error_log;
+use crate::backend::imap_client::{ImapError, ImapSession, open_session};
+use imap::extensions::idle::WaitOutcome;
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc as std_mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+// RFC 2177: servers may log off a client that has been idling for 30
+// minutes, so IDLE is ended and re-issued well before that
+const IDLE_REARM: Duration = Duration::from_secs(25 * 60);
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(5);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(5 * 60);
+
+/// The watched folder may have changed on the server.
+#[derive(Debug, Clone)]
+pub struct FolderChanged {
+    pub account_email: String,
+    pub folder: String,
+}
+
+/// Watches one folder on a background thread until dropped.
+pub struct MailboxWatcher {
+    /// Dropping the sender tells the thread to stop
+    stop: Option<std_mpsc::Sender<()>>,
+    /// Socket of the current connection, shut down to interrupt a blocking wait
+    socket: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl MailboxWatcher {
+    pub fn spawn(
+        credentials: AccountCredentials,
+        password: String,
+        folder: &str,
+        poll_interval: Duration,
+        changes: mpsc::UnboundedSender<FolderChanged>,
+    ) -> Self {
+        let (stop, stopped) = std_mpsc::channel();
+        let socket = Arc::new(Mutex::new(None));
+        let task = WatchTask {
+            credentials,
+            password,
+            folder: folder.to_string(),
+            poll_interval,
+            changes,
+            stopped,
+            socket: socket.clone(),
+        };
+        std::thread::spawn(move || task.run());
+
+        Self { stop: Some(stop), socket }
+    }
+}
+
+impl Drop for MailboxWatcher {
+    fn drop(&mut self) {
+        // Signal first so the thread sees the broken connection as intended
+        self.stop.take();
+        if let Ok(socket) = self.socket.lock()
+            && let Some(socket) = socket.as_ref() {
+            let _ = socket.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+struct WatchTask {
+    credentials: AccountCredentials,
+    password: String,
+    folder: String,
+    poll_interval: Duration,
+    changes: mpsc::UnboundedSender<FolderChanged>,
+    stopped: std_mpsc::Receiver<()>,
+    socket: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl WatchTask {
+    /// Watches until stopped, reconnecting with backoff when the connection
+    /// fails.
+    fn run(self) {
+        let mut delay = RECONNECT_DELAY_MIN;
+        let mut reconnecting = false;
+        loop {
+            let started = Instant::now();
+            match self.watch(reconnecting) {
+                Ok(()) => return,
+                Err(_) if self.is_stopped() => return,
+                Err(e) => error_log::report(format!("Watching {} for {} failed, retrying: {}", self.folder, self.credentials.email, e)),
+            }
+
+            // A connection that held up for a while is not what is failing
+            if started.elapsed() > RECONNECT_DELAY_MAX {
+                delay = RECONNECT_DELAY_MIN;
+            }
+            if !self.sleep(delay) {
+                return;
+            }
+            delay = (delay * 2).min(RECONNECT_DELAY_MAX);
+            reconnecting = true;
+        }
+    }
+
+    /// One connection's lifetime. Returns `Ok` only once stopped.
+    fn watch(&self, reconnecting: bool) -> Result<(), ImapError> {
+        let (mut session, _responses, socket) = open_session(&self.credentials, &self.password)?;
+        if let Ok(mut slot) = self.socket.lock() {
+            *slot = Some(socket);
+        }
+        // Stopped before the socket could be shut down
+        if self.is_stopped() {
+            let _ = session.logout();
+            return Ok(());
+        }
+
+        let idle = session.capabilities()?.has_str("IDLE");
+        // EXAMINE, since nothing is changed over this connection
+        session.examine(&self.folder)?;
+        if reconnecting {
+            // Changes made while disconnected were not reported
+            self.notify();
+        }
+
+        while !self.is_stopped() {
+            let changed = if idle {
+                wait_idle(&mut session)?
+            } else {
+                self.poll(&mut session)?
+            };
+            if changed {
+                self.notify();
+            }
+        }
+        let _ = session.logout();
+        Ok(())
+    }
+
+    fn poll(&self, session: &mut ImapSession) -> Result<bool, ImapError> {
+        if !self.sleep(self.poll_interval) {
+            return Ok(false);
+        }
+        let response = session.run_command_and_read_response("NOOP")?;
+        Ok(reports_change(&response))
+    }
+
+    fn notify(&self) {
+        let _ = self.changes.send(FolderChanged {
+            account_email: self.credentials.email.clone(),
+            folder: self.folder.clone(),
+        });
+    }
+
+    fn is_stopped(&self) -> bool {
+        matches!(self.stopped.try_recv(), Err(std_mpsc::TryRecvError::Disconnected))
+    }
+
+    /// Sleeps unless stopped first; returns whether to keep going.
+    fn sleep(&self, duration: Duration) -> bool {
+        matches!(self.stopped.recv_timeout(duration), Err(std_mpsc::RecvTimeoutError::Timeout))
+    }
+}
+
+/// Waits for the server to report a change. Returns `false` when the IDLE
+/// timed out; it has then been ended and is re-issued by the next call.
+fn wait_idle(session: &mut ImapSession) -> Result<bool, ImapError> {
+    let outcome = session.idle()?.wait_with_timeout(IDLE_REARM)?;
+    Ok(matches!(outcome, WaitOutcome::MailboxChanged))
+}
+
+/// Whether a NOOP response carries untagged mailbox updates.
+fn reports_change(response: &[u8]) -> bool {
+    String::from_utf8_lossy(response).lines().any(|line| {
+        let line = line.to_ascii_uppercase();
+        line.starts_with("* ")
+            && ["EXISTS", "EXPUNGE", "FETCH", "RECENT"]
+                .iter()
+                .any(|keyword| line.contains(keyword))
+    })
+}
@@ -0,0 +1,503 @@
+//! Turns a composed message into RFC 5322 wire format.
+//!
+//! The same bytes are submitted over SMTP, saved as a draft and copied to
+//! the Sent folder, so everything that makes a message well-formed lives
+//! here: encoded-words for non-ASCII headers, header folding, Message-ID and
+//! Date, reply threading headers, and transfer encodings that keep every
+//! line within the limits of RFC 5322 and RFC 2045.
+
+use crate::backend::mime_parser::{ParsedMessage, PartBody, message_ids, parse_parameters};
+use crate::types::{AddressError, Mailbox, MessageRef, OutgoingAttachment, OutgoingEmail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, FixedOffset, Local};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Lines are folded to stay within this length where they can be
+const MAX_LINE: usize = 78;
+
+/// Encoded lines of base64 and quoted-printable content
+const MAX_ENCODED_LINE: usize = 76;
+
+/// UTF-8 bytes per encoded-word: 64 characters once encoded, within the 75
+/// allowed by RFC 2047 and short enough to follow a header name on one line
+const ENCODED_WORD_BYTES: usize = 39;
+
+/// Header of a saved draft naming the message it replies to, so that it
+/// can be marked answered once the draft is sent
+pub const REPLYING_TO_HEADER: &str = "X-Mailcross-Replying-To";
+
+/// Keeps Message-IDs generated within the same nanosecond apart
+static MESSAGE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A message in wire format, with CRLF line endings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuiltMessage {
+    /// Message-ID without angle brackets
+    pub message_id: String,
+    pub bytes: Vec<u8>,
+}
+
+pub struct MessageBuilder<'a> {
+    email: &'a OutgoingEmail,
+    date: DateTime<FixedOffset>,
+    message_id: Option<String>,
+    include_bcc: bool,
+    extra_headers: Vec<(String, String)>,
+}
+
+impl<'a> MessageBuilder<'a> {
+    pub fn new(email: &'a OutgoingEmail) -> Self {
+        Self {
+            email,
+            date: Local::now().fixed_offset(),
+            message_id: None,
+            include_bcc: false,
+            extra_headers: Vec::new(),
+        }
+    }
+
+    #[allow(dead_code)] // Fixed dates make built messages reproducible
+    pub fn date(mut self, date: DateTime<FixedOffset>) -> Self {
+        self.date = date;
+        self
+    }
+
+    /// Uses a given Message-ID (without angle brackets) instead of a new one.
+    pub fn message_id(mut self, message_id: &str) -> Self {
+        self.message_id = Some(message_id.to_string());
+        self
+    }
+
+    /// Writes the Bcc header, which is left out of anything sent.
+    pub fn include_bcc(mut self, include: bool) -> Self {
+        self.include_bcc = include;
+        self
+    }
+
+    /// Adds a header with an ASCII value, written after the standard ones.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.extra_headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn build(&self) -> BuiltMessage {
+        let email = self.email;
+        let message_id = self
+            .message_id
+            .clone()
+            .unwrap_or_else(|| generate_message_id(email.from.domain(), self.date));
+
+        let mut message = String::new();
+        message.push_str(&fold_header("Date", &self.date.to_rfc2822()));
+        message.push_str(&fold_header("From", &address_list(std::slice::from_ref(&email.from))));
+        if !email.to.is_empty() {
+            message.push_str(&fold_header("To", &address_list(&email.to)));
+        }
+        if !email.cc.is_empty() {
+            message.push_str(&fold_header("Cc", &address_list(&email.cc)));
+        }
+        if self.include_bcc && !email.bcc.is_empty() {
+            message.push_str(&fold_header("Bcc", &address_list(&email.bcc)));
+        }
+        message.push_str(&fold_header("Subject", &encode_text(&email.subject)));
+        message.push_str(&fold_header("Message-ID", &format!("<{}>", message_id)));
+        if let Some(parent) = &email.in_reply_to {
+            message.push_str(&fold_header("In-Reply-To", &format!("<{}>", parent)));
+        }
+        if !email.references.is_empty() {
+            let references: Vec<String> = email.references.iter().map(|id| format!("<{}>", id)).collect();
+            message.push_str(&fold_header("References", &references.join(" ")));
+        }
+        for (name, value) in &self.extra_headers {
+            message.push_str(&fold_header(name, value));
+        }
+        message.push_str("MIME-Version: 1.0\r\n");
+
+        if email.attachments.is_empty() {
+            message.push_str(&text_part(&email.body));
+        } else {
+            // "=_" cannot occur in base64 or quoted-printable content
+            let boundary = format!("=_mailcross_{}", message_id.split('@').next().unwrap_or_default());
+            message.push_str(&format!("Content-Type: multipart/mixed;\r\n boundary=\"{}\"\r\n\r\n", boundary));
+            message.push_str("This is a multi-part message in MIME format.\r\n");
+            message.push_str(&format!("--{}\r\n", boundary));
+            message.push_str(&text_part(&email.body));
+            for attachment in &email.attachments {
+                message.push_str(&format!("\r\n--{}\r\n", boundary));
+                message.push_str(&attachment_part(attachment));
+            }
+            message.push_str(&format!("\r\n--{}--\r\n", boundary));
+        }
+
+        BuiltMessage {
+            message_id,
+            bytes: message.into_bytes(),
+        }
+    }
+}
+
+/// A new Message-ID in the sender's domain, without angle brackets.
+pub fn new_message_id(domain: &str) -> String {
+    generate_message_id(domain, Local::now().fixed_offset())
+}
+
+/// The value of `REPLYING_TO_HEADER` for a message on the server.
+pub fn encode_message_ref(message: &MessageRef) -> String {
+    format!(
+        "{}; {}; uid-validity={}; uid={}",
+        mime_parameter("account", &message.account_email),
+        mime_parameter("folder", &message.folder),
+        message.uid_validity,
+        message.uid
+    )
+}
+
+/// Reads back a value written by `encode_message_ref`.
+pub fn decode_message_ref(value: &str) -> Option<MessageRef> {
+    let params = parse_parameters(&format!("; {}", value));
+    let param = |name: &str| params.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+    Some(MessageRef {
+        account_email: param("account")?,
+        folder: param("folder")?,
+        uid_validity: param("uid-validity")?.parse().ok()?,
+        uid: param("uid")?.parse().ok()?,
+    })
+}
+
+/// Reads back a message built here with its Bcc header, as kept in the
+/// outbox.
+pub fn parse_outgoing(message: &ParsedMessage) -> Result<OutgoingEmail, AddressError> {
+    let addresses = |name| Mailbox::parse_list(&message.headers().get_decoded(name).unwrap_or_default());
+    let from = addresses("From")?
+        .into_iter()
+        .next()
+        .ok_or_else(|| AddressError { input: String::new(), reason: "no sender" })?;
+    Ok(OutgoingEmail {
+        from,
+        to: addresses("To")?,
+        cc: addresses("Cc")?,
+        bcc: addresses("Bcc")?,
+        subject: message.subject().unwrap_or_default(),
+        body: message.text_body().unwrap_or_default().to_string(),
+        attachments: outgoing_attachments(message),
+        in_reply_to: message.in_reply_to().and_then(|value| message_ids(&value).into_iter().next()),
+        references: message_ids(&message.references().join(" ")),
+    })
+}
+
+/// The attachments of a message, to be sent again.
+pub fn outgoing_attachments(message: &ParsedMessage) -> Vec<OutgoingAttachment> {
+    message
+        .attachments()
+        .into_iter()
+        .map(|part| {
+            let content = match &part.body {
+                PartBody::Text(text) => text.clone().into_bytes(),
+                PartBody::Binary(content) => content.clone(),
+                _ => Vec::new(),
+            };
+            OutgoingAttachment {
+                filename: part.filename().unwrap_or_else(|| "attachment".to_string()),
+                mime_type: part.content_type.mime_type.clone(),
+                content,
+            }
+        })
+        .collect()
+}
+
+/// A new globally unique id in the sender's domain.
+fn generate_message_id(domain: &str, date: DateTime<FixedOffset>) -> String {
+    let counter = MESSAGE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let domain = if domain.is_empty() { "localhost" } else { domain };
+    format!(
+        "{:x}.{:x}.{:x}@{}",
+        date.timestamp_nanos_opt().unwrap_or_default(),
+        std::process::id(),
+        counter,
+        domain
+    )
+}
+
+/// The body text as a MIME entity: 7bit if it is short-lined ASCII,
+/// quoted-printable otherwise.
+fn text_part(body: &str) -> String {
+    let body = body.replace("\r\n", "\n");
+    let plain = body.is_ascii() && body.lines().all(|line| line.len() <= MAX_LINE && !line.contains('\r'));
+    let mut part = String::from("Content-Type: text/plain; charset=utf-8\r\n");
+    if plain {
+        part.push_str("Content-Transfer-Encoding: 7bit\r\n\r\n");
+        part.push_str(&body.replace('\n', "\r\n"));
+    } else {
+        part.push_str("Content-Transfer-Encoding: quoted-printable\r\n\r\n");
+        part.push_str(&encode_quoted_printable(&body));
+    }
+    if !part.ends_with("\r\n") {
+        part.push_str("\r\n");
+    }
+    part
+}
+
+/// The MIME entity of an attachment, base64 encoded.
+fn attachment_part(attachment: &OutgoingAttachment) -> String {
+    let mut part = String::new();
+    part.push_str(&fold_header(
+        "Content-Type",
+        &format!("{}; {}", attachment.mime_type, mime_parameter("name", &attachment.filename)),
+    ));
+    part.push_str(&fold_header(
+        "Content-Disposition",
+        &format!("attachment; {}", mime_parameter("filename", &attachment.filename)),
+    ));
+    part.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
+    let encoded = BASE64.encode(&attachment.content);
+    for line in encoded.as_bytes().chunks(MAX_ENCODED_LINE) {
+        part.push_str(std::str::from_utf8(line).unwrap_or_default());
+        part.push_str("\r\n");
+    }
+    part
+}
+
+/// A `name="value"` MIME parameter, using RFC 2231 encoding for values
+/// that cannot be sent as a quoted string.
+fn mime_parameter(name: &str, value: &str) -> String {
+    if value.chars().all(|c| (' '..='~').contains(&c) && c != '"' && c != '\\') {
+        return format!("{}=\"{}\"", name, value);
+    }
+    let encoded: String = value
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    format!("{}*=utf-8''{}", name, encoded)
+}
+
+/// Mailboxes for an address header, names encoded where needed.
+fn address_list(mailboxes: &[Mailbox]) -> String {
+    mailboxes
+        .iter()
+        .map(|mailbox| match &mailbox.name {
+            Some(name) if !name.chars().all(|c| (' '..='~').contains(&c)) => {
+                format!("{} <{}>", encode_words(name), mailbox.address)
+            }
+            _ => mailbox.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// An unstructured header value: as is if it is plain ASCII, otherwise as
+/// encoded-words.
+pub fn encode_text(text: &str) -> String {
+    let plain = text.chars().all(|c| (' '..='~').contains(&c) || c == '\t');
+    // Text that merely looks like an encoded-word must be encoded too
+    if plain && !text.contains("=?") {
+        text.to_string()
+    } else {
+        encode_words(text)
+    }
+}
+
+/// RFC 2047 B-encoded words, split on character boundaries and separated by
+/// spaces, which decoders drop between adjacent encoded-words.
+pub fn encode_words(text: &str) -> String {
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in text.chars() {
+        if chunk.len() + c.len_utf8() > ENCODED_WORD_BYTES {
+            words.push(format!("=?utf-8?B?{}?=", BASE64.encode(&chunk)));
+            chunk.clear();
+        }
+        chunk.push(c);
+    }
+    if !chunk.is_empty() || words.is_empty() {
+        words.push(format!("=?utf-8?B?{}?=", BASE64.encode(&chunk)));
+    }
+    words.join(" ")
+}
+
+/// `Name: value` followed by CRLF, folded at spaces so lines stay within 78
+/// characters where possible.
+pub fn fold_header(name: &str, value: &str) -> String {
+    let mut header = format!("{}:", name);
+    let mut line_length = header.len();
+    for word in value.split(' ') {
+        if !word.is_empty() && line_length + 1 + word.len() > MAX_LINE && line_length > name.len() + 1 {
+            header.push_str("\r\n");
+            line_length = 0;
+        }
+        header.push(' ');
+        header.push_str(word);
+        line_length += 1 + word.len();
+    }
+    header.push_str("\r\n");
+    header
+}
+
+/// Quoted-printable with CRLF line breaks and soft breaks that keep encoded
+/// lines within 76 characters.
+pub fn encode_quoted_printable(text: &str) -> String {
+    let mut encoded = String::new();
+    let lines: Vec<&str> = text.split('\n').collect();
+    for (index, line) in lines.iter().enumerate() {
+        let bytes = line.as_bytes();
+        let mut current = String::new();
+        for (i, &byte) in bytes.iter().enumerate() {
+            let last = i + 1 == bytes.len();
+            let literal = match byte {
+                b'=' => false,
+                // Whitespace at the end of a line would be stripped in transit
+                b' ' | b'\t' => !last,
+                // "From " at the start of a line is mangled by mbox tools
+                b'F' if i == 0 && line.starts_with("From ") => false,
+                b'!'..=b'~' => true,
+                _ => false,
+            };
+            let token = if literal { (byte as char).to_string() } else { format!("={:02X}", byte) };
+            // Room for the soft break "=" unless this ends the line
+            let limit = if last { MAX_ENCODED_LINE } else { MAX_ENCODED_LINE - 1 };
+            if current.len() + token.len() > limit {
+                encoded.push_str(&current);
+                encoded.push_str("=\r\n");
+                current.clear();
+            }
+            current.push_str(&token);
+        }
+        encoded.push_str(&current);
+        if index + 1 < lines.len() {
+            encoded.push_str("\r\n");
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn message() -> OutgoingEmail {
+        OutgoingEmail {
+            from: Mailbox::parse("me@example.com").unwrap(),
+            to: Mailbox::parse_list("Jürgen Müller <juergen@example.de>, bob@example.org").unwrap(),
+            cc: Vec::new(),
+            bcc: Mailbox::parse_list("hidden@example.com").unwrap(),
+            subject: "Grüße aus München – ein sehr langer Betreff, der gefaltet werden muss".to_string(),
+            body: "Hallo!\n".to_string(),
+            attachments: Vec::new(),
+            in_reply_to: None,
+            references: Vec::new(),
+        }
+    }
+
+    fn build(email: &OutgoingEmail) -> (BuiltMessage, String) {
+        let date = FixedOffset::east_opt(3600).unwrap().with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+        let built = MessageBuilder::new(email).date(date).build();
+        let text = String::from_utf8(built.bytes.clone()).unwrap();
+        (built, text)
+    }
+
+    fn assert_line_lengths(text: &str) {
+        for line in text.split("\r\n") {
+            assert!(line.len() <= MAX_LINE, "line too long: {:?}", line);
+            assert!(!line.contains('\n') && !line.contains('\r'), "bare line break in {:?}", line);
+        }
+    }
+
+    #[test]
+    fn encodes_and_folds_headers() {
+        let email = message();
+        let (built, text) = build(&email);
+        assert!(text.is_ascii());
+        assert_line_lengths(&text);
+        assert!(text.starts_with("Date: Sat, 1 Mar 2025 12:00:00 +0100\r\n"));
+        assert!(!text.contains("hidden@example.com"));
+        assert!(built.message_id.ends_with("@example.com"));
+
+        let parsed = ParsedMessage::parse(&built.bytes);
+        assert_eq!(parsed.subject().as_deref(), Some(email.subject.as_str()));
+        assert_eq!(parsed.to().as_deref(), Some("Jürgen Müller <juergen@example.de>, bob@example.org"));
+        assert_eq!(parsed.message_id(), Some(format!("<{}>", built.message_id)));
+        assert_eq!(parsed.text_body(), Some("Hallo!\n"));
+    }
+
+    #[test]
+    fn sets_reply_headers() {
+        let mut email = message();
+        email.in_reply_to = Some("parent@example.org".to_string());
+        email.references = vec!["root@example.org".to_string(), "parent@example.org".to_string()];
+        let (built, _) = build(&email);
+        let parsed = ParsedMessage::parse(&built.bytes);
+        assert_eq!(parsed.in_reply_to().as_deref(), Some("<parent@example.org>"));
+        assert_eq!(parsed.references(), ["<root@example.org>", "<parent@example.org>"]);
+    }
+
+    #[test]
+    fn keeps_draft_headers() {
+        let original = MessageRef {
+            account_email: "me@example.com".to_string(),
+            folder: "INBOX/Ärger \"alt\"".to_string(),
+            uid_validity: 7,
+            uid: 42,
+        };
+        let email = message();
+        let built = MessageBuilder::new(&email)
+            .message_id("draft-1@example.com")
+            .include_bcc(true)
+            .header(REPLYING_TO_HEADER, &encode_message_ref(&original))
+            .build();
+        let parsed = ParsedMessage::parse(&built.bytes);
+        assert_eq!(parsed.message_id().as_deref(), Some("<draft-1@example.com>"));
+        assert_eq!(parsed.headers().get("Bcc"), Some("hidden@example.com"));
+        let value = parsed.headers().get(REPLYING_TO_HEADER).unwrap();
+        assert_eq!(decode_message_ref(value), Some(original));
+    }
+
+    #[test]
+    fn quoted_printable_round_trips_long_and_non_ascii_lines() {
+        let mut email = message();
+        email.body = format!("Ünïcödé = fine \nFrom here on\n{}\ntrailing tab\t\n", "x".repeat(200));
+        let (built, text) = build(&email);
+        assert!(text.contains("Content-Transfer-Encoding: quoted-printable"));
+        assert_line_lengths(&text);
+        let parsed = ParsedMessage::parse(&built.bytes);
+        assert_eq!(parsed.text_body(), Some(email.body.as_str()));
+    }
+
+    #[test]
+    fn builds_multipart_with_attachments() {
+        let mut email = message();
+        email.attachments.push(OutgoingAttachment {
+            filename: "Bericht März.pdf".to_string(),
+            mime_type: "application/pdf".to_string(),
+            content: (0..=255u8).cycle().take(1000).collect(),
+        });
+        let (built, text) = build(&email);
+        assert_line_lengths(&text);
+        let parsed = ParsedMessage::parse(&built.bytes);
+        assert_eq!(parsed.text_body(), Some("Hallo!\n"));
+        let attachments = parsed.attachments();
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].filename().as_deref(), Some("Bericht März.pdf"));
+        assert_eq!(attachments[0].body, PartBody::Binary(email.attachments[0].content.clone()));
+    }
+
+    #[test]
+    fn reads_back_outgoing_messages() {
+        let mut email = message();
+        email.in_reply_to = Some("parent@example.org".to_string());
+        email.references = vec!["parent@example.org".to_string()];
+        email.attachments.push(OutgoingAttachment {
+            filename: "notes.txt".to_string(),
+            mime_type: "text/plain".to_string(),
+            content: b"Notizen\n".to_vec(),
+        });
+        let built = MessageBuilder::new(&email).include_bcc(true).build();
+        assert_eq!(parse_outgoing(&ParsedMessage::parse(&built.bytes)), Ok(email));
+    }
+}
@@ -0,0 +1,1001 @@
+//! MIME message parsing: header unfolding, RFC 2047 encoded-words, RFC 2231
+//! parameters, multipart trees, transfer encodings and charset conversion.
+//!
+//! The parser never fails. Malformed input degrades to the most useful
+//! interpretation (e.g. a multipart without boundaries becomes plain text).
+
+use base64::Engine;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use encoding_rs::Encoding;
+
+// Guards against stack exhaustion on maliciously nested multiparts
+const MAX_DEPTH: usize = 32;
+
+const LENIENT_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+        .with_decode_allow_trailing_bits(true),
+);
+
+/// Unfolded header fields in their original order. Values are raw, i.e.
+/// still containing encoded-words.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Headers(Vec<(String, String)>);
+
+impl Headers {
+    pub fn parse(raw: &[u8]) -> Self {
+        let text = decode_header_bytes(raw);
+        let mut fields: Vec<(String, String)> = Vec::new();
+
+        for line in text.split('\n') {
+            let line = line.trim_end_matches('\r');
+            if line.starts_with([' ', '\t']) {
+                // Continuation of a folded field
+                if let Some((_, value)) = fields.last_mut() {
+                    value.push_str(line);
+                }
+            } else if let Some((name, value)) = line.split_once(':') {
+                let name = name.trim();
+                if !name.is_empty() && !name.contains(' ') {
+                    fields.push((name.to_string(), value.to_string()));
+                }
+            }
+        }
+
+        for (_, value) in fields.iter_mut() {
+            *value = value.trim().to_string();
+        }
+        Self(fields)
+    }
+
+    /// First value of a field, case-insensitively.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// First value of a field with encoded-words decoded.
+    pub fn get_decoded(&self, name: &str) -> Option<String> {
+        self.get(name).map(decode_encoded_words)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentType {
+    /// Lowercased "type/subtype"
+    pub mime_type: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl ContentType {
+    pub fn new(mime_type: &str) -> Self {
+        Self {
+            mime_type: mime_type.to_string(),
+            params: Vec::new(),
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let (mime_type, params) = value.split_once(';').unwrap_or((value, ""));
+        let mime_type = mime_type.trim().to_lowercase();
+        if !mime_type.contains('/') {
+            return None;
+        }
+        Some(Self {
+            mime_type,
+            params: parse_parameters(params),
+        })
+    }
+
+    fn from_headers(headers: &Headers, default: &str) -> Self {
+        headers
+            .get("Content-Type")
+            .and_then(Self::parse)
+            .unwrap_or_else(|| Self::new(default))
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn main_type(&self) -> &str {
+        self.mime_type.split('/').next().unwrap_or("")
+    }
+
+    pub fn subtype(&self) -> &str {
+        self.mime_type.split('/').nth(1).unwrap_or("")
+    }
+
+    pub fn is_multipart(&self) -> bool {
+        self.main_type() == "multipart"
+    }
+
+    pub fn charset(&self) -> &str {
+        self.param("charset").unwrap_or("us-ascii")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartBody {
+    /// Decoded and converted to UTF-8, line endings normalized to `\n`
+    Text(String),
+    Binary(Vec<u8>),
+    Multipart(Vec<MimePart>),
+    /// An attached or forwarded message (message/rfc822)
+    Message(Box<ParsedMessage>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MimePart {
+    pub headers: Headers,
+    pub content_type: ContentType,
+    /// IMAP section specifier of this part ("1", "2.1", ...). Empty for a
+    /// top-level multipart.
+    pub section: String,
+    pub body: PartBody,
+}
+
+impl MimePart {
+    fn build(headers: Headers, content_type: ContentType, raw_body: &[u8], section: String, depth: usize) -> Self {
+        let body = if content_type.is_multipart() {
+            match content_type.param("boundary").and_then(|b| split_multipart(raw_body, b)) {
+                Some(chunks) if depth < MAX_DEPTH => {
+                    let child_default = if content_type.subtype() == "digest" {
+                        "message/rfc822"
+                    } else {
+                        "text/plain"
+                    };
+                    let parts = chunks
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, chunk)| {
+                            let (child_headers, child_body) = split_entity(chunk);
+                            let child_type = ContentType::from_headers(&child_headers, child_default);
+                            let child_section = if section.is_empty() {
+                                (i + 1).to_string()
+                            } else {
+                                format!("{}.{}", section, i + 1)
+                            };
+                            MimePart::build(child_headers, child_type, child_body, child_section, depth + 1)
+                        })
+                        .collect();
+                    PartBody::Multipart(parts)
+                }
+                // No usable boundary: show whatever is there as text
+                _ => PartBody::Text(normalize_newlines(&String::from_utf8_lossy(raw_body))),
+            }
+        } else {
+            let decoded = decode_transfer_encoding(
+                raw_body,
+                headers.get("Content-Transfer-Encoding").unwrap_or("7bit"),
+            );
+            match content_type.main_type() {
+                "text" => PartBody::Text(normalize_newlines(&decode_charset(&decoded, content_type.charset()))),
+                "message" if matches!(content_type.subtype(), "rfc822" | "global") && depth < MAX_DEPTH => {
+                    PartBody::Message(Box::new(ParsedMessage::parse_nested(&decoded, &section, depth + 1)))
+                }
+                _ => PartBody::Binary(decoded),
+            }
+        };
+
+        Self {
+            headers,
+            content_type,
+            section,
+            body,
+        }
+    }
+
+    /// Lowercased disposition type ("inline", "attachment"), if given.
+    pub fn disposition(&self) -> Option<String> {
+        self.headers
+            .get("Content-Disposition")
+            .map(|value| value.split(';').next().unwrap_or("").trim().to_lowercase())
+    }
+
+    /// File name from Content-Disposition, falling back to the Content-Type
+    /// `name` parameter.
+    pub fn filename(&self) -> Option<String> {
+        self.headers
+            .get("Content-Disposition")
+            .and_then(|value| value.split_once(';'))
+            .map(|(_, params)| parse_parameters(params))
+            .and_then(|params| {
+                params
+                    .into_iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case("filename"))
+                    .map(|(_, value)| value)
+            })
+            .or_else(|| self.content_type.param("name").map(str::to_string))
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Content-ID without the angle brackets.
+    pub fn content_id(&self) -> Option<String> {
+        self.headers
+            .get("Content-ID")
+            .map(|id| id.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+            .filter(|id| !id.is_empty())
+    }
+
+    pub fn is_attachment(&self) -> bool {
+        match self.disposition().as_deref() {
+            Some("attachment") => true,
+            Some("inline") => false,
+            _ => self.filename().is_some() || matches!(self.body, PartBody::Binary(_) | PartBody::Message(_)),
+        }
+    }
+
+    /// Size of the decoded content in bytes.
+    pub fn size(&self) -> usize {
+        match &self.body {
+            PartBody::Text(text) => text.len(),
+            PartBody::Binary(bytes) => bytes.len(),
+            PartBody::Multipart(parts) => parts.iter().map(MimePart::size).sum(),
+            PartBody::Message(message) => message.root.size(),
+        }
+    }
+
+    fn find_text(&self, subtype: &str) -> Option<&str> {
+        match &self.body {
+            PartBody::Text(text)
+                if self.content_type.subtype() == subtype && !self.is_attachment() => Some(text),
+            PartBody::Multipart(parts) => parts.iter().find_map(|part| part.find_text(subtype)),
+            _ => None,
+        }
+    }
+
+    fn collect_inline_images<'a>(&'a self, out: &mut Vec<&'a MimePart>) {
+        match &self.body {
+            PartBody::Multipart(parts) => {
+                for part in parts {
+                    part.collect_inline_images(out);
+                }
+            }
+            PartBody::Binary(_) if self.content_type.main_type() == "image" && self.content_id().is_some() => out.push(self),
+            _ => {}
+        }
+    }
+
+    fn collect_attachments<'a>(&'a self, out: &mut Vec<&'a MimePart>) {
+        match &self.body {
+            PartBody::Multipart(parts) => {
+                for part in parts {
+                    part.collect_attachments(out);
+                }
+            }
+            _ if self.is_attachment() => out.push(self),
+            _ => {}
+        }
+    }
+}
+
+/// A fully parsed message. The root part carries the message headers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedMessage {
+    pub root: MimePart,
+}
+
+impl ParsedMessage {
+    /// Parses a complete message or just its header block.
+    pub fn parse(raw: &[u8]) -> Self {
+        Self::parse_nested(raw, "", 0)
+    }
+
+    fn parse_nested(raw: &[u8], base_section: &str, depth: usize) -> Self {
+        let (headers, body) = split_entity(raw);
+        let content_type = ContentType::from_headers(&headers, "text/plain");
+        let section = if content_type.is_multipart() {
+            base_section.to_string()
+        } else if base_section.is_empty() {
+            "1".to_string()
+        } else {
+            format!("{}.1", base_section)
+        };
+        Self {
+            root: MimePart::build(headers, content_type, body, section, depth),
+        }
+    }
+
+    #[allow(dead_code)] // Will be used by the message composer
+    pub fn headers(&self) -> &Headers {
+        &self.root.headers
+    }
+
+    pub fn subject(&self) -> Option<String> {
+        self.root.headers.get_decoded("Subject")
+    }
+
+    pub fn from(&self) -> Option<String> {
+        self.root.headers.get_decoded("From")
+    }
+
+    pub fn to(&self) -> Option<String> {
+        self.root.headers.get_decoded("To")
+    }
+
+    #[allow(dead_code)] // Will be used when replying to all
+    pub fn cc(&self) -> Option<String> {
+        self.root.headers.get_decoded("Cc")
+    }
+
+    pub fn date(&self) -> Option<String> {
+        self.root.headers.get("Date").map(str::to_string)
+    }
+
+    pub fn message_id(&self) -> Option<String> {
+        self.root.headers.get("Message-ID").map(str::to_string)
+    }
+
+    pub fn in_reply_to(&self) -> Option<String> {
+        self.root.headers.get("In-Reply-To").map(str::to_string)
+    }
+
+    pub fn references(&self) -> Vec<String> {
+        self.root
+            .headers
+            .get("References")
+            .map(|value| value.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    /// The first text/plain part that is not an attachment.
+    pub fn text_body(&self) -> Option<&str> {
+        self.root.find_text("plain")
+    }
+
+    /// The first text/html part that is not an attachment.
+    pub fn html_body(&self) -> Option<&str> {
+        self.root.find_text("html")
+    }
+
+    /// Text to show in the preview: the plain-text body, or the HTML body
+    /// with its markup stripped when there is no plain-text alternative.
+    pub fn display_text(&self) -> String {
+        if let Some(text) = self.text_body() {
+            return text.to_string();
+        }
+        self.html_body().map(strip_html).unwrap_or_default()
+    }
+
+    /// Images that the HTML body can refer to with a `cid:` URL.
+    pub fn inline_images(&self) -> Vec<&MimePart> {
+        let mut out = Vec::new();
+        self.root.collect_inline_images(&mut out);
+        out
+    }
+
+    /// Leaf parts meant to be saved rather than displayed.
+    pub fn attachments(&self) -> Vec<&MimePart> {
+        let mut out = Vec::new();
+        self.root.collect_attachments(&mut out);
+        out
+    }
+}
+
+/// Splits an entity at the first empty line into parsed headers and raw body.
+fn split_entity(raw: &[u8]) -> (Headers, &[u8]) {
+    // An entity starting with an empty line has no headers at all
+    if raw.starts_with(b"\r\n") {
+        return (Headers::default(), &raw[2..]);
+    }
+    if raw.starts_with(b"\n") {
+        return (Headers::default(), &raw[1..]);
+    }
+
+    let mut line_start = 0;
+    while line_start < raw.len() {
+        let line_end = raw[line_start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|pos| line_start + pos + 1)
+            .unwrap_or(raw.len());
+        let line = &raw[line_start..line_end];
+        if line == b"\r\n" || line == b"\n" {
+            return (Headers::parse(&raw[..line_start]), &raw[line_end..]);
+        }
+        line_start = line_end;
+    }
+
+    // Header-only input (e.g. an RFC822.HEADER fetch without the blank line)
+    (Headers::parse(raw), &[])
+}
+
+/// Returns the raw body parts between boundary delimiter lines, or None if
+/// the boundary never occurs. A missing closing delimiter is tolerated.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Option<Vec<&'a [u8]>> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+    let mut parts = Vec::new();
+    let mut current_start: Option<usize> = None;
+    let mut line_start = 0;
+    let mut found = false;
+
+    while line_start < body.len() {
+        let line_end = body[line_start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|pos| line_start + pos + 1)
+            .unwrap_or(body.len());
+        let line = trim_line_end(&body[line_start..line_end]);
+
+        if line.starts_with(delimiter) {
+            let rest = &line[delimiter.len()..];
+            let closing = rest.starts_with(b"--");
+            if closing || rest.iter().all(|b| b.is_ascii_whitespace()) {
+                found = true;
+                if let Some(start) = current_start.take() {
+                    // The line break before a delimiter belongs to the delimiter
+                    parts.push(strip_trailing_newline(&body[start..line_start]));
+                }
+                if closing {
+                    return Some(parts);
+                }
+                current_start = Some(line_end);
+            }
+        }
+        line_start = line_end;
+    }
+
+    if let Some(start) = current_start {
+        parts.push(&body[start.min(body.len())..]);
+    }
+    found.then_some(parts)
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let end = line
+        .iter()
+        .rposition(|b| !matches!(b, b'\r' | b'\n' | b' ' | b'\t'))
+        .map(|pos| pos + 1)
+        .unwrap_or(0);
+    &line[..end]
+}
+
+fn strip_trailing_newline(part: &[u8]) -> &[u8] {
+    let part = part.strip_suffix(b"\n").unwrap_or(part);
+    part.strip_suffix(b"\r").unwrap_or(part)
+}
+
+/// The `<...>` message ids in a header value, without the brackets.
+/// Comments and phrases some mailers put between them are skipped.
+pub fn message_ids(value: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let id: String = rest[start + 1..start + end].chars().filter(|c| !c.is_whitespace()).collect();
+        if !id.is_empty() {
+            ids.push(id);
+        }
+        rest = &rest[start + end + 1..];
+    }
+    ids
+}
+
+/// Crude HTML to text conversion: drops tags along with script and style
+/// contents and decodes the most common entities.
+pub fn strip_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let tag = &rest[start..];
+        let Some(end) = tag.find('>') else {
+            rest = "";
+            break;
+        };
+        let name = tag[1..end]
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        rest = &tag[end + 1..];
+
+        if matches!(name.as_str(), "script" | "style") && !tag.starts_with("</") {
+            let closing = format!("</{}", name);
+            rest = match rest.to_lowercase().find(&closing) {
+                Some(pos) => &rest[pos..],
+                None => "",
+            };
+        } else if matches!(name.as_str(), "br" | "p" | "div" | "tr" | "li" | "h1" | "h2" | "h3") {
+            out.push('\n');
+        }
+    }
+    out.push_str(rest);
+
+    let text = out
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    lines.join("\n").trim().to_string()
+}
+
+fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+/// Header bytes should be ASCII but are often raw UTF-8 or Latin-1.
+fn decode_header_bytes(raw: &[u8]) -> String {
+    match std::str::from_utf8(raw) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding_rs::WINDOWS_1252.decode_without_bom_handling(raw).0.into_owned(),
+    }
+}
+
+pub fn decode_transfer_encoding(raw: &[u8], encoding: &str) -> Vec<u8> {
+    match encoding.trim().to_lowercase().as_str() {
+        "base64" => decode_base64(raw),
+        "quoted-printable" => decode_quoted_printable(raw),
+        _ => raw.to_vec(),
+    }
+}
+
+/// Decodes base64, skipping line breaks and any other stray characters.
+pub fn decode_base64(raw: &[u8]) -> Vec<u8> {
+    let mut clean: Vec<u8> = raw
+        .iter()
+        .copied()
+        .take_while(|&b| b != b'=')
+        .filter(|b| b.is_ascii_alphanumeric() || *b == b'+' || *b == b'/')
+        .collect();
+    // A single dangling character cannot encode anything
+    if clean.len() % 4 == 1 {
+        clean.pop();
+    }
+    LENIENT_BASE64.decode(&clean).unwrap_or_default()
+}
+
+pub fn decode_quoted_printable(raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        match raw[i] {
+            b'=' => {
+                let rest = &raw[i + 1..];
+                // Soft line break, possibly with trailing whitespace
+                let ws = rest.iter().take_while(|b| matches!(b, b' ' | b'\t')).count();
+                if rest[ws..].starts_with(b"\r\n") {
+                    i += 1 + ws + 2;
+                } else if rest[ws..].starts_with(b"\n") {
+                    i += 1 + ws + 1;
+                } else if let (Some(hi), Some(lo)) = (
+                    rest.first().and_then(|b| (*b as char).to_digit(16)),
+                    rest.get(1).and_then(|b| (*b as char).to_digit(16)),
+                ) {
+                    out.push((hi * 16 + lo) as u8);
+                    i += 3;
+                } else {
+                    // Malformed escape: keep it literally
+                    out.push(b'=');
+                    i += 1;
+                }
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Converts bytes in a declared charset to UTF-8. Unknown charsets are
+/// treated as UTF-8 with replacement characters.
+pub fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    let label = charset.trim().trim_matches('"');
+    match Encoding::for_label(label.as_bytes()) {
+        Some(encoding) => encoding.decode_without_bom_handling(bytes).0.into_owned(),
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Decodes RFC 2047 encoded-words. Whitespace between adjacent encoded-words
+/// is dropped, and adjacent words in the same charset are joined before
+/// conversion so multi-byte characters split across words survive.
+pub fn decode_encoded_words(input: &str) -> String {
+    let mut out = String::new();
+    let mut rest = input;
+    // Raw bytes of the current run of encoded-words sharing a charset
+    let mut pending: Option<(String, Vec<u8>)> = None;
+
+    let flush = |out: &mut String, pending: &mut Option<(String, Vec<u8>)>| {
+        if let Some((charset, bytes)) = pending.take() {
+            out.push_str(&decode_charset(&bytes, &charset));
+        }
+    };
+
+    loop {
+        let Some(start) = rest.find("=?") else {
+            flush(&mut out, &mut pending);
+            out.push_str(rest);
+            break;
+        };
+
+        let (before, candidate) = rest.split_at(start);
+        match parse_encoded_word(candidate) {
+            Some((charset, bytes, consumed)) => {
+                let adjacent = pending.is_some() && before.chars().all(char::is_whitespace);
+                match &mut pending {
+                    Some((pending_charset, pending_bytes))
+                        if adjacent && pending_charset.eq_ignore_ascii_case(&charset) =>
+                    {
+                        pending_bytes.extend_from_slice(&bytes);
+                    }
+                    _ => {
+                        flush(&mut out, &mut pending);
+                        if !adjacent {
+                            out.push_str(before);
+                        }
+                        pending = Some((charset, bytes));
+                    }
+                }
+                rest = &candidate[consumed..];
+            }
+            None => {
+                flush(&mut out, &mut pending);
+                out.push_str(before);
+                out.push_str("=?");
+                rest = &candidate[2..];
+            }
+        }
+    }
+    out
+}
+
+/// Parses one `=?charset?enc?text?=` word at the start of `input`, returning
+/// the charset, the decoded bytes and the number of bytes consumed.
+fn parse_encoded_word(input: &str) -> Option<(String, Vec<u8>, usize)> {
+    let inner = input.strip_prefix("=?")?;
+    let charset_end = inner.find('?')?;
+    // RFC 2231 allows a language suffix: charset*lang
+    let charset = inner[..charset_end].split('*').next()?;
+    if charset.is_empty() || charset.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let after_charset = &inner[charset_end + 1..];
+    let encoding = *after_charset.as_bytes().first()?;
+    if after_charset.as_bytes().get(1) != Some(&b'?') {
+        return None;
+    }
+    let text = &after_charset[2..];
+    let text_end = text.find("?=")?;
+    let payload = &text[..text_end];
+    if payload.contains(['\r', '\n']) {
+        return None;
+    }
+
+    let bytes = match encoding.to_ascii_uppercase() {
+        b'B' => decode_base64(payload.as_bytes()),
+        b'Q' => decode_q(payload),
+        _ => return None,
+    };
+    let consumed = 2 + charset_end + 1 + 2 + text_end + 2;
+    Some((charset.to_string(), bytes, consumed))
+}
+
+fn decode_q(payload: &str) -> Vec<u8> {
+    let underscores_as_spaces: Vec<u8> = payload
+        .bytes()
+        .map(|b| if b == b'_' { b' ' } else { b })
+        .collect();
+    decode_quoted_printable(&underscores_as_spaces)
+}
+
+/// Parses `; key=value; key="quoted"` parameter lists, including RFC 2231
+/// continuations (`key*0=`, `key*1=`) and charset-tagged values (`key*=`).
+pub fn parse_parameters(input: &str) -> Vec<(String, String)> {
+    // (name, section index, percent-encoded, value)
+    let mut raw: Vec<(String, usize, bool, String)> = Vec::new();
+
+    for (key, value) in split_parameters(input) {
+        let key = key.to_lowercase();
+        let (name, extended) = match key.strip_suffix('*') {
+            Some(name) => (name.to_string(), true),
+            None => (key, false),
+        };
+        let (name, index) = match name.rsplit_once('*') {
+            Some((base, index)) if index.chars().all(|c| c.is_ascii_digit()) && !index.is_empty() => {
+                (base.to_string(), index.parse().unwrap_or(0))
+            }
+            _ => (name, 0),
+        };
+        raw.push((name, index, extended, value));
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    for (name, ..) in &raw {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+
+    names
+        .into_iter()
+        .map(|name| {
+            let mut sections: Vec<&(String, usize, bool, String)> =
+                raw.iter().filter(|(n, ..)| *n == name).collect();
+            sections.sort_by_key(|(_, index, ..)| *index);
+
+            let any_extended = sections.iter().any(|(_, _, extended, _)| *extended);
+            if !any_extended {
+                let joined: String = sections.iter().map(|(.., value)| value.as_str()).collect();
+                // Not standard, but many mailers put encoded-words in file names
+                return (name, decode_encoded_words(&joined));
+            }
+
+            let mut charset = String::from("utf-8");
+            let mut bytes = Vec::new();
+            for (i, (_, _, extended, value)) in sections.iter().enumerate() {
+                if *extended {
+                    let mut value = value.as_str();
+                    if i == 0 {
+                        // charset'language'value
+                        let mut pieces = value.splitn(3, '\'');
+                        if let (Some(cs), Some(_lang), Some(rest)) = (pieces.next(), pieces.next(), pieces.next()) {
+                            if !cs.is_empty() {
+                                charset = cs.to_string();
+                            }
+                            value = rest;
+                        }
+                    }
+                    bytes.extend(percent_decode(value));
+                } else {
+                    bytes.extend_from_slice(value.as_bytes());
+                }
+            }
+            (name, decode_charset(&bytes, &charset))
+        })
+        .collect()
+}
+
+/// Tokenizes `key=value` pairs separated by semicolons, honoring quotes.
+fn split_parameters(input: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    loop {
+        // Skip separators
+        while chars.peek().is_some_and(|c| *c == ';' || c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c == ';' {
+                break;
+            }
+            key.push(c);
+            chars.next();
+        }
+        if chars.next() != Some('=') {
+            continue;
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some(escaped) = chars.next() {
+                            value.push(escaped);
+                        }
+                    }
+                    '"' => break,
+                    c => value.push(c),
+                }
+            }
+            // Ignore anything between the closing quote and the next ';'
+            while chars.peek().is_some_and(|c| *c != ';') {
+                chars.next();
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == ';' {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+            value = value.trim().to_string();
+        }
+
+        let key = key.trim();
+        if !key.is_empty() {
+            params.push((key.to_string(), value));
+        }
+    }
+    params
+}
+
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let (Some(hi), Some(lo)) = (
+                bytes.get(i + 1).and_then(|b| (*b as char).to_digit(16)),
+                bytes.get(i + 2).and_then(|b| (*b as char).to_digit(16)),
+            )
+        {
+            out.push((hi * 16 + lo) as u8);
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use proptest::prelude::*;
+
+    const FIXTURES: &[(&str, &[u8])] = &[
+        ("folded_encoded_headers", include_bytes!("../../tests/fixtures/mime/folded_encoded_headers.eml")),
+        ("alternative_qp", include_bytes!("../../tests/fixtures/mime/alternative_qp.eml")),
+        ("nested_attachment", include_bytes!("../../tests/fixtures/mime/nested_attachment.eml")),
+        ("latin1_8bit", include_bytes!("../../tests/fixtures/mime/latin1_8bit.eml")),
+        ("forwarded_message", include_bytes!("../../tests/fixtures/mime/forwarded_message.eml")),
+        ("truncated_multipart", include_bytes!("../../tests/fixtures/mime/truncated_multipart.eml")),
+        ("cyrillic_subject", include_bytes!("../../tests/fixtures/mime/cyrillic_subject.eml")),
+    ];
+
+    fn fixture(name: &str) -> ParsedMessage {
+        let (_, raw) = FIXTURES.iter().find(|(n, _)| *n == name).unwrap();
+        ParsedMessage::parse(raw)
+    }
+
+    #[test]
+    fn unfolds_and_decodes_headers() {
+        let message = fixture("folded_encoded_headers");
+        assert_eq!(message.subject().as_deref(), Some("Grüße aus München – ein sehr langer Betreff"));
+        assert_eq!(message.from().as_deref(), Some("Jürgen Müller <juergen@example.de>"));
+        assert_eq!(message.references(), vec!["<a@example.de>", "<b@example.de>"]);
+        assert_eq!(message.text_body(), Some("Hallo!\n"));
+    }
+
+    #[test]
+    fn prefers_parts_by_type_in_alternative() {
+        let message = fixture("alternative_qp");
+        assert_eq!(
+            message.text_body(),
+            Some("Café au lait with a very long line that was soft-wrapped by the encoder.\n")
+        );
+        assert_eq!(message.html_body(), Some("<p>Caf\u{e9} <b>au lait</b></p>\n"));
+        assert!(message.attachments().is_empty());
+    }
+
+    #[test]
+    fn finds_nested_attachment_with_rfc2231_name() {
+        let message = fixture("nested_attachment");
+        assert_eq!(message.text_body(), Some("See attached."));
+        let attachments = message.attachments();
+        assert_eq!(attachments.len(), 1);
+        let attachment = attachments[0];
+        assert_eq!(attachment.filename().as_deref(), Some("naïve résumé.pdf"));
+        assert_eq!(attachment.content_type.mime_type, "application/pdf");
+        assert_eq!(attachment.section, "2");
+        assert_eq!(attachment.body, PartBody::Binary(b"%PDF-1.4 fake".to_vec()));
+    }
+
+    #[test]
+    fn converts_legacy_charsets() {
+        let message = fixture("latin1_8bit");
+        assert_eq!(message.subject().as_deref(), Some("Café"));
+        assert_eq!(message.text_body(), Some("Voilà, déjà vu.\n"));
+        assert_eq!(message.root.section, "1");
+    }
+
+    #[test]
+    fn parses_forwarded_messages() {
+        let message = fixture("forwarded_message");
+        let attachments = message.attachments();
+        assert_eq!(attachments.len(), 1);
+        let PartBody::Message(inner) = &attachments[0].body else {
+            panic!("expected an embedded message");
+        };
+        assert_eq!(inner.subject().as_deref(), Some("Original"));
+        assert_eq!(inner.text_body(), Some("Inner body\n"));
+        assert_eq!(inner.root.section, "2.1");
+    }
+
+    #[test]
+    fn tolerates_missing_closing_boundary() {
+        let message = fixture("truncated_multipart");
+        assert_eq!(message.text_body(), Some("first part"));
+        let PartBody::Multipart(parts) = &message.root.body else {
+            panic!("expected multipart");
+        };
+        assert_eq!(parts.len(), 2);
+    }
+
+    #[test]
+    fn joins_split_multibyte_encoded_words() {
+        let message = fixture("cyrillic_subject");
+        assert_eq!(message.subject().as_deref(), Some("Привет, мир"));
+        assert_eq!(message.from().as_deref(), Some("Иван <ivan@example.ru>"));
+        assert_eq!(message.text_body(), Some("Привет"));
+    }
+
+    #[test]
+    fn keeps_malformed_encoded_words_literally() {
+        assert_eq!(decode_encoded_words("=?utf-8?X?abc?= and =?broken"), "=?utf-8?X?abc?= and =?broken");
+        assert_eq!(decode_encoded_words("a =?utf-8?q?b?= c"), "a b c");
+    }
+
+    #[test]
+    fn every_fixture_prefix_parses() {
+        for (_, raw) in FIXTURES {
+            for end in 0..=raw.len() {
+                let _ = ParsedMessage::parse(&raw[..end]);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics_on_arbitrary_input(raw in proptest::collection::vec(any::<u8>(), 0..2048)) {
+            let message = ParsedMessage::parse(&raw);
+            let _ = message.subject();
+            let _ = message.text_body();
+            let _ = message.attachments();
+        }
+
+        #[test]
+        fn never_panics_on_mangled_fixtures(index in 0..FIXTURES.len(), cut in any::<usize>(), byte in any::<u8>()) {
+            let mut raw = FIXTURES[index].1.to_vec();
+            let position = cut % raw.len();
+            raw[position] = byte;
+            let _ = ParsedMessage::parse(&raw).attachments();
+        }
+
+        #[test]
+        fn base64_round_trips_with_line_breaks(data in proptest::collection::vec(any::<u8>(), 0..512)) {
+            let encoded = BASE64.encode(&data);
+            let wrapped: Vec<u8> = encoded
+                .as_bytes()
+                .chunks(76)
+                .flat_map(|line| line.iter().copied().chain(*b"\r\n"))
+                .collect();
+            prop_assert_eq!(decode_base64(&wrapped), data);
+        }
+
+        #[test]
+        fn encoded_words_round_trip(text in "\\PC{0,40}", chunk in 1usize..12) {
+            // Split the UTF-8 bytes at arbitrary points, as some mailers do
+            let words: Vec<String> = text
+                .as_bytes()
+                .chunks(chunk)
+                .map(|bytes| format!("=?UTF-8?B?{}?=", BASE64.encode(bytes)))
+                .collect();
+            prop_assert_eq!(decode_encoded_words(&words.join("\r\n ")), text);
+        }
+    }
+}
@@ -1,9 +1,28 @@
 pub mod imap_client;
+pub mod imap_stream;
+pub mod folder_sync;
+pub mod mailbox_name;
+pub mod mailbox_watcher;
+pub mod smtp_client;
+pub mod mime_parser;
+pub mod message_builder;
+pub mod body_structure;
+pub mod html;
+pub mod remote_content;
+pub mod downloads;
 pub mod credentials;
-pub mod email_cache;
+pub mod error_log;
+pub mod mail_store;
+pub mod outbox;
 pub mod account_manager;
 
 pub use imap_client::*;
+pub use folder_sync::*;
+pub use mailbox_watcher::*;
+pub use smtp_client::*;
+pub use mime_parser::*;
+pub use message_builder::*;
 pub use credentials::*;
-pub use email_cache::*;
+pub use mail_store::*;
+pub use outbox::*;
 pub use account_manager::*;
\ No newline at end of file
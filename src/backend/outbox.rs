@@ -0,0 +1,380 @@
+//! Messages waiting to be sent.
+//!
+//! The composer hands messages to the outbox rather than straight to the
+//! SMTP server, so nothing is lost when the network is down: each message is
+//! written to disk first and sent once it is due, with growing delays
+//! between failed attempts. A message is first due when the undo window
+//! has passed, or at the time it was scheduled for.
+
+use crate::backend::error_log;
+use crate::backend::mail_store::{read_json, remove_temp_files, time_id, write_atomic, write_json_atomic};
+use crate::types::MessageRef;
+use chrono::{DateTime, Duration as TimeDelta, NaiveDateTime, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Failed attempts after which a message is given up on
+pub const MAX_ATTEMPTS: u32 = 8;
+const FIRST_RETRY: Duration = Duration::from_secs(30);
+const MAX_RETRY: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutboxState {
+    Queued,
+    Sending,
+    /// Given up on until edited or deleted
+    Failed,
+}
+
+/// A message in the outbox. The message itself is kept next to it, built
+/// with its Bcc header so it can be edited again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: String,
+    pub account_email: String,
+    pub subject: String,
+    /// The To line, for display
+    pub recipients: String,
+    pub replying_to: Option<MessageRef>,
+    /// Message-ID of the draft it was sent from
+    pub draft_id: Option<String>,
+    pub state: OutboxState,
+    pub attempts: u32,
+    /// Unix time it is held until: the end of the undo window, or the time
+    /// it was scheduled for
+    #[serde(default)]
+    pub send_at: u64,
+    /// Whether `send_at` was chosen by the sender
+    #[serde(default)]
+    pub scheduled: bool,
+    /// Unix time before which it is not sent
+    pub next_attempt: u64,
+    pub last_error: Option<String>,
+}
+
+impl OutboxEntry {
+    pub fn new(account_email: &str, subject: &str, recipients: &str) -> Self {
+        Self {
+            id: time_id(),
+            account_email: account_email.to_string(),
+            subject: subject.to_string(),
+            recipients: recipients.to_string(),
+            replying_to: None,
+            draft_id: None,
+            state: OutboxState::Queued,
+            attempts: 0,
+            send_at: 0,
+            scheduled: false,
+            next_attempt: 0,
+            last_error: None,
+        }
+    }
+
+    /// Holds the message until `send_at`: a time the sender chose if
+    /// `scheduled`, otherwise the end of the undo window.
+    pub fn hold_until(mut self, send_at: u64, scheduled: bool) -> Self {
+        self.send_at = send_at;
+        self.scheduled = scheduled;
+        self.next_attempt = send_at;
+        self
+    }
+
+    /// Seconds left to take the message back before it is sent, while its
+    /// undo window is open.
+    pub fn undo_remaining(&self, now: u64) -> Option<u64> {
+        let waiting = self.state == OutboxState::Queued && self.attempts == 0 && !self.scheduled;
+        (waiting && self.send_at > now).then(|| self.send_at - now)
+    }
+
+    /// Whether the message waits for the time it was scheduled for.
+    pub fn is_scheduled_later(&self, now: u64) -> bool {
+        self.scheduled && self.state == OutboxState::Queued && self.send_at > now
+    }
+}
+
+/// Entries are mirrored in memory and written through; the layout is
+/// `<id>.json` for the entry and `<id>.eml` for the message.
+pub struct Outbox {
+    /// None when running without a usable data directory (memory only)
+    root: Option<PathBuf>,
+    /// Oldest first
+    entries: Vec<OutboxEntry>,
+    /// Messages of a memory-only outbox
+    contents: HashMap<String, Vec<u8>>,
+}
+
+impl Outbox {
+    /// Opens the outbox in `$XDG_DATA_HOME/mailcross/outbox`.
+    pub fn open_default() -> Self {
+        match dirs::data_dir() {
+            Some(dir) => Self::open(&dir.join("mailcross").join("outbox")),
+            None => Self::in_memory(),
+        }
+    }
+
+    pub fn open(root: &Path) -> Self {
+        if let Err(e) = fs::create_dir_all(root) {
+            error_log::report(format!("Outbox at {} unavailable, keeping it in memory: {}", root.display(), e));
+            return Self::in_memory();
+        }
+        remove_temp_files(root);
+        let mut entries: Vec<OutboxEntry> = fs::read_dir(root)
+            .map(|dir| {
+                dir.flatten()
+                    .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+                    .filter_map(|entry| read_json::<OutboxEntry>(&entry.path()))
+                    .filter(|entry| root.join(format!("{}.eml", entry.id)).exists())
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut outbox = Self { root: Some(root.to_path_buf()), entries, contents: HashMap::new() };
+        // Interrupted mid-send: whether the server took it is unknown, and
+        // sending twice beats not at all
+        let interrupted: Vec<String> = outbox.entries.iter()
+            .filter(|entry| entry.state == OutboxState::Sending)
+            .map(|entry| entry.id.clone())
+            .collect();
+        for id in interrupted {
+            outbox.update(&id, |entry| entry.state = OutboxState::Queued);
+        }
+        outbox
+    }
+
+    pub fn in_memory() -> Self {
+        Self { root: None, entries: Vec::new(), contents: HashMap::new() }
+    }
+
+    pub fn entries(&self) -> &[OutboxEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: &str) -> Option<&OutboxEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn content(&self, id: &str) -> Option<Vec<u8>> {
+        match &self.root {
+            Some(root) => fs::read(root.join(format!("{}.eml", id))).ok(),
+            None => self.contents.get(id).cloned(),
+        }
+    }
+
+    /// Adds a message, written to disk before this returns.
+    pub fn push(&mut self, entry: OutboxEntry, content: &[u8]) {
+        match &self.root {
+            Some(root) => {
+                report_write_error(write_atomic(&root.join(format!("{}.eml", entry.id)), content));
+                report_write_error(write_json_atomic(&root.join(format!("{}.json", entry.id)), &entry));
+            }
+            None => {
+                self.contents.insert(entry.id.clone(), content.to_vec());
+            }
+        }
+        self.entries.push(entry);
+    }
+
+    /// Ids of the queued messages whose time has come, oldest first.
+    pub fn due(&self, now: u64) -> Vec<String> {
+        self.entries.iter()
+            .filter(|entry| entry.state == OutboxState::Queued && entry.next_attempt <= now)
+            .map(|entry| entry.id.clone())
+            .collect()
+    }
+
+    pub fn set_sending(&mut self, id: &str) {
+        self.update(id, |entry| entry.state = OutboxState::Sending);
+    }
+
+    /// Records a failed attempt: the message is tried again later, or given
+    /// up on if retrying cannot help or it has failed too often.
+    pub fn record_failure(&mut self, id: &str, error: &str, permanent: bool, now: u64) {
+        self.update(id, |entry| {
+            entry.attempts += 1;
+            entry.last_error = Some(error.to_string());
+            if permanent || entry.attempts >= MAX_ATTEMPTS {
+                entry.state = OutboxState::Failed;
+            } else {
+                entry.state = OutboxState::Queued;
+                entry.next_attempt = now + retry_delay(entry.attempts).as_secs();
+            }
+        });
+    }
+
+    /// Ends the backoff of messages that failed before, when the network is
+    /// known to be back. Messages held for later stay held.
+    pub fn retry_now(&mut self) {
+        let waiting: Vec<String> = self.entries.iter()
+            .filter(|entry| entry.state == OutboxState::Queued && entry.attempts > 0)
+            .map(|entry| entry.id.clone())
+            .collect();
+        for id in waiting {
+            self.update(&id, |entry| entry.next_attempt = entry.send_at);
+        }
+    }
+
+    /// Takes a message out, with its content. Not while it is being sent.
+    pub fn remove(&mut self, id: &str) -> Option<(OutboxEntry, Vec<u8>)> {
+        let position = self.entries.iter()
+            .position(|entry| entry.id == id && entry.state != OutboxState::Sending)?;
+        let content = self.content(id).unwrap_or_default();
+        let entry = self.entries.remove(position);
+        match &self.root {
+            Some(root) => {
+                let _ = fs::remove_file(root.join(format!("{}.json", id)));
+                let _ = fs::remove_file(root.join(format!("{}.eml", id)));
+            }
+            None => {
+                self.contents.remove(id);
+            }
+        }
+        Some((entry, content))
+    }
+
+    fn update(&mut self, id: &str, change: impl FnOnce(&mut OutboxEntry)) {
+        let Some(entry) = self.entries.iter_mut().find(|entry| entry.id == id) else {
+            return;
+        };
+        change(entry);
+        if let Some(root) = &self.root {
+            report_write_error(write_json_atomic(&root.join(format!("{}.json", id)), entry));
+        }
+    }
+}
+
+/// How long to wait after the given number of failed attempts: doubling
+/// from 30 seconds, at most half an hour.
+pub fn retry_delay(attempts: u32) -> Duration {
+    let doublings = attempts.saturating_sub(1).min(16);
+    (FIRST_RETRY * 2u32.pow(doublings)).min(MAX_RETRY)
+}
+
+/// Reads the time a message is to be sent at: `YYYY-MM-DD HH:MM`, or just
+/// `HH:MM` for the next time the clock shows it. Returns Unix time.
+pub fn parse_send_at<Tz: TimeZone>(text: &str, now: DateTime<Tz>) -> Result<u64, String> {
+    let text = text.trim();
+    let zone = now.timezone();
+    let local = if let Ok(date_time) = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M") {
+        date_time
+    } else if let Ok(time) = NaiveTime::parse_from_str(text, "%H:%M") {
+        let today = now.naive_local().date().and_time(time);
+        if today > now.naive_local() { today } else { today + TimeDelta::days(1) }
+    } else {
+        return Err(format!("\"{}\" is not a time; use YYYY-MM-DD HH:MM or HH:MM", text));
+    };
+    // Clocks set forward skip some local times; take the first valid one
+    let time = zone
+        .from_local_datetime(&local)
+        .earliest()
+        .or_else(|| zone.from_local_datetime(&(local + TimeDelta::hours(1))).earliest())
+        .ok_or_else(|| format!("{} does not exist in this time zone", text))?;
+    if time <= now {
+        return Err(format!("{} is in the past", text));
+    }
+    Ok(time.timestamp() as u64)
+}
+
+fn report_write_error(result: std::io::Result<()>) {
+    if let Err(e) = result {
+        error_log::report(format!("Failed to write to the outbox: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_and_gives_up() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(3), Duration::from_secs(120));
+        assert_eq!(retry_delay(20), MAX_RETRY);
+
+        let mut outbox = Outbox::in_memory();
+        let entry = OutboxEntry::new("a@example.com", "Hi", "b@example.com");
+        let id = entry.id.clone();
+        outbox.push(entry, b"message");
+        assert_eq!(outbox.due(0), vec![id.clone()]);
+
+        outbox.set_sending(&id);
+        assert!(outbox.due(0).is_empty());
+        assert!(outbox.remove(&id).is_none(), "removed while sending");
+
+        outbox.record_failure(&id, "Connection error", false, 1000);
+        assert!(outbox.due(1029).is_empty());
+        assert_eq!(outbox.due(1030), vec![id.clone()]);
+        outbox.retry_now();
+        assert_eq!(outbox.due(0), vec![id.clone()]);
+
+        outbox.record_failure(&id, "Server rejected the message", true, 1000);
+        assert_eq!(outbox.get(&id).unwrap().state, OutboxState::Failed);
+        assert!(outbox.due(u64::MAX).is_empty());
+        let (entry, content) = outbox.remove(&id).unwrap();
+        assert_eq!(entry.attempts, 2);
+        assert_eq!(content, b"message");
+    }
+
+    #[test]
+    fn holds_messages_until_their_time() {
+        let mut outbox = Outbox::in_memory();
+        let undoable = OutboxEntry::new("a@example.com", "Oops", "b@example.com").hold_until(110, false);
+        let scheduled = OutboxEntry::new("a@example.com", "Later", "b@example.com").hold_until(5000, true);
+        let (undoable_id, scheduled_id) = (undoable.id.clone(), scheduled.id.clone());
+        outbox.push(undoable, b"1");
+        outbox.push(scheduled, b"2");
+
+        assert!(outbox.due(100).is_empty());
+        assert_eq!(outbox.get(&undoable_id).unwrap().undo_remaining(100), Some(10));
+        assert_eq!(outbox.get(&scheduled_id).unwrap().undo_remaining(100), None);
+        assert!(outbox.get(&scheduled_id).unwrap().is_scheduled_later(100));
+        assert_eq!(outbox.due(110), vec![undoable_id.clone()]);
+        assert_eq!(outbox.get(&undoable_id).unwrap().undo_remaining(110), None);
+
+        // Coming back online does not send scheduled messages early
+        outbox.retry_now();
+        assert_eq!(outbox.due(4999), vec![undoable_id.clone()]);
+        outbox.record_failure(&scheduled_id, "Connection error", false, 5000);
+        outbox.retry_now();
+        assert_eq!(outbox.due(5000), vec![undoable_id, scheduled_id]);
+    }
+
+    #[test]
+    fn keeps_held_messages_across_restarts() {
+        let root = std::env::temp_dir().join(format!("mailcross-outbox-test-{}", std::process::id()));
+        let mut outbox = Outbox::open(&root);
+        let scheduled = OutboxEntry::new("a@example.com", "Later", "b@example.com").hold_until(5000, true);
+        let sending = OutboxEntry::new("a@example.com", "Now", "b@example.com");
+        let (scheduled_id, sending_id) = (scheduled.id.clone(), sending.id.clone());
+        outbox.push(scheduled, b"later");
+        outbox.push(sending, b"now");
+        outbox.set_sending(&sending_id);
+
+        let reopened = Outbox::open(&root);
+        let content = reopened.content(&scheduled_id);
+        let _ = fs::remove_dir_all(&root);
+        assert_eq!(content.as_deref(), Some(&b"later"[..]));
+        let entry = reopened.get(&scheduled_id).unwrap();
+        assert!(entry.scheduled);
+        assert_eq!((entry.send_at, entry.next_attempt), (5000, 5000));
+        assert_eq!(reopened.get(&sending_id).unwrap().state, OutboxState::Queued);
+        assert_eq!(reopened.due(0), vec![sending_id]);
+    }
+
+    #[test]
+    fn parses_send_times() {
+        use chrono::FixedOffset;
+        let zone = FixedOffset::east_opt(3600).unwrap();
+        let now = zone.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+        let at = |y, m, d, h, min| zone.with_ymd_and_hms(y, m, d, h, min, 0).unwrap().timestamp() as u64;
+
+        assert_eq!(parse_send_at("2025-03-02 08:30", now), Ok(at(2025, 3, 2, 8, 30)));
+        assert_eq!(parse_send_at(" 14:00 ", now), Ok(at(2025, 3, 1, 14, 0)));
+        assert_eq!(parse_send_at("09:00", now), Ok(at(2025, 3, 2, 9, 0)));
+        assert!(parse_send_at("2025-02-01 10:00", now).is_err());
+        assert!(parse_send_at("12:00", now).is_ok_and(|time| time == at(2025, 3, 2, 12, 0)));
+        assert!(parse_send_at("tomorrow", now).is_err());
+    }
+}
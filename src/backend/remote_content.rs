@@ -0,0 +1,204 @@
+//! Images HTML mail loads from the network.
+//!
+//! Fetching a remote image tells the sender that, when and where the message
+//! was read, so the preview blocks them until the user allows it. Tracking
+//! pixels are never loaded; they are only reported.
+
+use crate::backend::html::{HtmlElement, HtmlNode};
+use std::time::Duration;
+use ureq::tls::{RootCerts, TlsConfig, TlsProvider};
+
+/// Hosts that serve open-tracking pixels for mailing services, matched
+/// together with their subdomains
+const TRACKER_HOSTS: &[&str] = &[
+    "list-manage.com",
+    "mailchimp.com",
+    "mcsv.net",
+    "sendgrid.net",
+    "mandrillapp.com",
+    "mailgun.org",
+    "mailgun.net",
+    "sparkpostmail.com",
+    "exct.net",
+    "exacttarget.com",
+    "hubspotemail.net",
+    "hs-analytics.net",
+    "mktoresp.com",
+    "createsend.com",
+    "cmail19.com",
+    "cmail20.com",
+    "klaviyomail.com",
+    "mlsend.com",
+    "customeriomail.com",
+    "mailtrack.io",
+    "getnotify.com",
+    "bananatag.com",
+    "yesware.com",
+    "mixmax.com",
+    "superhuman.com",
+    "google-analytics.com",
+    "doubleclick.net",
+];
+
+/// Path fragments of open-tracking endpoints on otherwise unknown hosts
+const TRACKER_PATHS: &[&str] = &["/track/open", "/wf/open", "/open.aspx", "/e/o/", "/pixel.gif", "/beacon"];
+
+/// Downloads larger than this are abandoned
+const MAX_IMAGE_SIZE: u64 = 10 * 1024 * 1024;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// An image a message loads from the network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteImage {
+    pub url: String,
+    pub tracking_pixel: bool,
+}
+
+impl RemoteImage {
+    pub fn host(&self) -> &str {
+        url_host(&self.url).unwrap_or(&self.url)
+    }
+}
+
+/// The remote images of a sanitized document, each URL once, in the order
+/// they appear.
+pub fn remote_images(nodes: &[HtmlNode]) -> Vec<RemoteImage> {
+    fn walk(nodes: &[HtmlNode], images: &mut Vec<RemoteImage>) {
+        for node in nodes {
+            let HtmlNode::Element(element) = node else {
+                continue;
+            };
+            if element.tag == "img"
+                && let Some(url) = remote_url(element)
+                && !images.iter().any(|image| image.url == url)
+            {
+                images.push(RemoteImage {
+                    url: url.to_string(),
+                    tracking_pixel: is_tracking_pixel(element),
+                });
+            }
+            walk(&element.children, images);
+        }
+    }
+    let mut images = Vec::new();
+    walk(nodes, &mut images);
+    images
+}
+
+/// The source of an `img` element if it is loaded over the network.
+pub fn remote_url(image: &HtmlElement) -> Option<&str> {
+    let src = image.attribute("src")?.trim();
+    let scheme = src.split_once(':')?.0;
+    (scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")).then_some(src)
+}
+
+/// Whether an image looks like it exists only to report that the message
+/// was opened: sized 1x1 or smaller, or served by a known tracking service.
+pub fn is_tracking_pixel(image: &HtmlElement) -> bool {
+    let dimension = |name| {
+        image
+            .attribute(name)
+            .and_then(|value| value.trim().trim_end_matches("px").trim().parse::<f32>().ok())
+    };
+    let tiny = match (dimension("width"), dimension("height")) {
+        (Some(width), Some(height)) => width <= 1.0 && height <= 1.0,
+        (Some(size), None) | (None, Some(size)) => size < 1.0,
+        (None, None) => false,
+    };
+    tiny || remote_url(image).is_some_and(is_tracker_url)
+}
+
+fn is_tracker_url(url: &str) -> bool {
+    let Some(host) = url_host(url) else {
+        return false;
+    };
+    let host = host.to_ascii_lowercase();
+    let known_host = TRACKER_HOSTS
+        .iter()
+        .any(|tracker| host == *tracker || host.ends_with(&format!(".{}", tracker)));
+    let path = url.to_ascii_lowercase();
+    known_host || TRACKER_PATHS.iter().any(|fragment| path.contains(fragment))
+}
+
+/// "https://user@img.example.com:8080/a.png" gives "img.example.com".
+pub fn url_host(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = match host.strip_prefix('[') {
+        // IPv6 literal
+        Some(literal) => literal.split(']').next()?,
+        None => host.split(':').next()?,
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+/// Downloads a remote image. Blocking; no cookies or referrer are sent.
+pub fn fetch_image(url: &str) -> Result<Vec<u8>, String> {
+    let tls = TlsConfig::builder()
+        .provider(TlsProvider::NativeTls)
+        .root_certs(RootCerts::PlatformVerifier)
+        .build();
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .tls_config(tls)
+        .timeout_global(Some(FETCH_TIMEOUT))
+        .max_redirects(5)
+        .build()
+        .into();
+
+    let mut response = agent.get(url).call().map_err(|e| e.to_string())?;
+    response
+        .body_mut()
+        .with_config()
+        .limit(MAX_IMAGE_SIZE)
+        .read_to_vec()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::html::sanitize;
+
+    #[test]
+    fn finds_each_remote_image_once() {
+        let nodes = sanitize(
+            r#"<p><img src="https://example.com/logo.png"><img src="cid:part1">
+               <img src="https://example.com/logo.png"><img src="http://cdn.example.org/a.jpg"></p>"#,
+        );
+        let urls: Vec<_> = remote_images(&nodes).into_iter().map(|image| image.url).collect();
+        assert_eq!(urls, ["https://example.com/logo.png", "http://cdn.example.org/a.jpg"]);
+    }
+
+    #[test]
+    fn detects_tiny_images_as_tracking_pixels() {
+        let nodes = sanitize(
+            r#"<img src="https://example.com/o.gif" width="1" height="1">
+               <img src="https://example.com/z.gif" width="0">
+               <img src="https://example.com/photo.jpg" width="1" height="300">"#,
+        );
+        let pixels: Vec<_> = remote_images(&nodes).into_iter().map(|image| image.tracking_pixel).collect();
+        assert_eq!(pixels, [true, true, false]);
+    }
+
+    #[test]
+    fn detects_tracking_services_by_host_and_path() {
+        let nodes = sanitize(
+            r#"<img src="https://acme.us5.list-manage.com/track/x.png">
+               <img src="https://u123.ct.sendgrid.net/wf/open?upn=abc">
+               <img src="https://mail.example.com/track/open/42">
+               <img src="https://notsendgrid.net/banner.png">"#,
+        );
+        let pixels: Vec<_> = remote_images(&nodes).into_iter().map(|image| image.tracking_pixel).collect();
+        assert_eq!(pixels, [true, true, true, false]);
+    }
+
+    #[test]
+    fn extracts_hosts() {
+        assert_eq!(url_host("https://img.example.com/a.png"), Some("img.example.com"));
+        assert_eq!(url_host("http://user@example.com:8080?x"), Some("example.com"));
+        assert_eq!(url_host("https://[::1]:443/"), Some("::1"));
+        assert_eq!(url_host("mailto:a@example.com"), None);
+    }
+}
@@ -0,0 +1,477 @@
+use crate::backend::credentials::{CredentialsManager, SmtpCredentials};
+use crate::backend::imap_client::{is_certificate_error, TlsMode};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use native_tls::TlsConnector;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum SmtpError {
+    Connection(String),
+    Certificate(String),
+    TlsUnavailable(String),
+    Authentication(String),
+    Rejected(u16, String),
+    Protocol(String),
+    Credentials(String),
+}
+
+impl fmt::Display for SmtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmtpError::Connection(msg) => write!(f, "Connection error: {}", msg),
+            SmtpError::Certificate(msg) => write!(
+                f,
+                "The server's certificate could not be verified ({}). It may be self-signed, expired or issued for another host.",
+                msg
+            ),
+            SmtpError::TlsUnavailable(msg) => write!(f, "No secure connection possible: {}", msg),
+            SmtpError::Authentication(msg) => write!(f, "Authentication failed: {}", msg),
+            SmtpError::Rejected(code, msg) => write!(f, "Server rejected the message ({} {})", code, msg),
+            SmtpError::Protocol(msg) => write!(f, "SMTP protocol error: {}", msg),
+            SmtpError::Credentials(msg) => write!(f, "Credential error: {}", msg),
+        }
+    }
+}
+
+impl SmtpError {
+    /// Whether trying again unchanged cannot help: the server refused the
+    /// message for good (5xx) or the account settings need fixing first.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            SmtpError::Rejected(code, _) => *code >= 500,
+            SmtpError::Connection(_) | SmtpError::Protocol(_) => false,
+            SmtpError::Certificate(_) | SmtpError::TlsUnavailable(_) | SmtpError::Authentication(_) | SmtpError::Credentials(_) => true,
+        }
+    }
+}
+
+impl From<std::io::Error> for SmtpError {
+    fn from(error: std::io::Error) -> Self {
+        SmtpError::Connection(error.to_string())
+    }
+}
+
+/// A complete server reply, with continuation lines joined.
+#[derive(Debug, Clone)]
+pub struct SmtpReply {
+    pub code: u16,
+    pub lines: Vec<String>,
+}
+
+impl SmtpReply {
+    pub fn is_positive(&self) -> bool {
+        (200..400).contains(&self.code)
+    }
+
+    pub fn message(&self) -> String {
+        self.lines.join(" ")
+    }
+}
+
+/// Envelope sender and recipients, independent of the message headers so
+/// that BCC recipients never appear in the transmitted data.
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    pub from: String,
+    pub recipients: Vec<String>,
+}
+
+/// One SMTP conversation over an already secured (or, in tests, plain) stream.
+pub struct SmtpConnection<S: Read + Write> {
+    stream: BufReader<S>,
+}
+
+impl<S: Read + Write> SmtpConnection<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    pub fn read_reply(&mut self) -> Result<SmtpReply, SmtpError> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line)? == 0 {
+                return Err(SmtpError::Connection("Server closed the connection".to_string()));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.len() < 3 {
+                return Err(SmtpError::Protocol(format!("Malformed reply: {}", line)));
+            }
+            let code = line[..3]
+                .parse::<u16>()
+                .map_err(|_| SmtpError::Protocol(format!("Malformed reply: {}", line)))?;
+            let last = line.as_bytes().get(3) != Some(&b'-');
+            lines.push(line.get(4..).unwrap_or("").to_string());
+            if last {
+                return Ok(SmtpReply { code, lines });
+            }
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), SmtpError> {
+        let stream = self.stream.get_mut();
+        stream.write_all(line.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    pub fn command(&mut self, line: &str) -> Result<SmtpReply, SmtpError> {
+        self.write_line(line)?;
+        self.read_reply()
+    }
+
+    /// Runs a command and turns any negative reply into `SmtpError::Rejected`.
+    fn expect(&mut self, line: &str) -> Result<SmtpReply, SmtpError> {
+        let reply = self.command(line)?;
+        if reply.is_positive() {
+            Ok(reply)
+        } else {
+            Err(SmtpError::Rejected(reply.code, reply.message()))
+        }
+    }
+
+    pub fn greeting(&mut self) -> Result<(), SmtpError> {
+        let reply = self.read_reply()?;
+        if reply.code == 220 {
+            Ok(())
+        } else {
+            Err(SmtpError::Rejected(reply.code, reply.message()))
+        }
+    }
+
+    /// Sends EHLO and returns the advertised extensions, uppercased.
+    pub fn ehlo(&mut self, client_name: &str) -> Result<Vec<String>, SmtpError> {
+        let reply = self.expect(&format!("EHLO {}", client_name))?;
+        Ok(reply.lines.iter().skip(1).map(|l| l.to_uppercase()).collect())
+    }
+
+    /// Authenticates with AUTH PLAIN, falling back to AUTH LOGIN.
+    pub fn authenticate(&mut self, extensions: &[String], username: &str, password: &str) -> Result<(), SmtpError> {
+        let mechanisms: Vec<&str> = extensions
+            .iter()
+            .filter_map(|ext| ext.strip_prefix("AUTH").map(|m| m.trim_start_matches([' ', '='])))
+            .flat_map(|m| m.split_whitespace())
+            .collect();
+
+        let reply = if mechanisms.contains(&"PLAIN") {
+            let token = BASE64.encode(format!("\0{}\0{}", username, password));
+            self.command(&format!("AUTH PLAIN {}", token))?
+        } else if mechanisms.contains(&"LOGIN") {
+            let challenge = self.command("AUTH LOGIN")?;
+            if challenge.code != 334 {
+                return Err(SmtpError::Authentication(challenge.message()));
+            }
+            let challenge = self.command(&BASE64.encode(username))?;
+            if challenge.code != 334 {
+                return Err(SmtpError::Authentication(challenge.message()));
+            }
+            self.command(&BASE64.encode(password))?
+        } else {
+            return Err(SmtpError::Authentication(
+                "Server offers neither AUTH PLAIN nor AUTH LOGIN".to_string(),
+            ));
+        };
+
+        if reply.code == 235 {
+            Ok(())
+        } else {
+            Err(SmtpError::Authentication(format!("{} {}", reply.code, reply.message())))
+        }
+    }
+
+    /// Transmits one message. Succeeds only if every recipient was accepted.
+    pub fn send_mail(&mut self, envelope: &Envelope, message: &[u8]) -> Result<(), SmtpError> {
+        if envelope.recipients.is_empty() {
+            return Err(SmtpError::Protocol("No recipients".to_string()));
+        }
+
+        self.expect(&format!("MAIL FROM:<{}>", envelope.from))?;
+        for recipient in &envelope.recipients {
+            self.expect(&format!("RCPT TO:<{}>", recipient))?;
+        }
+
+        let reply = self.command("DATA")?;
+        if reply.code != 354 {
+            return Err(SmtpError::Rejected(reply.code, reply.message()));
+        }
+
+        let stream = self.stream.get_mut();
+        stream.write_all(&dot_stuff(message))?;
+        stream.write_all(b".\r\n")?;
+        stream.flush()?;
+
+        let reply = self.read_reply()?;
+        if reply.is_positive() {
+            Ok(())
+        } else {
+            Err(SmtpError::Rejected(reply.code, reply.message()))
+        }
+    }
+
+    pub fn quit(&mut self) {
+        let _ = self.command("QUIT");
+    }
+}
+
+/// Normalizes line endings to CRLF and escapes lines starting with a dot, so
+/// the payload can be terminated with `<CRLF>.<CRLF>`.
+pub fn dot_stuff(message: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(message.len() + 64);
+    let mut at_line_start = true;
+    let mut i = 0;
+    while i < message.len() {
+        let byte = message[i];
+        if at_line_start && byte == b'.' {
+            out.push(b'.');
+        }
+        match byte {
+            b'\r' if message.get(i + 1) == Some(&b'\n') => {
+                out.extend_from_slice(b"\r\n");
+                i += 1;
+                at_line_start = true;
+            }
+            b'\r' | b'\n' => {
+                out.extend_from_slice(b"\r\n");
+                at_line_start = true;
+            }
+            _ => {
+                out.push(byte);
+                at_line_start = false;
+            }
+        }
+        i += 1;
+    }
+    if !at_line_start {
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+/// Runs the authenticated part of a submission (EHLO, AUTH, MAIL, QUIT) on a
+/// connection that has already received the greeting and, if needed, TLS.
+pub fn submit<S: Read + Write>(
+    connection: &mut SmtpConnection<S>,
+    username: &str,
+    password: &str,
+    envelope: &Envelope,
+    message: &[u8],
+) -> Result<(), SmtpError> {
+    let extensions = connection.ehlo(CLIENT_NAME)?;
+    connection.authenticate(&extensions, username, password)?;
+    connection.send_mail(envelope, message)?;
+    connection.quit();
+    Ok(())
+}
+
+const CLIENT_NAME: &str = "localhost";
+
+pub struct SmtpClient {
+    credentials_manager: CredentialsManager,
+}
+
+impl SmtpClient {
+    pub fn new() -> Self {
+        Self {
+            credentials_manager: CredentialsManager::new(),
+        }
+    }
+
+    /// Submits a message on port 465 (implicit TLS) or 587 (STARTTLS). The
+    /// IMAP password stored for the account is used for authentication.
+    pub fn send(&mut self, creds: &SmtpCredentials, envelope: &Envelope, message: &[u8]) -> Result<(), SmtpError> {
+        let password = self.credentials_manager
+            .get_password(&creds.email)
+            .map_err(|e| SmtpError::Credentials(e.to_string()))?;
+
+        let connector = TlsConnector::new()
+            .map_err(|e| SmtpError::Connection(format!("Failed to initialize TLS: {}", e)))?;
+
+        let address = format!("{}:{}", creds.server, creds.port);
+        let tcp_stream = TcpStream::connect(&address)
+            .map_err(|e| SmtpError::Connection(format!("Failed to connect to {}: {}", address, e)))?;
+        tcp_stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        tcp_stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+        match creds.tls_mode {
+            TlsMode::Implicit => {
+                let tls_stream = connector
+                    .connect(&creds.server, tcp_stream)
+                    .map_err(|e| tls_error(e.to_string()))?;
+                let mut connection = SmtpConnection::new(tls_stream);
+                connection.greeting()?;
+                submit(&mut connection, &creds.email, &password, envelope, message)
+            }
+            TlsMode::StartTls => {
+                let mut connection = SmtpConnection::new(tcp_stream);
+                connection.greeting()?;
+                let extensions = connection.ehlo(CLIENT_NAME)?;
+                if !extensions.iter().any(|ext| ext == "STARTTLS") {
+                    return Err(SmtpError::TlsUnavailable(format!("{} does not offer STARTTLS", address)));
+                }
+                connection
+                    .expect("STARTTLS")
+                    .map_err(|e| SmtpError::TlsUnavailable(format!("STARTTLS rejected: {}", e)))?;
+
+                let tls_stream = connector
+                    .connect(&creds.server, connection.into_inner())
+                    .map_err(|e| tls_error(e.to_string()))?;
+                let mut connection = SmtpConnection::new(tls_stream);
+                submit(&mut connection, &creds.email, &password, envelope, message)
+            }
+        }
+    }
+}
+
+impl Default for SmtpClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn tls_error(message: String) -> SmtpError {
+    if is_certificate_error(&message) {
+        SmtpError::Certificate(message)
+    } else {
+        SmtpError::TlsUnavailable(format!("TLS handshake failed: {}", message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves one scripted session: for each expected client line prefix, the
+    /// canned reply is written back. Returns everything the client sent.
+    fn fake_server(script: Vec<(&'static str, &'static str)>) -> (u16, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut transcript = String::new();
+            writer.write_all(b"220 fake.example ESMTP\r\n").unwrap();
+            for (expected, reply) in script {
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line).unwrap() == 0 {
+                        return transcript;
+                    }
+                    transcript.push_str(&line);
+                    // Message data is consumed line by line until the terminator
+                    if expected != "." || line == ".\r\n" {
+                        break;
+                    }
+                }
+                assert!(line.starts_with(expected), "expected {:?}, got {:?}", expected, line);
+                writer.write_all(reply.as_bytes()).unwrap();
+            }
+            transcript
+        });
+        (port, handle)
+    }
+
+    fn connect(port: u16) -> SmtpConnection<TcpStream> {
+        let mut connection = SmtpConnection::new(TcpStream::connect(("127.0.0.1", port)).unwrap());
+        connection.greeting().unwrap();
+        connection
+    }
+
+    fn envelope() -> Envelope {
+        Envelope {
+            from: "me@example.com".to_string(),
+            recipients: vec!["you@example.com".to_string(), "hidden@example.com".to_string()],
+        }
+    }
+
+    #[test]
+    fn submits_with_auth_plain() {
+        let (port, server) = fake_server(vec![
+            ("EHLO", "250-fake.example\r\n250-AUTH LOGIN PLAIN\r\n250 8BITMIME\r\n"),
+            ("AUTH PLAIN", "235 2.7.0 Accepted\r\n"),
+            ("MAIL FROM:<me@example.com>", "250 OK\r\n"),
+            ("RCPT TO:<you@example.com>", "250 OK\r\n"),
+            ("RCPT TO:<hidden@example.com>", "250 OK\r\n"),
+            ("DATA", "354 Go ahead\r\n"),
+            (".", "250 Queued\r\n"),
+            ("QUIT", "221 Bye\r\n"),
+        ]);
+
+        let mut connection = connect(port);
+        let message = b"Subject: Hi\r\n\r\n.hidden dot\nbare newline\r\n";
+        submit(&mut connection, "me@example.com", "secret", &envelope(), message).unwrap();
+        drop(connection);
+
+        let transcript = server.join().unwrap();
+        let token = BASE64.encode("\0me@example.com\0secret");
+        assert!(transcript.contains(&format!("AUTH PLAIN {}\r\n", token)));
+        assert!(transcript.contains("\r\n..hidden dot\r\nbare newline\r\n.\r\n"));
+    }
+
+    #[test]
+    fn falls_back_to_auth_login() {
+        let (port, server) = fake_server(vec![
+            ("EHLO", "250-fake.example\r\n250 AUTH LOGIN\r\n"),
+            ("AUTH LOGIN", "334 VXNlcm5hbWU6\r\n"),
+            ("bWVAZXhhbXBsZS5jb20=", "334 UGFzc3dvcmQ6\r\n"),
+            ("c2VjcmV0", "235 OK\r\n"),
+            ("MAIL FROM", "250 OK\r\n"),
+            ("RCPT TO", "250 OK\r\n"),
+            ("RCPT TO", "250 OK\r\n"),
+            ("DATA", "354 Go ahead\r\n"),
+            (".", "250 Queued\r\n"),
+            ("QUIT", "221 Bye\r\n"),
+        ]);
+
+        let mut connection = connect(port);
+        submit(&mut connection, "me@example.com", "secret", &envelope(), b"Subject: Hi\r\n\r\nBody").unwrap();
+        drop(connection);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn reports_rejected_recipient() {
+        let (port, server) = fake_server(vec![
+            ("EHLO", "250-fake.example\r\n250 AUTH PLAIN\r\n"),
+            ("AUTH PLAIN", "235 OK\r\n"),
+            ("MAIL FROM", "250 OK\r\n"),
+            ("RCPT TO", "550 5.1.1 No such user\r\n"),
+        ]);
+
+        let mut connection = connect(port);
+        let result = submit(&mut connection, "me@example.com", "secret", &envelope(), b"Body");
+        assert!(matches!(result, Err(SmtpError::Rejected(550, _))));
+        drop(connection);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn reports_failed_authentication() {
+        let (port, server) = fake_server(vec![
+            ("EHLO", "250-fake.example\r\n250 AUTH PLAIN\r\n"),
+            ("AUTH PLAIN", "535 5.7.8 Bad credentials\r\n"),
+        ]);
+
+        let mut connection = connect(port);
+        let result = submit(&mut connection, "me@example.com", "wrong", &envelope(), b"Body");
+        assert!(matches!(result, Err(SmtpError::Authentication(_))));
+        drop(connection);
+        server.join().unwrap();
+    }
+}
@@ -18,10 +18,11 @@ pub enum KeyAction {
     Reply,
     Forward,
     Delete,
-    #[allow(dead_code)] // Will be used for email state management
     MarkRead,
-    #[allow(dead_code)] // Will be used for email state management
     MarkUnread,
+    ToggleFlagged,
+    MoveToFolder,
+    CopyToFolder,
     
     // Search and selection
     SearchCurrentEmail,
@@ -32,7 +33,6 @@ pub enum KeyAction {
     SelectAll,
     
     // View operations
-    #[allow(dead_code)] // Will be used for email operations
     ToggleRead,
     RefreshFolder,
     
@@ -110,6 +110,11 @@ impl KeyboardHandler {
             (Key::L, m) if m.ctrl => Some(KeyAction::Forward), // L for forLward/reLay
             (Key::D, m) if m.ctrl => Some(KeyAction::Delete),
             (Key::Delete, _) => Some(KeyAction::Delete),
+            (Key::Q, m) if m.ctrl => Some(KeyAction::MarkRead),
+            (Key::U, m) if m.ctrl => Some(KeyAction::MarkUnread),
+            (Key::Insert, _) => Some(KeyAction::ToggleFlagged),
+            (Key::M, m) if m.ctrl && m.shift => Some(KeyAction::CopyToFolder),
+            (Key::M, m) if m.ctrl => Some(KeyAction::MoveToFolder),
             
             // Search - Standard convention
             (Key::F, m) if m.ctrl && !m.shift => Some(KeyAction::SearchCurrentEmail), // Ctrl+F: search current email
@@ -158,6 +163,10 @@ impl KeyboardHandler {
             (Key::C, _) => Some(KeyAction::Compose),
             (Key::R, _) => Some(KeyAction::Reply),
             (Key::F, _) => Some(KeyAction::Forward),
+            (Key::M, m) if m.shift => Some(KeyAction::MoveToFolder), // M (shift+m)
+            (Key::M, _) => Some(KeyAction::ToggleRead),
+            (Key::Y, _) => Some(KeyAction::CopyToFolder), // y for yank
+            (Key::S, _) => Some(KeyAction::ToggleFlagged),
             
             // Search
             (Key::Slash, _) => Some(KeyAction::SearchAllEmails), // Vim / searches all
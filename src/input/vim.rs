@@ -54,6 +54,13 @@ impl VimState {
             ":set vim" => Some(VimCommand::EnableVimMode),
             ":set novim" => Some(VimCommand::DisableVimMode),
             ":help" => Some(VimCommand::Help),
+            cmd if cmd.starts_with(":move ") => Some(VimCommand::Move(cmd[6..].trim().to_string())),
+            cmd if cmd.starts_with(":copy ") => Some(VimCommand::Copy(cmd[6..].trim().to_string())),
+            ":rmdir" => Some(VimCommand::DeleteFolder),
+            ":subscribe" => Some(VimCommand::Subscribe(true)),
+            ":unsubscribe" => Some(VimCommand::Subscribe(false)),
+            cmd if cmd.starts_with(":mkdir ") => Some(VimCommand::CreateFolder(cmd[7..].trim().to_string())),
+            cmd if cmd.starts_with(":rename ") => Some(VimCommand::RenameFolder(cmd[8..].trim().to_string())),
             cmd if cmd.starts_with(":set ") => {
                 // Handle :set commands
                 let setting = &cmd[5..];
@@ -82,6 +89,12 @@ pub enum VimCommand {
     DisableVimMode,
     Help,
     Set(String),
+    Move(String), // folder
+    Copy(String), // folder
+    CreateFolder(String), // path, levels separated by /
+    RenameFolder(String), // new path of the current folder
+    DeleteFolder,
+    Subscribe(bool),
 }
 
 impl Default for VimState {
@@ -107,6 +120,10 @@ impl VimKeymap {
             ("r", "Reply"),
             ("f", "Forward"),
             ("dd", "Delete"),
+            ("m", "Toggle read/unread"),
+            ("s", "Toggle star (\\Flagged)"),
+            ("M", "Move to folder"),
+            ("y", "Copy to folder"),
             
             // Search
             ("/", "Search"),
@@ -124,6 +141,13 @@ impl VimKeymap {
             (":", "Command mode"),
             (":q", "Quit"),
             (":set novim", "Disable vim mode"),
+            (":move <folder>", "Move to folder"),
+            (":copy <folder>", "Copy to folder"),
+            (":mkdir <path>", "Create folder (a/b for subfolders)"),
+            (":rename <path>", "Rename current folder"),
+            (":rmdir", "Delete current folder"),
+            (":subscribe/:unsubscribe", "Subscribe to current folder"),
+            (":set subscribed", "Show subscribed folders only"),
             
             // Special
             ("Ctrl+U", "Refresh folder"),
@@ -145,6 +169,10 @@ impl VimKeymap {
             ("Ctrl+R", "Reply"),
             ("Ctrl+L", "Forward"),
             ("Ctrl+D/Delete", "Delete email"),
+            ("Ctrl+Q/Ctrl+U", "Mark read/unread"),
+            ("Insert", "Toggle star (\\Flagged)"),
+            ("Ctrl+M", "Move to folder"),
+            ("Ctrl+Shift+M", "Copy to folder"),
             
             // Search
             ("Ctrl+F", "Search current email"),
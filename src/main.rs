@@ -18,6 +18,6 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "MailCross",
         options,
-        Box::new(|_cc| Ok(Box::new(MailCrossApp::new()))),
+        Box::new(|cc| Ok(Box::new(MailCrossApp::new(cc.egui_ctx.clone())))),
     )
 }
\ No newline at end of file
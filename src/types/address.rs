@@ -0,0 +1,243 @@
+//! Addresses typed into the composer.
+//!
+//! Recipient fields are free text ("a@example.com, Bob <b@example.com>"), so
+//! they are parsed into mailboxes and checked before anything is sent.
+
+use std::fmt;
+
+/// A display name and an address, as in `Bob <bob@example.com>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mailbox {
+    pub name: Option<String>,
+    pub address: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressError {
+    pub input: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\" is not a valid address: {}", self.input, self.reason)
+    }
+}
+
+impl Mailbox {
+    /// Parses one mailbox: a bare address, or a name followed by an address
+    /// in angle brackets.
+    pub fn parse(input: &str) -> Result<Self, AddressError> {
+        let input = input.trim();
+        let error = |reason| AddressError { input: input.to_string(), reason };
+        let (name, address) = match input.rfind('<') {
+            Some(start) => {
+                let Some(end) = input[start..].find('>').map(|end| start + end) else {
+                    return Err(error("missing '>'"));
+                };
+                if !input[end + 1..].trim().is_empty() {
+                    return Err(error("text after '>'"));
+                }
+                (Some(unquote(input[..start].trim())), input[start + 1..end].trim())
+            }
+            None => (None, input),
+        };
+        validate_address(address).map_err(error)?;
+        Ok(Self {
+            name: name.filter(|name| !name.is_empty()),
+            address: address.to_string(),
+        })
+    }
+
+    /// Parses a comma or semicolon separated list. An empty field is an
+    /// empty list. Commas inside quotes or angle brackets do not separate
+    /// entries, nor does one in an unquoted name such as "Doe, John <j@x.org>".
+    pub fn parse_list(field: &str) -> Result<Vec<Self>, AddressError> {
+        let mut mailboxes = Vec::new();
+        let mut pending_name = String::new();
+        for entry in split_list(field) {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            // A bit of display name that was cut off at a comma
+            if !entry.contains('@') && !entry.contains('<') {
+                pending_name.push_str(entry);
+                pending_name.push_str(", ");
+                continue;
+            }
+            if pending_name.is_empty() {
+                mailboxes.push(Self::parse(entry)?);
+            } else if entry.contains('<') && !entry.starts_with('<') {
+                mailboxes.push(Self::parse(&format!("{}{}", pending_name, entry))?);
+                pending_name.clear();
+            } else {
+                break;
+            }
+        }
+        if !pending_name.is_empty() {
+            let input = pending_name.trim_end_matches([',', ' ']).to_string();
+            return Err(AddressError { input, reason: "missing '@'" });
+        }
+        Ok(mailboxes)
+    }
+
+    /// The domain part of the address.
+    pub fn domain(&self) -> &str {
+        self.address.rsplit_once('@').map(|(_, domain)| domain).unwrap_or("")
+    }
+}
+
+/// `Name <address>` with the name quoted where needed, or the bare address.
+impl fmt::Display for Mailbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) if is_atom_phrase(name) => write!(f, "{} <{}>", name, self.address),
+            Some(name) => write!(f, "\"{}\" <{}>", name.replace('\\', "\\\\").replace('"', "\\\""), self.address),
+            None => write!(f, "{}", self.address),
+        }
+    }
+}
+
+/// Whether a name can be written as-is in a header phrase (RFC 5322 atoms
+/// separated by spaces).
+pub fn is_atom_phrase(name: &str) -> bool {
+    !name.is_empty()
+        && name.split(' ').all(|word| {
+            !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c))
+        })
+}
+
+fn split_list(field: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut angle = false;
+    let mut start = 0;
+    for (i, c) in field.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '<' if !quoted => angle = true,
+            '>' if !quoted => angle = false,
+            ',' | ';' if !quoted && !angle => {
+                entries.push(&field[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&field[start..]);
+    entries
+}
+
+fn unquote(name: &str) -> String {
+    let Some(inner) = name.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) else {
+        return name.to_string();
+    };
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            unquoted.extend(chars.next());
+        } else {
+            unquoted.push(c);
+        }
+    }
+    unquoted
+}
+
+/// Checks an `addr-spec`: a dot-atom or quoted local part and a domain
+/// name or literal. Internationalized addresses are not supported.
+fn validate_address(address: &str) -> Result<(), &'static str> {
+    if address.is_empty() {
+        return Err("empty address");
+    }
+    if !address.is_ascii() {
+        return Err("non-ASCII addresses are not supported");
+    }
+    // Addresses go verbatim into SMTP commands, where a line break would
+    // start a new command
+    if address.chars().any(|c| c.is_ascii_control()) {
+        return Err("control characters in address");
+    }
+    let Some((local, domain)) = address.rsplit_once('@') else {
+        return Err("missing '@'");
+    };
+    if local.is_empty() {
+        return Err("nothing before '@'");
+    }
+    let quoted_local = local.len() >= 2 && local.starts_with('"') && local.ends_with('"');
+    if !quoted_local && !is_dot_atom(local) {
+        return Err("invalid characters before '@'");
+    }
+    if domain.is_empty() {
+        return Err("nothing after '@'");
+    }
+    if domain.starts_with('[') && domain.ends_with(']') {
+        return Ok(());
+    }
+    let valid_label = |label: &str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    if !domain.split('.').all(valid_label) {
+        return Err("invalid domain");
+    }
+    Ok(())
+}
+
+fn is_dot_atom(text: &str) -> bool {
+    text.split('.')
+        .all(|atom| !atom.is_empty() && atom.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_quoted_commas() {
+        let list = Mailbox::parse_list("a@example.com; Bob <bob@example.org>, \"Doe, Jane\" <jane@example.net>").unwrap();
+        assert_eq!(list[0], Mailbox { name: None, address: "a@example.com".to_string() });
+        assert_eq!(list[1].name.as_deref(), Some("Bob"));
+        assert_eq!(list[1].address, "bob@example.org");
+        assert_eq!(list[2].name.as_deref(), Some("Doe, Jane"));
+        assert_eq!(list[2].to_string(), "\"Doe, Jane\" <jane@example.net>");
+    }
+
+    #[test]
+    fn keeps_unquoted_names_with_commas_together() {
+        let list = Mailbox::parse_list("Doe, John <john@example.com>, x@example.com").unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].name.as_deref(), Some("Doe, John"));
+        assert_eq!(list[1].address, "x@example.com");
+    }
+
+    #[test]
+    fn rejects_malformed_addresses() {
+        for input in ["bob", "bob@", "@example.com", "a b@example.com", "a@exa mple.com", "a@-x.com", "Bob <bob@example.com", "a..b@example.com"] {
+            assert!(Mailbox::parse_list(input).is_err(), "{} accepted", input);
+        }
+        assert!(Mailbox::parse_list(" , ").unwrap().is_empty());
+        assert!(Mailbox::parse("\"odd local\"@[192.0.2.1]").is_ok());
+    }
+
+    #[test]
+    fn rejects_control_characters() {
+        for address in [
+            "\"a\r\nRCPT TO:<x@evil.example>\"@example.com",
+            "\"a\nb\"@example.com",
+            "\"a\0\"@example.com",
+            "a@[192.0.2.1\r\nDATA]",
+            "\"tab\there\"@example.com",
+        ] {
+            assert_eq!(validate_address(address), Err("control characters in address"), "{:?}", address);
+            assert!(Mailbox::parse(&format!("Bob <{}>", address)).is_err());
+        }
+    }
+}
@@ -1,14 +1,36 @@
-#[derive(Debug, Clone, PartialEq)]
+use crate::backend::{MimePart, ParsedMessage, mailbox_name, message_ids};
+use crate::backend::body_structure::BodyPart;
+use crate::types::Mailbox;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 #[allow(dead_code)] // Will be used when IMAP is implemented
 pub struct Email {
+    /// IMAP UID within its folder. Only meaningful together with `uid_validity`.
     pub id: usize,
+    /// UIDVALIDITY of the folder at the time `id` was read (0 if unknown)
+    pub uid_validity: u32,
     pub sender: String,
     pub recipient: String,
     pub subject: String,
     pub date: String,
     pub body: String,
-    pub is_read: bool,
+    pub flags: EmailFlags,
+    #[serde(skip)]
     pub is_selected: bool,
+    /// False while `body` is still a placeholder from the header fetch
+    pub body_loaded: bool,
+    /// Known once the body has been loaded
+    pub attachments: Vec<Attachment>,
+    /// The HTML alternative of the body, if there is one
+    pub html: Option<String>,
+    /// Images the HTML refers to by Content-ID
+    pub inline_images: Vec<Attachment>,
+    /// Message-ID without angle brackets
+    pub message_id: Option<String>,
+    /// Message-IDs of the messages this one follows up, oldest first
+    pub references: Vec<String>,
 }
 
 impl Email {
@@ -16,38 +38,516 @@ impl Email {
     pub fn new(id: usize, sender: &str, recipient: &str, subject: &str, date: &str, body: &str) -> Self {
         Self {
             id,
+            uid_validity: 0,
             sender: sender.to_string(),
             recipient: recipient.to_string(),
             subject: subject.to_string(),
             date: date.to_string(),
             body: body.to_string(),
-            is_read: false,
+            flags: EmailFlags::default(),
             is_selected: false,
+            body_loaded: false,
+            attachments: Vec::new(),
+            html: None,
+            inline_images: Vec::new(),
+            message_id: None,
+            references: Vec::new(),
+        }
+    }
+
+    /// The sender's bare address, lowercased, if the From header has one.
+    pub fn sender_address(&self) -> Option<String> {
+        Mailbox::parse_list(&self.sender)
+            .ok()?
+            .into_iter()
+            .next()
+            .map(|mailbox| mailbox.address.to_lowercase())
+    }
+
+    /// Builds a list entry from a parsed message. Header-only input leaves
+    /// the body unloaded.
+    pub fn from_parsed(id: usize, message: &ParsedMessage) -> Self {
+        let body = message.display_text();
+        let mut email = Self::new(
+            id,
+            &message.from().unwrap_or_else(|| "Unknown Sender".to_string()),
+            &message.to().unwrap_or_default(),
+            &message.subject().unwrap_or_else(|| "(No Subject)".to_string()),
+            &message.date().unwrap_or_else(|| "Unknown Date".to_string()),
+            &body,
+        );
+        email.body_loaded = !body.is_empty();
+        email.attachments = message.attachments().into_iter().map(Attachment::from_mime_part).collect();
+        email.html = message.html_body().map(str::to_string);
+        email.inline_images = message.inline_images().into_iter().map(Attachment::from_mime_part).collect();
+        email.message_id = message.message_id().and_then(|value| message_ids(&value).into_iter().next());
+        email.references = message_ids(&message.references().join(" "));
+        // Some mailers only set In-Reply-To, or leave the parent out of References
+        if let Some(parent) = message.in_reply_to().and_then(|value| message_ids(&value).into_iter().next())
+            && !email.references.contains(&parent) {
+            email.references.push(parent);
         }
+        email
     }
 }
 
-#[derive(Debug, Clone)]
+/// What the preview shows of a message, as loaded on demand.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmailBody {
+    /// Plain text, converted from the HTML when there is no text part
+    pub text: String,
+    pub html: Option<String>,
+    pub attachments: Vec<Attachment>,
+    pub inline_images: Vec<Attachment>,
+}
+
+/// A file attached to a received message. Its content is downloaded only
+/// when it is saved or opened.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Attachment {
+    pub filename: String,
+    pub mime_type: String,
+    /// Decoded size in bytes; approximate when taken from BODYSTRUCTURE
+    pub size: usize,
+    /// IMAP section specifier of the part ("2", "1.3", ...)
+    pub part_id: String,
+    /// Content-ID without angle brackets, for parts shown inline
+    pub content_id: Option<String>,
+}
+
+impl Attachment {
+    pub fn from_body_part(part: &BodyPart) -> Self {
+        // BODYSTRUCTURE gives the encoded size; base64 takes 4 bytes for 3
+        let size = if part.encoding == "base64" { part.size / 4 * 3 } else { part.size };
+        Self {
+            filename: part.filename.clone().unwrap_or_else(|| Self::fallback_name(&part.section)),
+            mime_type: part.mime_type.clone(),
+            size,
+            part_id: part.section.clone(),
+            content_id: part.content_id.clone(),
+        }
+    }
+
+    pub fn from_mime_part(part: &MimePart) -> Self {
+        Self {
+            filename: part.filename().unwrap_or_else(|| Self::fallback_name(&part.section)),
+            mime_type: format!("{}/{}", part.content_type.main_type(), part.content_type.subtype()),
+            size: part.size(),
+            part_id: part.section.clone(),
+            content_id: part.content_id(),
+        }
+    }
+
+    fn fallback_name(part_id: &str) -> String {
+        format!("attachment-{}", part_id)
+    }
+
+    /// Size for display, e.g. "1.2 MB".
+    pub fn size_description(&self) -> String {
+        const UNITS: [&str; 4] = ["bytes", "KB", "MB", "GB"];
+        let mut size = self.size as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} {}", self.size, UNITS[0])
+        } else {
+            format!("{:.1} {}", size, UNITS[unit])
+        }
+    }
+}
+
+/// IMAP flags of a message. \Deleted and \Recent are not kept: deleted
+/// messages are expunged right away and \Recent only means something to the
+/// session that saw it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailFlags {
+    pub seen: bool,
+    pub flagged: bool,
+    pub answered: bool,
+    pub draft: bool,
+    /// Keywords such as `$Forwarded` or `$Junk`
+    pub keywords: Vec<String>,
+}
+
+impl EmailFlags {
+    pub fn contains(&self, flag: &MessageFlag) -> bool {
+        match flag {
+            MessageFlag::Seen => self.seen,
+            MessageFlag::Flagged => self.flagged,
+            MessageFlag::Answered => self.answered,
+            MessageFlag::Draft => self.draft,
+            MessageFlag::Keyword(keyword) => self.keywords.iter().any(|k| k.eq_ignore_ascii_case(keyword)),
+        }
+    }
+
+    pub fn set(&mut self, flag: &MessageFlag, value: bool) {
+        match flag {
+            MessageFlag::Seen => self.seen = value,
+            MessageFlag::Flagged => self.flagged = value,
+            MessageFlag::Answered => self.answered = value,
+            MessageFlag::Draft => self.draft = value,
+            MessageFlag::Keyword(keyword) => {
+                self.keywords.retain(|k| !k.eq_ignore_ascii_case(keyword));
+                if value {
+                    self.keywords.push(keyword.clone());
+                }
+            }
+        }
+    }
+}
+
+/// A single flag, as set or cleared with STORE.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(dead_code)] // Draft and keywords are not set from the UI yet
+pub enum MessageFlag {
+    Seen,
+    Flagged,
+    Answered,
+    Draft,
+    /// Must be an IMAP atom: no spaces, parentheses or quotes
+    Keyword(String),
+}
+
+impl MessageFlag {
+    /// The flag as written in IMAP commands
+    pub fn imap_name(&self) -> &str {
+        match self {
+            MessageFlag::Seen => "\\Seen",
+            MessageFlag::Flagged => "\\Flagged",
+            MessageFlag::Answered => "\\Answered",
+            MessageFlag::Draft => "\\Draft",
+            MessageFlag::Keyword(keyword) => keyword,
+        }
+    }
+}
+
+/// Identifies a message on the server.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MessageRef {
+    pub account_email: String,
+    pub folder: String,
+    pub uid_validity: u32,
+    pub uid: usize,
+}
+
+/// A message ready for submission, as entered in the composer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutgoingEmail {
+    pub from: Mailbox,
+    pub to: Vec<Mailbox>,
+    pub cc: Vec<Mailbox>,
+    pub bcc: Vec<Mailbox>,
+    pub subject: String,
+    pub body: String,
+    pub attachments: Vec<OutgoingAttachment>,
+    /// Message-ID of the message replied to
+    pub in_reply_to: Option<String>,
+    /// Message-IDs of the conversation so far, oldest first
+    pub references: Vec<String>,
+}
+
+/// A file attached in the composer, read when it was added.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutgoingAttachment {
+    pub filename: String,
+    pub mime_type: String,
+    pub content: Vec<u8>,
+}
+
+impl OutgoingAttachment {
+    pub fn from_path(path: &std::path::Path) -> std::io::Result<Self> {
+        let content = std::fs::read(path)?;
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "attachment".to_string());
+        Ok(Self {
+            mime_type: mime_type_for(&filename).to_string(),
+            filename,
+            content,
+        })
+    }
+
+    pub fn size_description(&self) -> String {
+        Attachment { size: self.content.len(), ..Default::default() }.size_description()
+    }
+}
+
+/// Guesses a MIME type from a file name's extension.
+pub fn mime_type_for(filename: &str) -> &'static str {
+    let extension = filename.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "txt" | "log" => "text/plain",
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "json" => "application/json",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "eml" => "message/rfc822",
+        "ics" => "text/calendar",
+        _ => "application/octet-stream",
+    }
+}
+
+impl OutgoingEmail {
+    /// Every address the message is delivered to, including BCC.
+    pub fn envelope_recipients(&self) -> Vec<String> {
+        self.to.iter().chain(&self.cc).chain(&self.bcc).map(|mailbox| mailbox.address.clone()).collect()
+    }
+}
+
+/// Role of a folder, from its RFC 6154 SPECIAL-USE attribute or, failing
+/// that, its name. Ordered as the folders are pinned in the folder list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SpecialUse {
+    Inbox,
+    Drafts,
+    Sent,
+    Archive,
+    All,
+    Junk,
+    Trash,
+}
+
+impl SpecialUse {
+    /// Parses a LIST attribute such as `\Sent`.
+    pub fn from_attribute(attribute: &str) -> Option<Self> {
+        let name = attribute.strip_prefix('\\')?;
+        [
+            ("Drafts", Self::Drafts),
+            ("Sent", Self::Sent),
+            ("Archive", Self::Archive),
+            ("All", Self::All),
+            ("Junk", Self::Junk),
+            ("Trash", Self::Trash),
+        ]
+        .into_iter()
+        .find(|(known, _)| name.eq_ignore_ascii_case(known))
+        .map(|(_, special_use)| special_use)
+    }
+
+    /// Guesses the role from the last level of a decoded name, for servers
+    /// without SPECIAL-USE.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let leaf = name.to_lowercase();
+        match leaf.as_str() {
+            "inbox" => Some(Self::Inbox),
+            "drafts" | "draft" | "entwürfe" | "brouillons" => Some(Self::Drafts),
+            "sent" | "sent items" | "sent mail" | "sent messages" | "gesendet" | "gesendete objekte" | "envoyés" => Some(Self::Sent),
+            "archive" | "archives" | "archiv" => Some(Self::Archive),
+            "all mail" => Some(Self::All),
+            "junk" | "spam" | "junk e-mail" | "junk email" => Some(Self::Junk),
+            "trash" | "deleted" | "deleted items" | "deleted messages" | "papierkorb" | "corbeille" => Some(Self::Trash),
+            _ => None,
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Self::Inbox => "📥",
+            Self::Drafts => "📝",
+            Self::Sent => "📤",
+            Self::Archive => "🗄",
+            Self::All => "📚",
+            Self::Junk => "⚠",
+            Self::Trash => "🗑️",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Folder {
+    /// Mailbox name as the server knows it, in modified UTF-7
     pub name: String,
     pub icon: String,
-    pub count: usize,
+    /// Number of messages
+    #[serde(alias = "count")]
+    pub total: usize,
+    /// Messages without \Seen
+    pub unread: usize,
+    /// Messages with \Recent
+    pub recent: usize,
+    /// UIDNEXT reported by the server (0 if unknown)
+    pub uid_next: u32,
+    /// UIDVALIDITY reported by the server (0 if unknown)
+    pub uid_validity: u32,
+    /// Hierarchy delimiter, `None` on servers without subfolders
+    pub delimiter: Option<char>,
+    /// Listed by LSUB
+    pub subscribed: bool,
+    pub special_use: Option<SpecialUse>,
+    /// False for \Noselect folders, which only hold other folders
+    pub selectable: bool,
+    /// \HasChildren, or known to have subfolders
+    pub has_children: bool,
 }
 
 impl Folder {
-    pub fn new(name: &str, icon: &str, count: usize) -> Self {
-        Self {
+    pub fn new(name: &str, icon: &str, total: usize) -> Self {
+        let mut folder = Self {
             name: name.to_string(),
             icon: icon.to_string(),
-            count,
-        }
+            total,
+            unread: 0,
+            recent: 0,
+            uid_next: 0,
+            uid_validity: 0,
+            delimiter: Some('/'),
+            subscribed: true,
+            special_use: None,
+            selectable: true,
+            has_children: false,
+        };
+        folder.special_use = SpecialUse::from_name(&folder.leaf_name());
+        folder
+    }
+
+    /// The name decoded for display.
+    pub fn decoded_name(&self) -> String {
+        mailbox_name::decode(&self.name)
+    }
+
+    /// Decoded hierarchy levels, outermost first.
+    pub fn path(&self) -> Vec<String> {
+        mailbox_name::components(&self.name, self.delimiter)
+    }
+
+    /// The last hierarchy level, decoded.
+    pub fn leaf_name(&self) -> String {
+        self.path().pop().unwrap_or_default()
+    }
+
+    /// INBOX cannot be renamed away or deleted.
+    pub fn is_inbox(&self) -> bool {
+        self.name.eq_ignore_ascii_case("INBOX")
     }
     
     pub fn display_name(&self) -> String {
-        if self.count > 0 {
-            format!("{} {} ({})", self.icon, self.name, self.count)
+        self.label(&self.decoded_name())
+    }
+
+    /// Label for the folder tree, where the parents are shown above.
+    pub fn tree_name(&self) -> String {
+        self.label(&self.leaf_name())
+    }
+
+    /// Shows the unread count; the total is in the tooltip.
+    fn label(&self, name: &str) -> String {
+        if self.unread > 0 {
+            format!("{} {} ({})", self.icon, name, self.unread)
         } else {
-            format!("{} {}", self.icon, self.name)
+            format!("{} {}", self.icon, name)
+        }
+    }
+
+    /// Hover text with both counts.
+    pub fn counts_description(&self) -> String {
+        format!("{} messages, {} unread", self.total, self.unread)
+    }
+}
+
+impl Default for Folder {
+    fn default() -> Self {
+        Self::new("", "", 0)
+    }
+}
+
+/// A folder and its subfolders, for showing the folder list as a tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FolderNode {
+    /// Index into the folder list; `None` for a level the server did not list
+    pub index: Option<usize>,
+    /// Decoded last hierarchy level
+    pub name: String,
+    /// Decoded full path, unique within the account
+    pub path: String,
+    pub children: Vec<FolderNode>,
+}
+
+impl FolderNode {
+    /// Arranges folders by hierarchy. Special folders come first in
+    /// `SpecialUse` order, each with its subfolders, followed by the
+    /// remaining tree sorted by name.
+    pub fn build(folders: &[Folder]) -> Vec<FolderNode> {
+        let mut roots: Vec<FolderNode> = Vec::new();
+        for (index, folder) in folders.iter().enumerate() {
+            let path = folder.path();
+            let mut level = &mut roots;
+            for depth in 0..path.len() {
+                let position = match level.iter().position(|node| node.name == path[depth]) {
+                    Some(position) => position,
+                    None => {
+                        level.push(FolderNode {
+                            index: None,
+                            name: path[depth].clone(),
+                            path: path[..=depth].join("/"),
+                            children: Vec::new(),
+                        });
+                        level.len() - 1
+                    }
+                };
+                if depth + 1 == path.len() {
+                    level[position].index = Some(index);
+                }
+                level = &mut level[position].children;
+            }
+        }
+
+        let mut pinned = Vec::new();
+        Self::take_special(&mut roots, folders, &mut pinned);
+        pinned.sort_by(|a, b| {
+            let role = |node: &FolderNode| node.index.and_then(|index| folders[index].special_use);
+            role(a).cmp(&role(b)).then_with(|| a.path.cmp(&b.path))
+        });
+        Self::sort_by_name(&mut roots);
+        pinned.extend(roots);
+        pinned
+    }
+
+    /// Moves special folders out of the tree, dropping containers left empty.
+    fn take_special(nodes: &mut Vec<FolderNode>, folders: &[Folder], pinned: &mut Vec<FolderNode>) {
+        let mut remaining = Vec::new();
+        for mut node in nodes.drain(..) {
+            let folder = node.index.map(|index| &folders[index]);
+            if folder.is_some_and(|folder| folder.special_use.is_some()) {
+                Self::sort_by_name(&mut node.children);
+                pinned.push(node);
+                continue;
+            }
+            Self::take_special(&mut node.children, folders, pinned);
+            let container = folder.is_none_or(|folder| !folder.selectable);
+            if !(container && node.children.is_empty()) {
+                remaining.push(node);
+            }
+        }
+        *nodes = remaining;
+    }
+
+    fn sort_by_name(nodes: &mut [FolderNode]) {
+        nodes.sort_by_key(|node| node.name.to_lowercase());
+        for node in nodes {
+            Self::sort_by_name(&mut node.children);
         }
     }
 }
@@ -61,6 +561,11 @@ pub struct Account {
     pub port: u16,
     #[allow(dead_code)] // TLS configuration
     pub use_tls: bool,
+    pub smtp_server: String,
+    pub smtp_port: u16,
+    /// Whether sent mail is copied to the Sent folder; off for providers
+    /// such as Gmail that file it themselves
+    pub save_sent_copy: bool,
     #[allow(dead_code)] // Will store synced folders
     pub folders: Vec<Folder>,
     #[allow(dead_code)] // Will store fetched emails
@@ -76,6 +581,9 @@ impl Account {
             server: String::new(),
             port: 993,
             use_tls: true,
+            smtp_server: String::new(),
+            smtp_port: 587,
+            save_sent_copy: true,
             folders: vec![
                 Folder::new("INBOX", "📥", 0),
                 Folder::new("Sent", "📤", 0),
@@ -95,12 +603,28 @@ impl Account {
             server: server.to_string(),
             port,
             use_tls,
+            smtp_server: String::new(),
+            smtp_port: 587,
+            save_sent_copy: true,
             folders: Vec::new(),
             emails: Vec::new(),
             is_connected: false,
         }
     }
 
+    #[allow(dead_code)] // Will be used for custom server configurations
+    pub fn with_smtp(mut self, server: &str, port: u16) -> Self {
+        self.smtp_server = server.to_string();
+        self.smtp_port = port;
+        self
+    }
+
+    /// For servers that put sent mail in Sent on their own.
+    pub fn without_sent_copy(mut self) -> Self {
+        self.save_sent_copy = false;
+        self
+    }
+
     pub fn connection_status(&self) -> &str {
         if self.is_connected {
             "🟢 Connected"
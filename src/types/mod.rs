@@ -1,3 +1,7 @@
+pub mod address;
 pub mod email;
+pub mod thread;
 
-pub use email::*;
\ No newline at end of file
+pub use address::*;
+pub use email::*;
+pub use thread::*;
@@ -0,0 +1,463 @@
+//! Conversation threads.
+//!
+//! Threads are built with Jamie Zawinski's algorithm
+//! (<https://www.jwz.org/doc/threading.html>) from Message-ID and
+//! References, or taken from a server's `THREAD REFERENCES` response, which
+//! runs the same algorithm over the whole folder.
+
+use crate::types::Email;
+use std::collections::{HashMap, HashSet};
+
+/// A message's place in a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadEntry {
+    /// Index into the message list
+    pub index: usize,
+    /// Reply depth below the thread root
+    pub depth: usize,
+}
+
+/// A conversation in the message list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailThread {
+    /// Messages in conversation order: replies follow what they answer
+    pub entries: Vec<ThreadEntry>,
+}
+
+/// A node of a server THREAD response. `uid` is None where the server
+/// knows of a message it does not have.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadNode {
+    pub uid: Option<usize>,
+    pub children: Vec<ThreadNode>,
+}
+
+impl EmailThread {
+    /// Threads a message list, the thread with the newest message first.
+    pub fn build(emails: &[Email]) -> Vec<Self> {
+        let mut tree = ThreadTree::default();
+        tree.link_references(emails);
+        let roots = tree.prune(tree.roots(), true);
+        let roots = tree.group_by_subject(emails, roots);
+        tree.flatten(emails, roots)
+    }
+
+    /// Threads a message list as the server threaded the folder. None if the
+    /// response does not cover every message, for example because some
+    /// arrived after it.
+    pub fn from_server(emails: &[Email], nodes: &[ThreadNode]) -> Option<Vec<Self>> {
+        let indices: HashMap<usize, usize> = emails.iter().enumerate().map(|(i, email)| (email.id, i)).collect();
+        let mut tree = ThreadTree::default();
+        let roots: Vec<usize> = nodes.iter().map(|node| tree.add_node(node, None, &indices)).collect();
+        if tree.containers.iter().filter(|container| container.message.is_some()).count() != emails.len() {
+            return None;
+        }
+        let roots = tree.prune(roots, true);
+        Some(tree.flatten(emails, roots))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[allow(dead_code)] // Pairs with len
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.entries.iter().any(|entry| entry.index == index)
+    }
+
+    /// The thread's messages with their entries, skipping entries the list
+    /// no longer reaches, as after a move shortened it before the threads
+    /// were rebuilt.
+    pub fn messages<'a>(&'a self, emails: &'a [Email]) -> impl Iterator<Item = (ThreadEntry, &'a Email)> + 'a {
+        self.entries
+            .iter()
+            .filter_map(|entry| emails.get(entry.index).map(|email| (*entry, email)))
+    }
+
+    /// Index of the most recent message.
+    pub fn latest(&self, emails: &[Email]) -> Option<usize> {
+        self.messages(emails)
+            .max_by_key(|(_, email)| email.id)
+            .map(|(entry, _)| entry.index)
+    }
+
+    pub fn unread(&self, emails: &[Email]) -> usize {
+        self.messages(emails).filter(|(_, email)| !email.flags.seen).count()
+    }
+}
+
+/// The subject with reply and forward prefixes removed, lowercased.
+pub fn base_subject(subject: &str) -> String {
+    let mut subject = subject.trim();
+    while let Some((prefix, rest)) = subject.split_once(':') {
+        // "Re", "RE[2]", "Fwd", "AW" (German), "SV" (Nordic)
+        let prefix = prefix.trim().to_lowercase();
+        let word = prefix.split('[').next().unwrap_or("");
+        if !matches!(word, "re" | "fw" | "fwd" | "aw" | "sv" | "wg") {
+            break;
+        }
+        subject = rest.trim_start();
+    }
+    subject.to_lowercase()
+}
+
+fn is_reply(subject: &str) -> bool {
+    base_subject(subject) != subject.trim().to_lowercase()
+}
+
+#[derive(Debug, Default)]
+struct Container {
+    /// Index into the message list; None for a message only known from
+    /// the References of others
+    message: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// Containers linked into a forest, as in the JWZ description.
+#[derive(Debug, Default)]
+struct ThreadTree {
+    containers: Vec<Container>,
+}
+
+impl ThreadTree {
+    fn add(&mut self, message: Option<usize>) -> usize {
+        self.containers.push(Container { message, ..Container::default() });
+        self.containers.len() - 1
+    }
+
+    fn add_node(&mut self, node: &ThreadNode, parent: Option<usize>, indices: &HashMap<usize, usize>) -> usize {
+        let container = self.add(node.uid.and_then(|uid| indices.get(&uid).copied()));
+        if let Some(parent) = parent {
+            self.link(parent, container);
+        }
+        for child in &node.children {
+            self.add_node(child, Some(container), indices);
+        }
+        container
+    }
+
+    fn link(&mut self, parent: usize, child: usize) {
+        self.unlink(child);
+        self.containers[child].parent = Some(parent);
+        self.containers[parent].children.push(child);
+    }
+
+    fn unlink(&mut self, child: usize) {
+        if let Some(parent) = self.containers[child].parent.take() {
+            self.containers[parent].children.retain(|&c| c != child);
+        }
+    }
+
+    /// Whether `ancestor` is `container` or above it.
+    fn is_ancestor(&self, ancestor: usize, container: usize) -> bool {
+        let mut current = Some(container);
+        while let Some(c) = current {
+            if c == ancestor {
+                return true;
+            }
+            current = self.containers[c].parent;
+        }
+        false
+    }
+
+    /// Steps 1 and 2: one container per Message-ID, linked along References.
+    fn link_references(&mut self, emails: &[Email]) {
+        let mut by_id: HashMap<&str, usize> = HashMap::new();
+        for (index, email) in emails.iter().enumerate() {
+            let container = match email.message_id.as_deref().and_then(|id| by_id.get(id).copied()) {
+                // Known from the References of an earlier message
+                Some(container) if self.containers[container].message.is_none() => container,
+                // No Message-ID, or a duplicate: thread it on its own
+                Some(_) | None => {
+                    let container = self.add(None);
+                    if let Some(id) = email.message_id.as_deref() {
+                        by_id.entry(id).or_insert(container);
+                    }
+                    container
+                }
+            };
+            self.containers[container].message = Some(index);
+
+            let mut previous: Option<usize> = None;
+            for reference in &email.references {
+                let current = match by_id.get(reference.as_str()) {
+                    Some(&current) => current,
+                    None => {
+                        let current = self.add(None);
+                        by_id.insert(reference, current);
+                        current
+                    }
+                };
+                // Earlier messages' links are trusted over later ones'
+                if let Some(previous) = previous
+                    && self.containers[current].parent.is_none()
+                    && !self.is_ancestor(current, previous)
+                {
+                    self.link(previous, current);
+                }
+                previous = Some(current);
+            }
+
+            // The message's own References decide its parent
+            match previous {
+                Some(parent) if !self.is_ancestor(container, parent) => self.link(parent, container),
+                Some(_) => {}
+                None => self.unlink(container),
+            }
+        }
+    }
+
+    fn roots(&self) -> Vec<usize> {
+        (0..self.containers.len())
+            .filter(|&c| self.containers[c].parent.is_none())
+            .collect()
+    }
+
+    /// Step 4: drops containers without a message, moving their replies
+    /// up. A missing root with several replies is kept to hold them together.
+    fn prune(&mut self, containers: Vec<usize>, at_root: bool) -> Vec<usize> {
+        let mut kept = Vec::new();
+        for container in containers {
+            let children = std::mem::take(&mut self.containers[container].children);
+            let children = self.prune(children, false);
+            for &child in &children {
+                self.containers[child].parent = Some(container);
+            }
+            self.containers[container].children = children;
+
+            if self.containers[container].message.is_some() || (at_root && self.containers[container].children.len() > 1) {
+                kept.push(container);
+                continue;
+            }
+            let children = std::mem::take(&mut self.containers[container].children);
+            for &child in &children {
+                self.containers[child].parent = None;
+            }
+            if at_root {
+                kept.extend(self.prune(children, true));
+            } else {
+                kept.extend(children);
+            }
+        }
+        kept
+    }
+
+    /// Step 5: joins threads whose references were lost, by subject. Unlike
+    /// the original, two messages that are not replies are not joined, so
+    /// recurring notifications with the same subject stay apart.
+    fn group_by_subject(&mut self, emails: &[Email], roots: Vec<usize>) -> Vec<usize> {
+        let subject_of = |tree: &Self, container: usize| -> Option<&str> {
+            let container = &tree.containers[container];
+            let message = container
+                .message
+                .or_else(|| container.children.first().and_then(|&child| tree.containers[child].message))?;
+            Some(emails[message].subject.as_str())
+        };
+        // Preferred holder of a subject: missing root, then original, then reply
+        let rank = |tree: &Self, container: usize| match tree.containers[container].message {
+            None => 0,
+            Some(message) if !is_reply(&emails[message].subject) => 1,
+            Some(_) => 2,
+        };
+
+        let mut holders: HashMap<String, usize> = HashMap::new();
+        for &root in &roots {
+            let Some(subject) = subject_of(self, root).map(base_subject).filter(|subject| !subject.is_empty()) else {
+                continue;
+            };
+            match holders.get(&subject) {
+                Some(&holder) if rank(self, holder) <= rank(self, root) => {}
+                _ => {
+                    holders.insert(subject, root);
+                }
+            }
+        }
+
+        let mut merged = HashSet::new();
+        for &root in &roots {
+            let Some(subject) = subject_of(self, root).map(base_subject) else {
+                continue;
+            };
+            let Some(&holder) = holders.get(&subject) else {
+                continue;
+            };
+            if holder == root {
+                continue;
+            }
+            match (rank(self, holder), rank(self, root)) {
+                // Two missing roots: one holds the replies of both
+                (0, 0) => {
+                    for child in std::mem::take(&mut self.containers[root].children) {
+                        self.containers[child].parent = None;
+                        self.link(holder, child);
+                    }
+                }
+                (0, _) | (1, 2) => self.link(holder, root),
+                _ => continue,
+            }
+            merged.insert(root);
+        }
+        roots.into_iter().filter(|root| !merged.contains(root)).collect()
+    }
+
+    /// Orders replies by arrival and threads by their newest message.
+    fn flatten(&self, emails: &[Email], roots: Vec<usize>) -> Vec<EmailThread> {
+        let mut threads: Vec<(usize, EmailThread)> = roots
+            .into_iter()
+            .map(|root| {
+                let mut entries = Vec::new();
+                self.collect(emails, root, 0, &mut entries);
+                let newest = entries.iter().map(|entry| emails[entry.index].id).max().unwrap_or(0);
+                (newest, EmailThread { entries })
+            })
+            .filter(|(_, thread)| !thread.entries.is_empty())
+            .collect();
+        threads.sort_by_key(|(newest, _)| std::cmp::Reverse(*newest));
+        threads.into_iter().map(|(_, thread)| thread).collect()
+    }
+
+    fn collect(&self, emails: &[Email], container: usize, depth: usize, entries: &mut Vec<ThreadEntry>) {
+        let (child_depth, message) = match self.containers[container].message {
+            Some(index) => (depth + 1, Some(index)),
+            None => (depth, None),
+        };
+        if let Some(index) = message {
+            entries.push(ThreadEntry { index, depth });
+        }
+        let mut children = self.containers[container].children.clone();
+        children.sort_by_key(|&child| self.oldest(emails, child));
+        for child in children {
+            self.collect(emails, child, child_depth, entries);
+        }
+    }
+
+    /// UID of the first message at or below a container.
+    fn oldest(&self, emails: &[Email], container: usize) -> usize {
+        let own = self.containers[container].message.map(|index| emails[index].id);
+        self.containers[container]
+            .children
+            .iter()
+            .map(|&child| self.oldest(emails, child))
+            .chain(own)
+            .min()
+            .unwrap_or(usize::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(id: usize, subject: &str, message_id: &str, references: &[&str]) -> Email {
+        let mut email = Email::new(id, "a@example.com", "b@example.com", subject, "", "");
+        email.message_id = (!message_id.is_empty()).then(|| message_id.to_string());
+        email.references = references.iter().map(|id| id.to_string()).collect();
+        email
+    }
+
+    fn layout(emails: &[Email], threads: &[EmailThread]) -> Vec<Vec<(usize, usize)>> {
+        threads
+            .iter()
+            .map(|thread| thread.entries.iter().map(|entry| (emails[entry.index].id, entry.depth)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn threads_replies_under_what_they_answer() {
+        // Newest first, as the list is sorted
+        let emails = vec![
+            email(4, "Re: Lunch", "d@x", &["a@x", "b@x"]),
+            email(3, "Other", "c@x", &[]),
+            email(2, "Re: Lunch", "b@x", &["a@x"]),
+            email(1, "Lunch", "a@x", &[]),
+        ];
+        let threads = EmailThread::build(&emails);
+        assert_eq!(layout(&emails, &threads), vec![vec![(1, 0), (2, 1), (4, 2)], vec![(3, 0)]]);
+        assert_eq!(threads[0].latest(&emails).map(|index| emails[index].id), Some(4));
+        assert_eq!(threads[0].unread(&emails), 3);
+    }
+
+    #[test]
+    fn keeps_replies_together_when_the_root_is_missing() {
+        let emails = vec![
+            email(3, "Re: Plan", "c@x", &["a@x"]),
+            email(2, "Re: Plan", "b@x", &["a@x"]),
+        ];
+        let threads = EmailThread::build(&emails);
+        assert_eq!(layout(&emails, &threads), vec![vec![(2, 0), (3, 0)]]);
+    }
+
+    #[test]
+    fn promotes_a_reply_to_a_missing_parent() {
+        let emails = vec![email(2, "Re: Plan", "b@x", &["missing@x"]), email(1, "Hello", "a@x", &[])];
+        let threads = EmailThread::build(&emails);
+        assert_eq!(layout(&emails, &threads), vec![vec![(2, 0)], vec![(1, 0)]]);
+    }
+
+    #[test]
+    fn survives_reference_loops_and_duplicate_ids() {
+        let emails = vec![
+            email(3, "Loop", "a@x", &["b@x"]),
+            email(2, "Loop", "b@x", &["a@x"]),
+            email(1, "Loop", "a@x", &[]),
+        ];
+        let threads = EmailThread::build(&emails);
+        let total: usize = threads.iter().map(EmailThread::len).sum();
+        assert_eq!(total, 3);
+    }
+
+    #[test]
+    fn groups_replies_that_lost_their_references_by_subject() {
+        let emails = vec![
+            email(3, "RE: Report", "c@x", &[]),
+            email(2, "Weekly digest", "b@x", &[]),
+            email(1, "Report", "a@x", &[]),
+            email(0, "Weekly digest", "z@x", &[]),
+        ];
+        let threads = EmailThread::build(&emails);
+        assert_eq!(layout(&emails, &threads), vec![vec![(1, 0), (3, 1)], vec![(2, 0)], vec![(0, 0)]]);
+    }
+
+    #[test]
+    fn uses_the_server_threads_when_they_cover_the_list() {
+        let emails = vec![email(7, "b", "", &[]), email(5, "a", "", &[]), email(4, "a", "", &[])];
+        let node = |uid, children| ThreadNode { uid, children };
+        let nodes = vec![node(Some(4), vec![node(None, vec![node(Some(5), vec![])])]), node(Some(7), vec![])];
+        let threads = EmailThread::from_server(&emails, &nodes).unwrap();
+        assert_eq!(layout(&emails, &threads), vec![vec![(7, 0)], vec![(4, 0), (5, 1)]]);
+
+        assert!(EmailThread::from_server(&emails, &nodes[..1]).is_none());
+    }
+
+    #[test]
+    fn skips_messages_removed_since_threading() {
+        let mut emails = vec![
+            email(3, "Re: Lunch", "c@x", &["a@x"]),
+            email(2, "Other", "b@x", &[]),
+            email(1, "Lunch", "a@x", &[]),
+        ];
+        let threads = EmailThread::build(&emails);
+        // Moved away before the threads are rebuilt
+        emails.truncate(1);
+
+        let walked: Vec<Vec<usize>> = threads
+            .iter()
+            .map(|thread| thread.messages(&emails).map(|(_, email)| email.id).collect())
+            .collect();
+        assert_eq!(walked, vec![vec![3], vec![]]);
+        assert_eq!(threads[0].latest(&emails), Some(0));
+        assert_eq!(threads[1].latest(&emails), None);
+        assert_eq!(threads[1].unread(&emails), 0);
+    }
+
+    #[test]
+    fn strips_reply_prefixes() {
+        assert_eq!(base_subject("Re: RE[2]: Fwd:  Budget"), "budget");
+        assert_eq!(base_subject("Agenda: Monday"), "agenda: monday");
+    }
+}
@@ -1,6 +1,13 @@
 use eframe::egui;
-use crate::types::{Account, Email};
+use crate::backend::{ParsedMessage, REPLYING_TO_HEADER, decode_message_ref, message_ids, new_message_id, outgoing_attachments, parse_send_at};
+use crate::types::{Account, Email, Mailbox, MessageRef, OutgoingAttachment, OutgoingEmail};
+use chrono::{Local, TimeZone};
+use std::path::Path;
 use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// How long edits wait before they are saved as a draft
+const AUTOSAVE_DELAY: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone)]
 pub enum ComposerAction {
@@ -10,19 +17,20 @@ pub enum ComposerAction {
 }
 
 #[derive(Debug, Clone)]
-#[allow(dead_code)] // Will be used for email backend integration
 pub enum ComposerMessage {
     SendEmail {
-        from: String,
-        to: String,
-        subject: String,
-        body: String,
+        message: Box<OutgoingEmail>,
+        replying_to: Option<MessageRef>,
+        /// Message-ID of the saved draft, removed once sent
+        draft_id: Option<String>,
+        /// Unix time it is scheduled for; otherwise it goes once the undo
+        /// delay is over
+        send_at: Option<u64>,
     },
     SaveDraft {
-        from: String,
-        to: String,
-        subject: String,
-        body: String,
+        message: Box<OutgoingEmail>,
+        replying_to: Option<MessageRef>,
+        draft_id: String,
     },
 }
 
@@ -33,9 +41,22 @@ pub struct ComposerWindow {
     pub bcc: String,
     pub subject: String,
     pub body: String,
+    pub attachments: Vec<OutgoingAttachment>,
+    /// Path typed after "Attach…", while the field is shown
+    attach_path: Option<String>,
+    attach_error: Option<String>,
+    /// Why the last send was refused
+    error: Option<String>,
     pub from_account: usize,
     pub mode: ComposerMode,
-    #[allow(dead_code)] // Will be used for message composition
+    /// Server copy of the message being replied to, marked answered once sent
+    pub replying_to: Option<MessageRef>,
+    /// Message-ID the message is saved under in Drafts, once saved
+    draft_id: Option<String>,
+    /// When the fields were first changed after the last save
+    edited_at: Option<Instant>,
+    /// Time typed after "Send at", while scheduling is on
+    send_at: Option<String>,
     sender: Option<Sender<ComposerMessage>>,
 }
 
@@ -55,8 +76,16 @@ impl ComposerWindow {
             bcc: String::new(),
             subject: String::new(),
             body: String::new(),
+            attachments: Vec::new(),
+            attach_path: None,
+            attach_error: None,
+            error: None,
             from_account: 0,
             mode: ComposerMode::Compose,
+            replying_to: None,
+            draft_id: None,
+            edited_at: None,
+            send_at: None,
             sender: None,
         }
     }
@@ -65,13 +94,19 @@ impl ComposerWindow {
         self.visible = true;
         self.mode = ComposerMode::Compose;
         self.from_account = account_index;
+        self.replying_to = None;
         self.clear_fields();
     }
 
-    pub fn show_reply(&mut self, email: &Email, account_index: usize) {
+    pub fn show_reply(&mut self, email: &Email, account_index: usize, replying_to: Option<MessageRef>) {
         self.visible = true;
         self.mode = ComposerMode::Reply(Box::new(email.clone()));
         self.from_account = account_index;
+        self.replying_to = replying_to;
+        self.error = None;
+        self.draft_id = None;
+        self.edited_at = None;
+        self.send_at = None;
         self.to = email.sender.clone();
         self.subject = if email.subject.starts_with("Re: ") {
             email.subject.clone()
@@ -85,15 +120,22 @@ impl ComposerWindow {
         );
         self.cc.clear();
         self.bcc.clear();
+        self.clear_attachments();
     }
 
     pub fn show_forward(&mut self, email: &Email, account_index: usize) {
         self.visible = true;
         self.mode = ComposerMode::Forward(Box::new(email.clone()));
         self.from_account = account_index;
+        self.replying_to = None;
+        self.error = None;
+        self.draft_id = None;
+        self.edited_at = None;
+        self.send_at = None;
         self.to.clear();
         self.cc.clear();
         self.bcc.clear();
+        self.clear_attachments();
         self.subject = if email.subject.starts_with("Fwd: ") {
             email.subject.clone()
         } else {
@@ -114,13 +156,17 @@ impl ComposerWindow {
         }
 
         let mut action = None;
-        let mut should_close = false;
+        let mut open = true;
 
         egui::Window::new("Compose Email")
             .resizable(true)
             .default_size([600.0, 400.0])
-            .open(&mut should_close)
+            .open(&mut open)
             .show(ctx, |ui| {
+                let from_account = self.from_account;
+                let attachment_count = self.attachments.len();
+                let mut edited = false;
+
                 ui.horizontal(|ui| {
                     ui.label("From:");
                     egui::ComboBox::from_id_salt("from_account")
@@ -134,38 +180,59 @@ impl ComposerWindow {
 
                 ui.horizontal(|ui| {
                     ui.label("To:");
-                    ui.text_edit_singleline(&mut self.to);
+                    edited |= ui.text_edit_singleline(&mut self.to).changed();
                 });
 
                 ui.horizontal(|ui| {
                     ui.label("CC:");
-                    ui.text_edit_singleline(&mut self.cc);
+                    edited |= ui.text_edit_singleline(&mut self.cc).changed();
                 });
 
                 ui.horizontal(|ui| {
                     ui.label("BCC:");
-                    ui.text_edit_singleline(&mut self.bcc);
+                    edited |= ui.text_edit_singleline(&mut self.bcc).changed();
                 });
 
                 ui.horizontal(|ui| {
                     ui.label("Subject:");
-                    ui.text_edit_singleline(&mut self.subject);
+                    edited |= ui.text_edit_singleline(&mut self.subject).changed();
                 });
 
                 ui.separator();
 
                 ui.label("Body:");
-                ui.text_edit_multiline(&mut self.body);
+                edited |= ui.text_edit_multiline(&mut self.body).changed();
+
+                self.render_attachments(ui);
+                if edited || from_account != self.from_account || attachment_count != self.attachments.len() {
+                    self.mark_edited();
+                }
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
 
                 ui.separator();
 
                 ui.horizontal(|ui| {
-                    if ui.button("Send").clicked() {
+                    let send_label = if self.send_at.is_some() { "Schedule" } else { "Send" };
+                    if ui.button(send_label).clicked() {
                         action = Some(ComposerAction::Send);
                     }
+                    let mut scheduling = self.send_at.is_some();
+                    if ui.checkbox(&mut scheduling, "Send at").changed() {
+                        self.send_at = scheduling.then(String::new);
+                    }
+                    if let Some(send_at) = &mut self.send_at {
+                        ui.add(egui::TextEdit::singleline(send_at).hint_text("YYYY-MM-DD HH:MM").desired_width(130.0));
+                    }
                     if ui.button("Save Draft").clicked() {
                         action = Some(ComposerAction::Save);
                     }
+                    if ui.button("Attach…").clicked() {
+                        self.attach_path = Some(String::new());
+                        self.attach_error = None;
+                    }
                     if ui.button("Cancel").clicked() {
                         action = Some(ComposerAction::Cancel);
                     }
@@ -178,41 +245,329 @@ impl ComposerWindow {
                     ComposerMode::Forward(_) => "Forward",
                 };
                 ui.separator();
-                ui.weak(format!("Mode: {}", mode_text));
+                ui.horizontal(|ui| {
+                    ui.weak(format!("Mode: {}", mode_text));
+                    if self.draft_id.is_some() {
+                        let state = if self.edited_at.is_some() { "Draft (unsaved changes)" } else { "Draft saved" };
+                        ui.weak(format!("· {}", state));
+                    }
+                });
             });
 
-        // Handle window close button
-        if should_close {
-            self.visible = false;
-            self.clear_fields();
+        // Files dropped anywhere on the window while composing
+        let dropped: Vec<_> = ctx.input(|i| i.raw.dropped_files.iter().filter_map(|file| file.path.clone()).collect());
+        for path in dropped {
+            if self.attach(&path) {
+                self.mark_edited();
+            }
+        }
+
+        // Handle window close button; edits not saved yet are saved as a
+        // draft, and the window stays open if they cannot be
+        if !open {
+            let from = accounts[self.from_account].email.clone();
+            if self.set_aside(&from).is_ok() {
+                self.visible = false;
+                self.clear_fields();
+            }
         }
 
         action
     }
 
+    fn render_attachments(&mut self, ui: &mut egui::Ui) {
+        let files_hovered = ui.ctx().input(|i| !i.raw.hovered_files.is_empty());
+        if files_hovered {
+            ui.weak("Drop files to attach them");
+        }
+
+        let mut removed = None;
+        for (index, attachment) in self.attachments.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("📎 {}", attachment.filename));
+                ui.weak(attachment.size_description());
+                if ui.small_button("✕").on_hover_text("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.attachments.remove(index);
+        }
+
+        if let Some(path) = &mut self.attach_path {
+            let mut submitted = false;
+            let mut cancelled = false;
+            ui.horizontal(|ui| {
+                ui.label("File:");
+                let response = ui.text_edit_singleline(path);
+                response.request_focus();
+                submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                submitted |= ui.button("Attach").clicked();
+                cancelled = ui.button("Cancel").clicked();
+            });
+            if submitted {
+                let path = path.trim().to_string();
+                if self.attach(Path::new(&path)) {
+                    self.attach_path = None;
+                }
+            } else if cancelled {
+                self.attach_path = None;
+                self.attach_error = None;
+            }
+        }
+        if let Some(error) = &self.attach_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+    }
+
+    /// Reads a file into the message. Returns false if it could not be read.
+    pub fn attach(&mut self, path: &Path) -> bool {
+        match OutgoingAttachment::from_path(path) {
+            Ok(attachment) => {
+                self.attachments.push(attachment);
+                self.attach_error = None;
+                true
+            }
+            Err(e) => {
+                self.attach_error = Some(format!("Cannot attach {}: {}", path.display(), e));
+                false
+            }
+        }
+    }
+
+    fn clear_attachments(&mut self) {
+        self.attachments.clear();
+        self.attach_path = None;
+        self.attach_error = None;
+    }
+
     fn clear_fields(&mut self) {
+        self.error = None;
+        self.draft_id = None;
+        self.edited_at = None;
+        self.send_at = None;
         self.to.clear();
         self.cc.clear();
         self.bcc.clear();
         self.subject.clear();
         self.body.clear();
+        self.clear_attachments();
     }
 
-    #[allow(dead_code)] // Will be used for email backend integration
     pub fn set_sender(&mut self, sender: Sender<ComposerMessage>) {
         self.sender = Some(sender);
     }
 
-    #[allow(dead_code)] // Will be used for draft functionality
+    /// Hands the composed message to the backend and closes the window.
+    /// If the message is incomplete or an address is malformed, the window
+    /// stays open and the reason is returned.
+    pub fn submit_send(&mut self, from: &str) -> Result<(), String> {
+        if !self.is_valid() {
+            return Err("Add a recipient and a subject before sending".to_string());
+        }
+        let send_at = match self.send_at.as_deref().map(|text| parse_send_at(text, Local::now())) {
+            Some(Err(e)) => {
+                self.error = Some(e.clone());
+                return Err(e);
+            }
+            Some(Ok(time)) => Some(time),
+            None => None,
+        };
+        let message = match self.outgoing(from) {
+            Ok(message) if message.envelope_recipients().is_empty() => {
+                return Err("Add a recipient before sending".to_string());
+            }
+            Ok(message) => message,
+            Err(e) => {
+                self.error = Some(e.clone());
+                return Err(e);
+            }
+        };
+
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(ComposerMessage::SendEmail {
+                message: Box::new(message),
+                replying_to: self.replying_to.take(),
+                draft_id: self.draft_id.take(),
+                send_at,
+            });
+        }
+
+        self.visible = false;
+        self.clear_fields();
+        Ok(())
+    }
+
+    /// Hands the message to the backend to be saved in Drafts, replacing
+    /// the copy saved before. The window stays open.
+    pub fn submit_draft(&mut self, from: &str) -> Result<(), String> {
+        let message = self.outgoing(from).inspect_err(|e| self.error = Some(e.clone()))?;
+        self.save_draft(message);
+        Ok(())
+    }
+
+    /// Saves the draft in the background without interrupting typing; a
+    /// half-typed address only puts the next attempt off.
+    pub fn autosave(&mut self, from: &str) -> bool {
+        match self.outgoing(from) {
+            Ok(message) => {
+                self.save_draft(message);
+                true
+            }
+            Err(_) => {
+                self.edited_at = Some(Instant::now());
+                false
+            }
+        }
+    }
+
+    /// Saves edits not saved yet as a draft, so that the window can close
+    /// or take another message. Fails, keeping everything, if they cannot
+    /// be saved.
+    pub fn set_aside(&mut self, from: &str) -> Result<(), String> {
+        if !self.visible || self.is_empty() || self.edited_at.is_none() {
+            return Ok(());
+        }
+        self.submit_draft(from)
+    }
+
+    fn save_draft(&mut self, message: OutgoingEmail) {
+        let draft_id = self
+            .draft_id
+            .get_or_insert_with(|| new_message_id(message.from.domain()))
+            .clone();
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(ComposerMessage::SaveDraft {
+                message: Box::new(message),
+                replying_to: self.replying_to.clone(),
+                draft_id,
+            });
+        }
+        self.error = None;
+        self.edited_at = None;
+    }
+
+    /// Whether edits have waited long enough to be saved; otherwise how long
+    /// until they have. None if there is nothing to save.
+    pub fn autosave_due(&self, now: Instant) -> Option<Duration> {
+        if !self.visible || self.is_empty() {
+            return None;
+        }
+        let edited_at = self.edited_at?;
+        Some((edited_at + AUTOSAVE_DELAY).saturating_duration_since(now))
+    }
+
+    fn mark_edited(&mut self) {
+        self.edited_at.get_or_insert_with(Instant::now);
+    }
+
+    /// Reopens a saved draft with all its fields, including the conversation
+    /// it replies to.
+    pub fn show_draft(&mut self, draft: &ParsedMessage, account_index: usize) {
+        self.visible = true;
+        self.clear_fields();
+        self.from_account = account_index;
+        let header = |name| draft.headers().get_decoded(name).unwrap_or_default();
+        self.to = header("To");
+        self.cc = header("Cc");
+        self.bcc = header("Bcc");
+        self.subject = draft.subject().unwrap_or_default();
+        self.body = draft.text_body().unwrap_or_default().to_string();
+        self.attachments = outgoing_attachments(draft);
+        self.draft_id = draft.message_id().and_then(|value| message_ids(&value).into_iter().next());
+        self.replying_to = draft
+            .headers()
+            .get(REPLYING_TO_HEADER)
+            .and_then(decode_message_ref);
+
+        // The original is known by its Message-ID; the draft's References
+        // end with it
+        let parent = draft.in_reply_to().and_then(|value| message_ids(&value).into_iter().next());
+        self.mode = match parent {
+            Some(parent) => {
+                let mut references = message_ids(&draft.references().join(" "));
+                if references.last() == Some(&parent) {
+                    references.pop();
+                }
+                let mut original = Email::new(0, "", "", &self.subject, "", "");
+                original.message_id = Some(parent);
+                original.references = references;
+                ComposerMode::Reply(Box::new(original))
+            }
+            None => ComposerMode::Compose,
+        };
+    }
+
+    /// Reopens a message taken back out of the outbox, with the time it
+    /// was scheduled for. It is saved nowhere now, so it is autosaved as a
+    /// draft unless sent again.
+    pub fn show_unsent(
+        &mut self,
+        message: &ParsedMessage,
+        account_index: usize,
+        replying_to: Option<MessageRef>,
+        draft_id: Option<String>,
+        send_at: Option<u64>,
+    ) {
+        self.show_draft(message, account_index);
+        self.replying_to = replying_to;
+        self.draft_id = draft_id;
+        self.send_at = send_at
+            .and_then(|time| Local.timestamp_opt(time as i64, 0).single())
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string());
+        self.mark_edited();
+    }
+
+    /// Saves a message taken back out of the outbox as a draft without
+    /// showing it, for when the window holds a message that cannot be set
+    /// aside.
+    pub fn save_unsent(
+        &self,
+        message: &ParsedMessage,
+        from: &str,
+        replying_to: Option<MessageRef>,
+        draft_id: Option<String>,
+    ) -> Result<(), String> {
+        let mut unsent = Self::new();
+        unsent.sender = self.sender.clone();
+        unsent.show_unsent(message, self.from_account, replying_to, draft_id, None);
+        unsent.submit_draft(from)
+    }
+
+    /// The message as entered, with the threading headers of a reply.
+    pub fn outgoing(&self, from: &str) -> Result<OutgoingEmail, String> {
+        let parse = |field: &str, label: &str| Mailbox::parse_list(field).map_err(|e| format!("{}: {}", label, e));
+        let (in_reply_to, references) = match &self.mode {
+            ComposerMode::Reply(original) => {
+                let mut references = original.references.clone();
+                references.extend(original.message_id.clone());
+                (original.message_id.clone(), references)
+            }
+            _ => (None, Vec::new()),
+        };
+        Ok(OutgoingEmail {
+            from: Mailbox::parse(from).map_err(|e| format!("From: {}", e))?,
+            to: parse(&self.to, "To")?,
+            cc: parse(&self.cc, "CC")?,
+            bcc: parse(&self.bcc, "BCC")?,
+            subject: self.subject.clone(),
+            body: self.body.clone(),
+            attachments: self.attachments.clone(),
+            in_reply_to,
+            references,
+        })
+    }
+
     pub fn is_empty(&self) -> bool {
         self.to.is_empty() && 
         self.cc.is_empty() && 
         self.bcc.is_empty() && 
         self.subject.is_empty() && 
-        self.body.is_empty()
+        self.body.is_empty() &&
+        self.attachments.is_empty()
     }
 
-    #[allow(dead_code)] // Will be used for validation
     pub fn is_valid(&self) -> bool {
         !self.to.is_empty() && !self.subject.is_empty()
     }
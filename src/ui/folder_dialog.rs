@@ -0,0 +1,152 @@
+use eframe::egui;
+use crate::types::Folder;
+
+#[derive(Debug, Clone)]
+enum FolderDialogKind {
+    /// `parent` holds the decoded hierarchy levels of the parent folder
+    Create { parent: Vec<String> },
+    Rename { folder: String, parent: Vec<String> },
+    Delete { folder: String, display_name: String },
+}
+
+#[derive(Debug, Clone)]
+pub enum FolderDialogAction {
+    Create(Vec<String>), // decoded path
+    Rename { folder: String, new_path: Vec<String> },
+    Delete(String), // folder
+    Cancel,
+}
+
+/// Asks for a folder name when creating or renaming, and for confirmation
+/// when deleting.
+#[derive(Debug, Clone, Default)]
+pub struct FolderDialog {
+    pub visible: bool,
+    kind: Option<FolderDialogKind>,
+    name: String,
+}
+
+impl FolderDialog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn show_create(&mut self, parent: Option<&Folder>) {
+        self.open(FolderDialogKind::Create {
+            parent: parent.map(|folder| folder.path()).unwrap_or_default(),
+        }, String::new());
+    }
+
+    pub fn show_rename(&mut self, folder: &Folder) {
+        let mut parent = folder.path();
+        let name = parent.pop().unwrap_or_default();
+        self.open(FolderDialogKind::Rename { folder: folder.name.clone(), parent }, name);
+    }
+
+    pub fn show_delete(&mut self, folder: &Folder) {
+        self.open(FolderDialogKind::Delete {
+            folder: folder.name.clone(),
+            display_name: folder.decoded_name(),
+        }, String::new());
+    }
+
+    fn open(&mut self, kind: FolderDialogKind, name: String) {
+        self.visible = true;
+        self.kind = Some(kind);
+        self.name = name;
+    }
+
+    pub fn hide(&mut self) {
+        self.visible = false;
+        self.kind = None;
+    }
+
+    pub fn render(&mut self, ctx: &egui::Context) -> Option<FolderDialogAction> {
+        if !self.visible {
+            return None;
+        }
+        let kind = self.kind.clone()?;
+
+        let title = match &kind {
+            FolderDialogKind::Create { .. } => "New Folder",
+            FolderDialogKind::Rename { .. } => "Rename Folder",
+            FolderDialogKind::Delete { .. } => "Delete Folder",
+        };
+
+        let mut action = None;
+        let mut open = true;
+
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                match &kind {
+                    FolderDialogKind::Create { parent } | FolderDialogKind::Rename { parent, .. } => {
+                        if !parent.is_empty() {
+                            ui.weak(format!("In {}", parent.join(" › ")));
+                        }
+                        let response = ui.text_edit_singleline(&mut self.name);
+                        response.request_focus();
+                        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        ui.add_space(10.0);
+
+                        ui.horizontal(|ui| {
+                            let label = if matches!(kind, FolderDialogKind::Create { .. }) { "Create" } else { "Rename" };
+                            let valid = !self.name.trim().is_empty();
+                            if (ui.add_enabled(valid, egui::Button::new(label)).clicked() || (submitted && valid))
+                                && let Some(chosen) = self.submit(&kind) {
+                                action = Some(chosen);
+                            }
+                            if ui.button("Cancel").clicked() {
+                                action = Some(FolderDialogAction::Cancel);
+                            }
+                        });
+                    }
+                    FolderDialogKind::Delete { folder, display_name } => {
+                        ui.vertical_centered(|ui| {
+                            ui.label(format!("Delete the folder \"{}\" and all messages in it?", display_name));
+                            ui.weak("This cannot be undone.");
+                            ui.add_space(20.0);
+
+                            ui.horizontal(|ui| {
+                                if ui.button("Delete").clicked() {
+                                    action = Some(FolderDialogAction::Delete(folder.clone()));
+                                }
+                                if ui.button("Cancel").clicked() {
+                                    action = Some(FolderDialogAction::Cancel);
+                                }
+                            });
+                        });
+                    }
+                }
+            });
+
+        if !open {
+            action = Some(FolderDialogAction::Cancel);
+        }
+        if action.is_some() {
+            self.hide();
+        }
+
+        action
+    }
+
+    fn submit(&self, kind: &FolderDialogKind) -> Option<FolderDialogAction> {
+        let name = self.name.trim().to_string();
+        match kind {
+            FolderDialogKind::Create { parent } => {
+                let mut path = parent.clone();
+                path.push(name);
+                Some(FolderDialogAction::Create(path))
+            }
+            FolderDialogKind::Rename { folder, parent } => {
+                let mut new_path = parent.clone();
+                new_path.push(name);
+                Some(FolderDialogAction::Rename { folder: folder.clone(), new_path })
+            }
+            FolderDialogKind::Delete { .. } => None,
+        }
+    }
+}
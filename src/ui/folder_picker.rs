@@ -0,0 +1,149 @@
+use eframe::egui;
+use crate::types::Account;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferKind {
+    Move,
+    Copy,
+}
+
+impl TransferKind {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            TransferKind::Move => "Move",
+            TransferKind::Copy => "Copy",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FolderPickerAction {
+    Pick { kind: TransferKind, account: usize, folder: String },
+    Cancel,
+}
+
+/// Dialog for choosing where to move or copy the selected message.
+#[derive(Debug, Clone)]
+pub struct FolderPicker {
+    pub visible: bool,
+    pub kind: TransferKind,
+    pub account: usize,
+    pub filter: String,
+}
+
+impl FolderPicker {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            kind: TransferKind::Move,
+            account: 0,
+            filter: String::new(),
+        }
+    }
+
+    /// Opens the picker on the given account's folders.
+    pub fn show(&mut self, kind: TransferKind, account: usize) {
+        self.visible = true;
+        self.kind = kind;
+        self.account = account;
+        self.filter.clear();
+    }
+
+    pub fn hide(&mut self) {
+        self.visible = false;
+    }
+
+    pub fn render(&mut self, ctx: &egui::Context, accounts: &[&Account]) -> Option<FolderPickerAction> {
+        if !self.visible {
+            return None;
+        }
+
+        let mut action = None;
+        let mut open = true;
+
+        egui::Window::new(format!("{} to Folder", self.kind.display_name()))
+            .collapsible(false)
+            .resizable(true)
+            .default_size([320.0, 400.0])
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Account:");
+                    let selected = accounts.get(self.account).map(|a| a.name.as_str()).unwrap_or("");
+                    egui::ComboBox::from_id_salt("folder_picker_account")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for (index, account) in accounts.iter().enumerate() {
+                                ui.selectable_value(&mut self.account, index, format!("{} ({})", account.name, account.email));
+                            }
+                        });
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Filter:");
+                    ui.text_edit_singleline(&mut self.filter).request_focus();
+                });
+                ui.separator();
+
+                let filter = self.filter.to_lowercase();
+                let folders: Vec<&str> = accounts
+                    .get(self.account)
+                    .map(|account| {
+                        account.folders
+                            .iter()
+                            .map(|folder| folder.name.as_str())
+                            .filter(|name| name.to_lowercase().contains(&filter))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                egui::ScrollArea::vertical().id_salt("folder_picker").max_height(280.0).show(ui, |ui| {
+                    if folders.is_empty() {
+                        ui.weak("No matching folders");
+                    }
+                    for name in &folders {
+                        if ui.selectable_label(false, *name).clicked() {
+                            action = Some(FolderPickerAction::Pick {
+                                kind: self.kind,
+                                account: self.account,
+                                folder: name.to_string(),
+                            });
+                        }
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    // Enter picks the only match, or the first one
+                    let enter = ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if (ui.add_enabled(!folders.is_empty(), egui::Button::new(self.kind.display_name())).clicked() || enter)
+                        && let Some(name) = folders.first() {
+                        action = Some(FolderPickerAction::Pick {
+                            kind: self.kind,
+                            account: self.account,
+                            folder: name.to_string(),
+                        });
+                    }
+                    if ui.button("Cancel").clicked() {
+                        action = Some(FolderPickerAction::Cancel);
+                    }
+                });
+            });
+
+        if !open {
+            action = Some(FolderPickerAction::Cancel);
+        }
+        if action.is_some() {
+            self.visible = false;
+        }
+
+        action
+    }
+}
+
+impl Default for FolderPicker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,409 @@
+use eframe::egui;
+use crate::backend::html::{HtmlElement, HtmlNode};
+use crate::backend::remote_content;
+use std::collections::HashMap;
+
+/// Elements laid out as blocks; everything else flows inline
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "caption", "center", "dd", "div", "dl", "dt", "figcaption",
+    "figure", "footer", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li", "main", "nav", "ol", "p", "pre",
+    "section", "table", "tbody", "td", "tfoot", "th", "thead", "tr", "ul",
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct TextStyle {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+    code: bool,
+    small: bool,
+    heading: Option<u8>,
+}
+
+/// A piece of a paragraph.
+enum Inline {
+    Text(String, TextStyle),
+    Link(String, TextStyle, String), // text, style, URL
+    Image(HtmlElement),
+    LineBreak,
+}
+
+/// Images available to a document.
+pub struct HtmlImages<'a> {
+    /// Textures of loaded inline images by Content-ID
+    pub inline: &'a HashMap<String, egui::TextureHandle>,
+    /// Textures of loaded remote images by URL
+    pub remote: &'a HashMap<String, egui::TextureHandle>,
+    /// Whether remote images may be loaded; tracking pixels never are
+    pub load_remote: bool,
+}
+
+/// Images a document shows that are not loaded yet.
+#[derive(Debug, Clone, Default)]
+pub struct MissingImages {
+    pub content_ids: Vec<String>,
+    pub urls: Vec<String>,
+}
+
+impl MissingImages {
+    pub fn is_empty(&self) -> bool {
+        self.content_ids.is_empty() && self.urls.is_empty()
+    }
+}
+
+/// Draws a sanitized HTML document with egui widgets. Layout is an
+/// approximation: tables become grids and CSS is ignored.
+pub struct HtmlView<'a> {
+    images: &'a HtmlImages<'a>,
+    missing: MissingImages,
+    next_id: usize,
+}
+
+impl<'a> HtmlView<'a> {
+    /// Renders `nodes` and returns the images it could not show yet.
+    pub fn render(ui: &mut egui::Ui, nodes: &[HtmlNode], images: &'a HtmlImages<'a>) -> MissingImages {
+        let mut view = Self {
+            images,
+            missing: MissingImages::default(),
+            next_id: 0,
+        };
+        ui.vertical(|ui| {
+            ui.spacing_mut().item_spacing.y = 4.0;
+            view.render_blocks(ui, nodes, TextStyle::default());
+        });
+        view.missing
+    }
+
+    fn render_blocks(&mut self, ui: &mut egui::Ui, nodes: &[HtmlNode], style: TextStyle) {
+        let mut inline = Vec::new();
+        for node in nodes {
+            match node {
+                HtmlNode::Element(element) if BLOCK_ELEMENTS.contains(&element.tag.as_str()) => {
+                    self.render_paragraph(ui, std::mem::take(&mut inline));
+                    self.render_block(ui, element, style);
+                }
+                node => collect_inline(node, style, None, &mut inline),
+            }
+        }
+        self.render_paragraph(ui, inline);
+    }
+
+    fn render_block(&mut self, ui: &mut egui::Ui, element: &HtmlElement, style: TextStyle) {
+        match element.tag.as_str() {
+            "hr" => {
+                ui.separator();
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = element.tag[1..].parse().unwrap_or(6);
+                ui.add_space(4.0);
+                self.render_blocks(ui, &element.children, TextStyle { heading: Some(level), bold: true, ..style });
+            }
+            "pre" => {
+                let text = text_content(&element.children);
+                ui.label(egui::RichText::new(text.trim_end_matches('\n')).monospace());
+            }
+            "blockquote" => {
+                let id = self.next_id(ui, "blockquote");
+                ui.indent(id, |ui| {
+                    self.render_blocks(ui, &element.children, TextStyle { italic: true, ..style });
+                });
+            }
+            "ul" | "ol" => self.render_list(ui, element, style),
+            "dd" => {
+                let id = self.next_id(ui, "dd");
+                ui.indent(id, |ui| self.render_blocks(ui, &element.children, style));
+            }
+            "dt" => self.render_blocks(ui, &element.children, TextStyle { bold: true, ..style }),
+            "table" => self.render_table(ui, element, style),
+            // A list item or table part outside its container
+            _ => self.render_blocks(ui, &element.children, style),
+        }
+    }
+
+    fn render_list(&mut self, ui: &mut egui::Ui, list: &HtmlElement, style: TextStyle) {
+        let ordered = list.tag == "ol";
+        let mut number: i64 = list.attribute("start").and_then(|start| start.trim().parse().ok()).unwrap_or(1);
+        for child in &list.children {
+            let HtmlNode::Element(item) = child else {
+                continue;
+            };
+            let marker = if ordered { format!("{}.", number) } else { "•".to_string() };
+            number += 1;
+            ui.horizontal(|ui| {
+                ui.add_space(8.0);
+                ui.label(marker);
+                ui.vertical(|ui| {
+                    if item.tag == "li" {
+                        self.render_blocks(ui, &item.children, style);
+                    } else {
+                        self.render_blocks(ui, std::slice::from_ref(child), style);
+                    }
+                });
+            });
+        }
+    }
+
+    fn render_table(&mut self, ui: &mut egui::Ui, table: &HtmlElement, style: TextStyle) {
+        let mut rows: Vec<&HtmlElement> = Vec::new();
+        for child in elements(&table.children) {
+            match child.tag.as_str() {
+                "caption" => self.render_blocks(ui, &child.children, TextStyle { bold: true, ..style }),
+                "thead" | "tbody" | "tfoot" => rows.extend(elements(&child.children).filter(|row| row.tag == "tr")),
+                "tr" => rows.push(child),
+                _ => {}
+            }
+        }
+
+        let columns = rows
+            .iter()
+            .map(|row| elements(&row.children).filter(|cell| matches!(cell.tag.as_str(), "td" | "th")).count())
+            .max()
+            .unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        // Share the width between columns so wrapped text stays readable
+        let column_width = (ui.available_width() / columns as f32 - 8.0).max(40.0);
+
+        let id = self.next_id(ui, "table");
+        egui::Grid::new(id).spacing([8.0, 4.0]).show(ui, |ui| {
+            for row in rows {
+                for cell in elements(&row.children).filter(|cell| matches!(cell.tag.as_str(), "td" | "th")) {
+                    ui.vertical(|ui| {
+                        ui.set_max_width(column_width);
+                        let cell_style = if cell.tag == "th" { TextStyle { bold: true, ..style } } else { style };
+                        self.render_blocks(ui, &cell.children, cell_style);
+                    });
+                }
+                ui.end_row();
+            }
+        });
+    }
+
+    fn render_paragraph(&mut self, ui: &mut egui::Ui, items: Vec<Inline>) {
+        let items = collapse_whitespace(items);
+        if items.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            for item in items {
+                match item {
+                    Inline::Text(text, style) => {
+                        ui.label(rich_text(ui, text, style));
+                    }
+                    Inline::Link(text, style, url) => {
+                        ui.hyperlink_to(rich_text(ui, text, style), url);
+                    }
+                    Inline::Image(image) => self.render_image(ui, &image),
+                    Inline::LineBreak => ui.end_row(),
+                }
+            }
+        });
+    }
+
+    fn render_image(&mut self, ui: &mut egui::Ui, image: &HtmlElement) {
+        let alt = image.attribute("alt").unwrap_or("").trim();
+        let content_id = image.attribute("src").and_then(|src| {
+            let src = src.trim();
+            src.get(..4).filter(|scheme| scheme.eq_ignore_ascii_case("cid:")).map(|_| src[4..].to_string())
+        });
+
+        if let Some(content_id) = content_id {
+            if let Some(texture) = self.images.inline.get(&content_id) {
+                Self::show_texture(ui, image, texture);
+                return;
+            }
+            if !self.missing.content_ids.contains(&content_id) {
+                self.missing.content_ids.push(content_id);
+            }
+        } else if let Some(url) = remote_content::remote_url(image) {
+            // Reported in the preview header rather than drawn
+            if remote_content::is_tracking_pixel(image) {
+                return;
+            }
+            if let Some(texture) = self.images.remote.get(url) {
+                Self::show_texture(ui, image, texture);
+                return;
+            }
+            if self.images.load_remote && !self.missing.urls.iter().any(|missing| missing == url) {
+                self.missing.urls.push(url.to_string());
+            }
+        }
+
+        let label = if alt.is_empty() { "🖼".to_string() } else { format!("🖼 {}", alt) };
+        ui.weak(label);
+    }
+
+    fn show_texture(ui: &mut egui::Ui, image: &HtmlElement, texture: &egui::TextureHandle) {
+        let mut size = texture.size_vec2();
+        if let Some(width) = image.attribute("width").and_then(|width| width.trim().trim_end_matches("px").parse::<f32>().ok()) {
+            size = egui::vec2(width, size.y * width / size.x.max(1.0));
+        }
+        let scale = (ui.available_width() / size.x).min(1.0);
+        ui.add(egui::Image::new(texture).fit_to_exact_size(size * scale));
+    }
+
+    fn next_id(&mut self, ui: &egui::Ui, kind: &str) -> egui::Id {
+        self.next_id += 1;
+        ui.id().with((kind, self.next_id))
+    }
+}
+
+fn collect_inline(node: &HtmlNode, style: TextStyle, link: Option<&str>, out: &mut Vec<Inline>) {
+    let element = match node {
+        HtmlNode::Text(text) => {
+            out.push(match link {
+                Some(url) => Inline::Link(text.clone(), style, url.to_string()),
+                None => Inline::Text(text.clone(), style),
+            });
+            return;
+        }
+        HtmlNode::Element(element) => element,
+    };
+
+    let mut style = style;
+    let mut link = link;
+    match element.tag.as_str() {
+        "br" => {
+            out.push(Inline::LineBreak);
+            return;
+        }
+        "img" => {
+            out.push(Inline::Image(element.clone()));
+            return;
+        }
+        "b" | "strong" => style.bold = true,
+        "i" | "em" | "cite" | "dfn" | "var" | "q" => style.italic = true,
+        "u" | "ins" => style.underline = true,
+        "s" | "strike" | "del" => style.strikethrough = true,
+        "code" | "kbd" | "samp" | "tt" => style.code = true,
+        "small" | "sub" | "sup" => style.small = true,
+        "a" => link = element.attribute("href").or(link),
+        _ => {}
+    }
+    for child in &element.children {
+        if let HtmlNode::Element(block) = child
+            && BLOCK_ELEMENTS.contains(&block.tag.as_str())
+        {
+            // A block inside an inline element, e.g. <a><div>..</div></a>
+            out.push(Inline::LineBreak);
+            collect_inline(&HtmlNode::Element(HtmlElement { tag: "span".to_string(), ..block.clone() }), style, link, out);
+            out.push(Inline::LineBreak);
+            continue;
+        }
+        collect_inline(child, style, link, out);
+    }
+}
+
+/// Collapses runs of whitespace to a single space, as HTML does outside
+/// `<pre>`, and trims the ends of the paragraph and its lines.
+fn collapse_whitespace(items: Vec<Inline>) -> Vec<Inline> {
+    let mut out: Vec<Inline> = Vec::new();
+    let mut after_space = true;
+    for mut item in items {
+        let text = match &mut item {
+            Inline::Text(text, _) | Inline::Link(text, _, _) => text,
+            Inline::LineBreak => {
+                trim_trailing_space(&mut out);
+                after_space = true;
+                out.push(item);
+                continue;
+            }
+            Inline::Image(_) => {
+                after_space = false;
+                out.push(item);
+                continue;
+            }
+        };
+        let mut collapsed = String::with_capacity(text.len());
+        for c in text.chars() {
+            // Non-breaking spaces are kept as written
+            if c.is_whitespace() && c != '\u{a0}' {
+                if !after_space {
+                    collapsed.push(' ');
+                    after_space = true;
+                }
+            } else {
+                collapsed.push(c);
+                after_space = false;
+            }
+        }
+        if !collapsed.is_empty() {
+            *text = collapsed;
+            out.push(item);
+        }
+    }
+    trim_trailing_space(&mut out);
+    // Drop line breaks that would only leave empty space at the end
+    while matches!(out.last(), Some(Inline::LineBreak)) {
+        out.pop();
+    }
+    out
+}
+
+fn trim_trailing_space(items: &mut [Inline]) {
+    if let Some(Inline::Text(text, _) | Inline::Link(text, _, _)) = items.last_mut() {
+        let trimmed = text.trim_end_matches(' ').len();
+        text.truncate(trimmed);
+    }
+}
+
+fn rich_text(ui: &egui::Ui, text: String, style: TextStyle) -> egui::RichText {
+    let mut rich = egui::RichText::new(text);
+    if let Some(level) = style.heading {
+        let body = ui.style().text_styles.get(&egui::TextStyle::Body).map_or(14.0, |font| font.size);
+        let scale = match level {
+            1 => 1.6,
+            2 => 1.4,
+            3 => 1.2,
+            _ => 1.05,
+        };
+        rich = rich.size(body * scale);
+    } else if style.small {
+        rich = rich.small();
+    }
+    if style.bold {
+        rich = rich.strong();
+    }
+    if style.italic {
+        rich = rich.italics();
+    }
+    if style.underline {
+        rich = rich.underline();
+    }
+    if style.strikethrough {
+        rich = rich.strikethrough();
+    }
+    if style.code {
+        rich = rich.code();
+    }
+    rich
+}
+
+fn elements(nodes: &[HtmlNode]) -> impl Iterator<Item = &HtmlElement> {
+    nodes.iter().filter_map(|node| match node {
+        HtmlNode::Element(element) => Some(element),
+        HtmlNode::Text(_) => None,
+    })
+}
+
+fn text_content(nodes: &[HtmlNode]) -> String {
+    nodes
+        .iter()
+        .map(|node| match node {
+            HtmlNode::Text(text) => text.clone(),
+            HtmlNode::Element(element) if element.tag == "br" => "\n".to_string(),
+            HtmlNode::Element(element) => text_content(&element.children),
+        })
+        .collect()
+}
+
+/// Decodes an image attachment into a texture, if its format is supported.
+pub fn load_texture(ctx: &egui::Context, name: &str, bytes: &[u8]) -> Option<egui::TextureHandle> {
+    let image = image::load_from_memory(bytes).ok()?.to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    let color_image = egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+    Some(ctx.load_texture(name, color_image, egui::TextureOptions::LINEAR))
+}
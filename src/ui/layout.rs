@@ -1,4 +1,5 @@
 #[derive(Debug, PartialEq, Clone)]
+#[allow(clippy::enum_variant_names)] // Names mirror the pane count of each layout
 pub enum LayoutMode {
     ThreePane,     // Fullscreen: folders | emails | preview (1200+)
     TwoPane,       // Half horizontal: folders+emails | preview (800+) 
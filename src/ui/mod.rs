@@ -4,9 +4,14 @@ pub mod components;
 pub mod composer;
 pub mod search;
 pub mod settings;
+pub mod folder_picker;
+pub mod folder_dialog;
+pub mod html_view;
 
 pub use layout::*;
 pub use panels::*;
 pub use composer::*;
 pub use search::*;
-pub use settings::*;
\ No newline at end of file
+pub use settings::*;
+pub use folder_picker::*;
+pub use folder_dialog::*;
\ No newline at end of file
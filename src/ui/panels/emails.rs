@@ -1,179 +1,311 @@
 use eframe::egui;
+use crate::types::{Email, EmailThread};
 use crate::ui::SearchState;
 
+/// Requested from the message list's context menu.
+#[derive(Debug, Clone)]
+pub enum EmailsAction {
+    ToggleConversations,
+}
+
+/// Sizes of a list row
+struct RowStyle {
+    height: f32,
+    top: f32,
+    padding: f32,
+    sender_size: f32,
+    subject_size: f32,
+    detail_size: f32,
+}
+
+const DESKTOP_ROW: RowStyle = RowStyle {
+    height: 50.0,
+    top: 6.0,
+    padding: 12.0,
+    sender_size: 13.0,
+    subject_size: 12.0,
+    detail_size: 11.0,
+};
+
+const MOBILE_ROW: RowStyle = RowStyle {
+    height: 40.0,
+    top: 4.0,
+    padding: 8.0,
+    sender_size: 12.0,
+    subject_size: 11.0,
+    detail_size: 10.0,
+};
+
+/// Indentation per reply level of an expanded conversation
+const THREAD_INDENT: f32 = 14.0;
+const MAX_THREAD_DEPTH: usize = 6;
+
+/// What the header row of a collapsible conversation shows
+struct ThreadSummary {
+    count: usize,
+    unread: usize,
+    expanded: bool,
+}
+
 pub struct EmailsPanel;
 
 impl EmailsPanel {
-    #[allow(dead_code)] // Used in some layout modes  
-    pub fn render(ui: &mut egui::Ui, selected_email: &mut usize) {
+    #[allow(dead_code)] // Used in some layout modes
+    pub fn render(ui: &mut egui::Ui, selected_email: &mut usize, emails: &[Email], loading: bool, threads: Option<&[EmailThread]>) -> Option<EmailsAction> {
         egui::ScrollArea::vertical()
             .auto_shrink([false, false])
             .id_salt("basic_emails")
-            .show(ui, |ui| {
-                Self::render_email_list(ui, selected_email);
-            });
+            .show(ui, |ui| Self::render_email_list(ui, selected_email, emails, loading, threads))
+            .inner
     }
 
-    pub fn render_with_search(ui: &mut egui::Ui, selected_email: &mut usize, search_state: &SearchState) {
+    pub fn render_with_search(
+        ui: &mut egui::Ui,
+        selected_email: &mut usize,
+        emails: &[Email],
+        loading: bool,
+        search_state: &SearchState,
+        threads: Option<&[EmailThread]>,
+    ) -> Option<EmailsAction> {
         egui::ScrollArea::vertical()
             .auto_shrink([false, false])
             .id_salt("main_emails")
             .show(ui, |ui| {
                 if search_state.active && search_state.has_results() {
                     Self::render_search_results(ui, search_state);
+                    None
                 } else {
-                    Self::render_email_list(ui, selected_email);
+                    Self::render_email_list(ui, selected_email, emails, loading, threads)
                 }
-            });
+            })
+            .inner
     }
-    
+
     #[allow(dead_code)] // Used in some layout modes
-    pub fn render_compact(ui: &mut egui::Ui, selected_email: &mut usize) {
-        Self::render_email_list(ui, selected_email);
+    pub fn render_compact(ui: &mut egui::Ui, selected_email: &mut usize, emails: &[Email], loading: bool, threads: Option<&[EmailThread]>) -> Option<EmailsAction> {
+        Self::render_email_list(ui, selected_email, emails, loading, threads)
     }
-    
-    fn render_email_list(ui: &mut egui::Ui, selected_email: &mut usize) {
+
+    fn render_email_list(ui: &mut egui::Ui, selected_email: &mut usize, emails: &[Email], loading: bool, threads: Option<&[EmailThread]>) -> Option<EmailsAction> {
+        if emails.is_empty() {
+            Self::render_placeholder(ui, loading);
+            return None;
+        }
+
         ui.spacing_mut().item_spacing.y = 0.0;
-        
-        let emails = vec![
-            ("Alice Johnson", "Project Update", "Jan 15"),
-            ("Bob Smith", "Meeting Tomorrow", "Jan 14"), 
-            ("Newsletter", "Weekly Tech News", "Jan 14"),
-            ("Charlie Brown", "Re: Budget Review", "Jan 13"),
-            ("Diana Prince", "Quarterly Report", "Jan 12"),
-            ("Evan Davis", "Lunch Plans", "Jan 11"),
-        ];
-        
-        for (i, (sender, subject, date)) in emails.iter().enumerate() {
-            let selected = *selected_email == i && *selected_email != usize::MAX;
-            
-            // Allocate rect for the entire email item
-            let (rect, response) = ui.allocate_exact_size(
-                egui::vec2(ui.available_width(), 50.0),
-                egui::Sense::click()
+        Self::render_rows(ui, selected_email, emails, threads, &DESKTOP_ROW)
+    }
+
+    /// The messages one per row, or grouped into conversations when threads
+    /// are given.
+    fn render_rows(
+        ui: &mut egui::Ui,
+        selected_email: &mut usize,
+        emails: &[Email],
+        threads: Option<&[EmailThread]>,
+        style: &RowStyle,
+    ) -> Option<EmailsAction> {
+        let mut action = None;
+        let Some(threads) = threads else {
+            for (i, email) in emails.iter().enumerate() {
+                let selected = *selected_email == i && *selected_email != usize::MAX;
+                let (response, _) = Self::render_row(ui, email, selected, style, 0, None);
+                if response.clicked() {
+                    *selected_email = i;
+                }
+                Self::render_row_menu(&response, false, &mut action);
+            }
+            return action;
+        };
+
+        for thread in threads {
+            let Some(latest) = thread.latest(emails) else {
+                continue;
+            };
+            if thread.len() == 1 {
+                let (response, _) = Self::render_row(ui, &emails[latest], *selected_email == latest, style, 0, None);
+                if response.clicked() {
+                    *selected_email = latest;
+                }
+                Self::render_row_menu(&response, true, &mut action);
+                continue;
+            }
+
+            // Expansion is remembered per conversation, by its first message
+            let expanded_id = ui.id().with(("thread_expanded", thread.messages(emails).next().map(|(_, email)| email.id)));
+            let expanded = ui.data(|data| data.get_temp::<bool>(expanded_id)).unwrap_or(false);
+            let summary = ThreadSummary {
+                count: thread.len(),
+                unread: thread.unread(emails),
+                expanded,
+            };
+            let selected = !expanded && thread.contains(*selected_email);
+            let (response, toggled) = Self::render_row(ui, &emails[latest], selected, style, 0, Some(&summary));
+            if toggled {
+                ui.data_mut(|data| data.insert_temp(expanded_id, !expanded));
+            } else if response.clicked() {
+                *selected_email = latest;
+            }
+            Self::render_row_menu(&response, true, &mut action);
+
+            if expanded {
+                for (entry, email) in thread.messages(emails) {
+                    let selected = *selected_email == entry.index;
+                    let (response, _) = Self::render_row(ui, email, selected, style, entry.depth + 1, None);
+                    if response.clicked() {
+                        *selected_email = entry.index;
+                    }
+                    Self::render_row_menu(&response, true, &mut action);
+                }
+            }
+        }
+        action
+    }
+
+    /// Draws one list row; for a conversation header also reports whether
+    /// its expand toggle was clicked.
+    fn render_row(
+        ui: &mut egui::Ui,
+        email: &Email,
+        selected: bool,
+        style: &RowStyle,
+        depth: usize,
+        thread: Option<&ThreadSummary>,
+    ) -> (egui::Response, bool) {
+        // Allocate rect for the entire email item
+        let (rect, response) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), style.height),
+            egui::Sense::click()
+        );
+
+        // Draw selection background
+        if selected {
+            ui.painter().rect_filled(
+                rect,
+                0.0,
+                ui.visuals().selection.bg_fill,
             );
-            
-            if response.clicked() {
-                *selected_email = i;
+        }
+
+        // Draw content inside the rect
+        let mut ui_child = ui.new_child(egui::UiBuilder::new().max_rect(rect).layout(*ui.layout()));
+        ui_child.add_space(style.top);
+        let indent = style.padding + depth.min(MAX_THREAD_DEPTH) as f32 * THREAD_INDENT;
+        let mut toggled = false;
+
+        ui_child.horizontal(|ui| {
+            ui.add_space(indent);
+            if let Some(thread) = thread {
+                let arrow = if thread.expanded { "▾" } else { "▸" };
+                toggled = ui.add(egui::Button::new(egui::RichText::new(arrow).size(style.sender_size)).frame(false)).clicked();
             }
-            
-            // Draw selection background
-            if selected {
-                ui.painter().rect_filled(
-                    rect,
-                    0.0,
-                    ui.visuals().selection.bg_fill,
-                );
+            let sender = egui::RichText::new(&email.sender).size(style.sender_size);
+            let unread = thread.map_or(!email.flags.seen, |thread| thread.unread > 0);
+            ui.label(if unread { sender.strong() } else { sender });
+            if let Some(thread) = thread {
+                ui.label(egui::RichText::new(thread.count.to_string()).size(style.detail_size).weak());
             }
-            
-            // Draw content inside the rect
-            let mut ui_child = ui.child_ui(rect, *ui.layout(), None);
-            ui_child.add_space(6.0);
-            
-            ui_child.horizontal(|ui| {
-                ui.add_space(12.0);
-                ui.label(egui::RichText::new(*sender).strong().size(13.0));
-                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    ui.add_space(12.0);
-                    ui.label(egui::RichText::new(*date).size(11.0).weak());
-                });
-            });
-            
-            ui_child.horizontal(|ui| {
-                ui.add_space(12.0);
-                ui.label(egui::RichText::new(*subject).size(12.0).weak());
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.add_space(style.padding);
+                ui.label(egui::RichText::new(&email.date).size(style.detail_size).weak());
+                ui.label(egui::RichText::new(Self::flag_markers(email)).size(style.detail_size));
+                if let Some(thread) = thread.filter(|thread| thread.unread > 0) {
+                    let badge = egui::RichText::new(format!("● {}", thread.unread)).size(style.detail_size).strong();
+                    ui.label(badge).on_hover_text(format!("{} unread", thread.unread));
+                }
             });
-            
-            ui.separator();
+        });
+
+        ui_child.horizontal(|ui| {
+            ui.add_space(indent);
+            ui.label(egui::RichText::new(&email.subject).size(style.subject_size).weak());
+        });
+
+        ui.separator();
+        (response, toggled)
+    }
+
+    fn render_row_menu(response: &egui::Response, conversations: bool, action: &mut Option<EmailsAction>) {
+        response.context_menu(|ui| {
+            let label = if conversations { "Show as list" } else { "Show as conversations" };
+            if ui.button(label).clicked() {
+                *action = Some(EmailsAction::ToggleConversations);
+                ui.close();
+            }
+        });
+    }
+
+    /// Icons for the flags worth showing in a list row
+    fn flag_markers(email: &Email) -> String {
+        let mut markers = Vec::new();
+        if email.flags.flagged {
+            markers.push("⭐");
+        }
+        if email.flags.answered {
+            markers.push("↩");
         }
+        if email.flags.draft {
+            markers.push("📝");
+        }
+        markers.join(" ")
+    }
+
+    fn render_placeholder(ui: &mut egui::Ui, loading: bool) {
+        ui.add_space(12.0);
+        ui.horizontal(|ui| {
+            ui.add_space(12.0);
+            if loading {
+                ui.spinner();
+                ui.weak("Loading messages...");
+            } else {
+                ui.weak("No messages in this folder");
+            }
+        });
     }
 
     fn render_search_results(ui: &mut egui::Ui, search_state: &SearchState) {
         for (i, email) in search_state.results.iter().enumerate() {
             let selected = i == search_state.selected_result;
-            
+
             ui.group(|ui| {
                 ui.horizontal(|ui| {
                     let email_text = format!("{} - {}", email.sender, email.subject);
                     let response = ui.selectable_label(selected, email_text);
-                    
+
                     if selected {
                         response.scroll_to_me(Some(egui::Align::Center));
                     }
                 });
-                
+
                 // Show search context/preview
                 if !email.body.is_empty() {
                     ui.weak(format!("  {}", email.body.chars().take(60).collect::<String>()));
                 }
             });
-            
+
             ui.add_space(2.0);
         }
     }
 
-    pub fn render_mobile(ui: &mut egui::Ui, selected_email: &mut usize, _search_state: &SearchState) {
-        Self::render_mobile_email_list(ui, selected_email, true);
+    pub fn render_mobile(ui: &mut egui::Ui, selected_email: &mut usize, emails: &[Email], loading: bool, _search_state: &SearchState, threads: Option<&[EmailThread]>) -> Option<EmailsAction> {
+        Self::render_mobile_email_list(ui, selected_email, emails, loading, threads, true)
     }
 
-    pub fn render_mobile_full(ui: &mut egui::Ui, selected_email: &mut usize) {
-        Self::render_mobile_email_list(ui, selected_email, false);
+    pub fn render_mobile_full(ui: &mut egui::Ui, selected_email: &mut usize, emails: &[Email], loading: bool, threads: Option<&[EmailThread]>) -> Option<EmailsAction> {
+        Self::render_mobile_email_list(ui, selected_email, emails, loading, threads, false)
     }
 
-    fn render_mobile_email_list(ui: &mut egui::Ui, selected_email: &mut usize, _compact: bool) {
+    fn render_mobile_email_list(ui: &mut egui::Ui, selected_email: &mut usize, emails: &[Email], loading: bool, threads: Option<&[EmailThread]>, _compact: bool) -> Option<EmailsAction> {
+        if emails.is_empty() {
+            Self::render_placeholder(ui, loading);
+            return None;
+        }
+
         ui.spacing_mut().item_spacing.y = 0.0;
-        
-        let emails = [
-            ("Alice", "Meeting Tomorrow", "Jan 15"),
-            ("Bob", "Project Update", "Jan 14"),
-            ("Carol", "Code Review", "Jan 13"), 
-            ("David", "Status Report", "Jan 12"),
-            ("Evan", "Lunch Plans", "Jan 11"),
-        ];
-
-        egui::ScrollArea::vertical().id_salt("mobile_emails").show(ui, |ui| {
-            for (i, (sender, subject, date)) in emails.iter().enumerate() {
-                let selected = *selected_email == i && *selected_email != usize::MAX;
-                
-                // Allocate rect for the entire email item
-                let (rect, response) = ui.allocate_exact_size(
-                    egui::vec2(ui.available_width(), 40.0),
-                    egui::Sense::click()
-                );
-                
-                if response.clicked() {
-                    *selected_email = i;
-                }
-                
-                // Draw selection background
-                if selected {
-                    ui.painter().rect_filled(
-                        rect,
-                        0.0,
-                        ui.visuals().selection.bg_fill,
-                    );
-                }
-                
-                // Draw content inside the rect
-                let mut ui_child = ui.child_ui(rect, *ui.layout(), None);
-                ui_child.add_space(4.0);
-                
-                ui_child.horizontal(|ui| {
-                    ui.add_space(8.0);
-                    ui.label(egui::RichText::new(*sender).strong().size(12.0));
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        ui.add_space(8.0);
-                        ui.label(egui::RichText::new(*date).size(10.0).weak());
-                    });
-                });
-                
-                ui_child.horizontal(|ui| {
-                    ui.add_space(8.0);
-                    ui.label(egui::RichText::new(*subject).size(11.0).weak());
-                });
-                
-                ui.separator();
-            }
-        });
+
+        egui::ScrollArea::vertical()
+            .id_salt("mobile_emails")
+            .show(ui, |ui| Self::render_rows(ui, selected_email, emails, threads, &MOBILE_ROW))
+            .inner
     }
-}
\ No newline at end of file
+}
@@ -1,70 +1,319 @@
 use eframe::egui;
+use crate::backend::{OutboxEntry, OutboxState, unix_now};
+use crate::types::{Folder, FolderNode};
+
+/// Requested from a folder's context menu; folders are given by index.
+#[derive(Debug, Clone)]
+pub enum FolderAction {
+    Create { parent: Option<usize> },
+    Rename(usize),
+    Delete(usize),
+    SetSubscribed(usize, bool),
+    ToggleSubscribedOnly,
+    /// Take a message out of the outbox into the composer
+    EditOutboxItem(String), // outbox id
+    DeleteOutboxItem(String), // outbox id
+}
 
 pub struct FoldersPanel;
 
 impl FoldersPanel {
-    const FOLDERS: &'static [(&'static str, &'static str, u32)] = &[
-        ("📥", "INBOX", 42),
-        ("📤", "Sent", 0), 
-        ("📝", "Drafts", 0),
-        ("🗑", "Spam", 0),
-    ];
-
-    // Vertical layout with text - properly sized
-    pub fn render(ui: &mut egui::Ui, selected_folder: &mut usize) {
+    // Vertical layout: the folder tree, special folders pinned on top
+    // followed by the outbox
+    pub fn render(ui: &mut egui::Ui, selected_folder: &mut usize, folders: &[Folder], loading: bool, subscribed_only: bool, outbox: &[&OutboxEntry]) -> Option<FolderAction> {
         ui.spacing_mut().item_spacing.y = 0.5;
-        
-        for (i, (icon, name, count)) in Self::FOLDERS.iter().enumerate() {
-            let selected = *selected_folder == i;
-            let label = if *count > 0 {
-                format!("{} {} ({})", icon, name, count)
-            } else {
-                format!("{} {}", icon, name)
-            };
-            
-            if ui.selectable_label(selected, label).clicked() {
-                *selected_folder = i;
+
+        let mut action = None;
+        if folders.is_empty() {
+            Self::render_placeholder(ui, loading);
+            Self::render_outbox(ui, outbox, &mut action);
+            return action;
+        }
+
+        let mut in_pinned = true;
+        for node in &FolderNode::build(folders) {
+            let pinned = node.index.is_some_and(|i| folders[i].special_use.is_some());
+            if in_pinned && !pinned {
+                in_pinned = false;
+                Self::render_outbox(ui, outbox, &mut action);
+                ui.separator();
             }
+            Self::render_node(ui, node, folders, selected_folder, subscribed_only, &mut action);
+        }
+        if in_pinned {
+            Self::render_outbox(ui, outbox, &mut action);
         }
+        action
     }
-    
+
     // Horizontal compact layout with text
-    pub fn render_compact(ui: &mut egui::Ui, selected_folder: &mut usize) {
+    pub fn render_compact(ui: &mut egui::Ui, selected_folder: &mut usize, folders: &[Folder], loading: bool, subscribed_only: bool, outbox: &[&OutboxEntry]) -> Option<FolderAction> {
+        let mut action = None;
         ui.horizontal(|ui| {
             ui.spacing_mut().item_spacing.x = 4.0;
-            
-            for (i, (icon, name, count)) in Self::FOLDERS.iter().enumerate() {
-                let selected = *selected_folder == i;
-                let label = if *count > 0 {
-                    format!("{} {} ({})", icon, name, count)
-                } else {
-                    format!("{} {}", icon, name)
-                };
-                
-                if ui.selectable_label(selected, label).clicked() {
-                    *selected_folder = i;
+
+            if folders.is_empty() {
+                Self::render_placeholder(ui, loading);
+                return;
+            }
+
+            for i in Self::display_order(folders) {
+                let folder = &folders[i];
+                if Self::is_shown(i, folder, *selected_folder, subscribed_only) {
+                    Self::render_folder(ui, i, folder, folder.display_name(), selected_folder, subscribed_only, &mut action);
                 }
             }
+            Self::render_outbox_menu(ui, outbox, &mut action);
         });
+        action
     }
 
     // Mobile with short text
-    pub fn render_mobile(ui: &mut egui::Ui, selected_folder: &mut usize) {
+    pub fn render_mobile(ui: &mut egui::Ui, selected_folder: &mut usize, folders: &[Folder], loading: bool, subscribed_only: bool, outbox: &[&OutboxEntry]) -> Option<FolderAction> {
+        let mut action = None;
         ui.horizontal(|ui| {
             ui.spacing_mut().item_spacing.x = 2.0;
-            
-            for (i, (icon, name, count)) in Self::FOLDERS.iter().enumerate() {
-                let selected = *selected_folder == i;
-                let label = if *count > 0 {
-                    format!("{} {} {}", icon, name, count)
+
+            if folders.is_empty() {
+                Self::render_placeholder(ui, loading);
+                return;
+            }
+
+            for i in Self::display_order(folders) {
+                let folder = &folders[i];
+                if !Self::is_shown(i, folder, *selected_folder, subscribed_only) {
+                    continue;
+                }
+                let label = if folder.unread > 0 {
+                    format!("{} {} {}", folder.icon, folder.decoded_name(), folder.unread)
                 } else {
-                    format!("{} {}", icon, name)
+                    format!("{} {}", folder.icon, folder.decoded_name())
                 };
-                
-                if ui.selectable_label(selected, label).clicked() {
-                    *selected_folder = i;
+
+                Self::render_folder(ui, i, folder, label, selected_folder, subscribed_only, &mut action);
+            }
+            Self::render_outbox_menu(ui, outbox, &mut action);
+        });
+        action
+    }
+
+    fn render_node(
+        ui: &mut egui::Ui,
+        node: &FolderNode,
+        folders: &[Folder],
+        selected_folder: &mut usize,
+        subscribed_only: bool,
+        action: &mut Option<FolderAction>,
+    ) {
+        if !Self::is_node_shown(node, folders, *selected_folder, subscribed_only) {
+            return;
+        }
+
+        let mut render_entry = |ui: &mut egui::Ui| match node.index {
+            Some(i) => Self::render_folder(ui, i, &folders[i], folders[i].tree_name(), selected_folder, subscribed_only, action),
+            // A level the server did not list
+            None => {
+                ui.weak(format!("📁 {}", node.name));
+            }
+        };
+
+        if node.children.is_empty() {
+            // Line up with the labels next to collapse arrows
+            ui.horizontal(|ui| {
+                ui.add_space(ui.spacing().icon_width + ui.spacing().icon_spacing);
+                render_entry(ui);
+            });
+            return;
+        }
+
+        let id = ui.make_persistent_id(("folder_tree", &node.path));
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true)
+            .show_header(ui, |ui| render_entry(ui))
+            .body(|ui| {
+                for child in &node.children {
+                    Self::render_node(ui, child, folders, selected_folder, subscribed_only, action);
                 }
+            });
+    }
+
+    /// Folder indices in the order of the tree, for the flat layouts.
+    fn display_order(folders: &[Folder]) -> Vec<usize> {
+        fn walk(nodes: &[FolderNode], order: &mut Vec<usize>) {
+            for node in nodes {
+                order.extend(node.index);
+                walk(&node.children, order);
+            }
+        }
+        let mut order = Vec::new();
+        walk(&FolderNode::build(folders), &mut order);
+        order
+    }
+
+    fn is_node_shown(node: &FolderNode, folders: &[Folder], selected_folder: usize, subscribed_only: bool) -> bool {
+        node.index.is_some_and(|i| Self::is_shown(i, &folders[i], selected_folder, subscribed_only))
+            || node.children.iter().any(|child| Self::is_node_shown(child, folders, selected_folder, subscribed_only))
+    }
+
+    /// INBOX and the open folder stay visible in the subscribed-only view.
+    fn is_shown(index: usize, folder: &Folder, selected_folder: usize, subscribed_only: bool) -> bool {
+        !subscribed_only || folder.subscribed || folder.is_inbox() || index == selected_folder
+    }
+
+    fn render_folder(
+        ui: &mut egui::Ui,
+        index: usize,
+        folder: &Folder,
+        label: String,
+        selected_folder: &mut usize,
+        subscribed_only: bool,
+        action: &mut Option<FolderAction>,
+    ) {
+        let response = if folder.selectable {
+            ui.selectable_label(*selected_folder == index, label)
+                .on_hover_text(folder.counts_description())
+        } else {
+            // Only holds other folders; kept for its context menu
+            ui.add(egui::Label::new(egui::RichText::new(label).weak()).sense(egui::Sense::click()))
+        };
+        if response.clicked() && folder.selectable {
+            *selected_folder = index;
+        }
+        response.context_menu(|ui| {
+            if let Some(chosen) = Self::render_context_menu(ui, index, folder, subscribed_only) {
+                *action = Some(chosen);
+                ui.close();
             }
         });
     }
-}
\ No newline at end of file
+
+    fn render_context_menu(ui: &mut egui::Ui, index: usize, folder: &Folder, subscribed_only: bool) -> Option<FolderAction> {
+        let mut action = None;
+        if ui.button("New folder…").clicked() {
+            action = Some(FolderAction::Create { parent: None });
+        }
+        if folder.delimiter.is_some() && ui.button("New subfolder…").clicked() {
+            action = Some(FolderAction::Create { parent: Some(index) });
+        }
+        if ui.add_enabled(!folder.is_inbox(), egui::Button::new("Rename…")).clicked() {
+            action = Some(FolderAction::Rename(index));
+        }
+        if ui.add_enabled(!folder.is_inbox(), egui::Button::new("Delete…")).clicked() {
+            action = Some(FolderAction::Delete(index));
+        }
+        ui.separator();
+        let subscription = if folder.subscribed { "Unsubscribe" } else { "Subscribe" };
+        if ui.button(subscription).clicked() {
+            action = Some(FolderAction::SetSubscribed(index, !folder.subscribed));
+        }
+        let view = if subscribed_only { "Show all folders" } else { "Show subscribed only" };
+        if ui.button(view).clicked() {
+            action = Some(FolderAction::ToggleSubscribedOnly);
+        }
+        action
+    }
+
+    /// A virtual Outbox entry listing the messages waiting to be sent, shown
+    /// while there are any.
+    fn render_outbox(ui: &mut egui::Ui, outbox: &[&OutboxEntry], action: &mut Option<FolderAction>) {
+        if outbox.is_empty() {
+            return;
+        }
+        let id = ui.make_persistent_id("outbox");
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true)
+            .show_header(ui, |ui| {
+                ui.label(Self::outbox_label(outbox));
+            })
+            .body(|ui| {
+                for entry in outbox {
+                    Self::render_outbox_entry(ui, entry, action);
+                }
+            });
+    }
+
+    /// The outbox in the flat layouts: its messages open in a menu.
+    fn render_outbox_menu(ui: &mut egui::Ui, outbox: &[&OutboxEntry], action: &mut Option<FolderAction>) {
+        if outbox.is_empty() {
+            return;
+        }
+        ui.menu_button(Self::outbox_label(outbox), |ui| {
+            for entry in outbox {
+                Self::render_outbox_entry(ui, entry, action);
+            }
+        });
+    }
+
+    fn outbox_label(outbox: &[&OutboxEntry]) -> String {
+        let failed = outbox.iter().filter(|entry| entry.state == OutboxState::Failed).count();
+        if failed > 0 {
+            format!("📤 Outbox {} ⚠ {}", outbox.len(), failed)
+        } else {
+            format!("📤 Outbox {}", outbox.len())
+        }
+    }
+
+    /// One message of the outbox with its state. Messages not being sent
+    /// can be taken back; failed ones offer it right away.
+    fn render_outbox_entry(ui: &mut egui::Ui, entry: &OutboxEntry, action: &mut Option<FolderAction>) {
+        let error = entry.last_error.as_deref().unwrap_or_default();
+        let now = unix_now();
+        let scheduled = entry.is_scheduled_later(now);
+        let (icon, state) = match entry.state {
+            OutboxState::Queued if scheduled => ("🕒", format!("Scheduled for {}", Self::format_send_at(entry.send_at))),
+            OutboxState::Queued if entry.attempts == 0 => match entry.undo_remaining(now) {
+                Some(remaining) => ("⏳", format!("Sending in {} s", remaining)),
+                None => ("⏳", "Queued".to_string()),
+            },
+            OutboxState::Queued => ("⏳", format!("Will retry after: {}", error)),
+            OutboxState::Sending => ("📨", "Sending…".to_string()),
+            OutboxState::Failed => ("⚠", format!("Failed: {}", error)),
+        };
+        let subject = if entry.subject.is_empty() { "(no subject)" } else { &entry.subject };
+        let response = ui
+            .add(egui::Label::new(format!("{} {}", icon, subject)).truncate().sense(egui::Sense::click()))
+            .on_hover_text(format!("To: {}\n{}", entry.recipients, state));
+        if entry.state == OutboxState::Sending {
+            return;
+        }
+        response.context_menu(|ui| {
+            if let Some(chosen) = Self::render_outbox_buttons(ui, entry) {
+                *action = Some(chosen);
+                ui.close();
+            }
+        });
+        if entry.state == OutboxState::Failed || scheduled {
+            ui.horizontal(|ui| {
+                if let Some(chosen) = Self::render_outbox_buttons(ui, entry) {
+                    *action = Some(chosen);
+                }
+            });
+        }
+    }
+
+    fn format_send_at(send_at: u64) -> String {
+        chrono::DateTime::from_timestamp(send_at as i64, 0)
+            .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default()
+    }
+
+    fn render_outbox_buttons(ui: &mut egui::Ui, entry: &OutboxEntry) -> Option<FolderAction> {
+        let mut action = None;
+        if ui.small_button("✏ Edit").clicked() {
+            action = Some(FolderAction::EditOutboxItem(entry.id.clone()));
+        }
+        if ui.small_button("🗑 Delete").clicked() {
+            action = Some(FolderAction::DeleteOutboxItem(entry.id.clone()));
+        }
+        action
+    }
+
+    fn render_placeholder(ui: &mut egui::Ui, loading: bool) {
+        if loading {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.weak("Loading folders...");
+            });
+        } else {
+            ui.weak("No folders");
+        }
+    }
+}
@@ -1,18 +1,74 @@
 use eframe::egui;
+use crate::backend::html::HtmlNode;
+use crate::backend::remote_content::RemoteImage;
+use crate::types::{Attachment, Email};
+use crate::ui::html_view::{HtmlImages, HtmlView, MissingImages};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone)]
+pub enum PreviewAction {
+    SaveAttachment(Attachment),
+    OpenAttachment(Attachment),
+    /// Images the HTML part shows that are not loaded yet
+    LoadImages(MissingImages),
+    ShowPlainText(bool),
+    /// Show the remote images of the open message this time only
+    LoadRemoteImagesOnce,
+    /// Always show remote images from a sender address or domain
+    AllowRemoteImages(String),
+    /// Make another message of the conversation the current one
+    SelectEmail(usize), // email id
+    /// Fetch the body of another message of the conversation
+    LoadBody(usize), // email id
+    /// Reopen the draft in the composer
+    EditDraft,
+}
+
+/// Reply depth beyond which conversation messages are not indented further
+const MAX_THREAD_INDENT: usize = 6;
+
+/// What the preview needs besides the message itself
+pub struct PreviewContent<'a> {
+    pub loading: bool,
+    /// Part ids of attachments being fetched
+    pub downloading: &'a HashSet<String>,
+    /// The sanitized HTML part of the message, if it has one
+    pub html: Option<&'a [HtmlNode]>,
+    pub images: HtmlImages<'a>,
+    /// Images the HTML part loads from the web
+    pub remote_images: &'a [RemoteImage],
+    pub plain_text: bool,
+    /// The message's conversation with reply depths, when stacked
+    pub thread: &'a [(&'a Email, usize)],
+    /// Ids of messages whose bodies are being fetched
+    pub bodies_loading: &'a HashSet<usize>,
+}
 
 pub struct PreviewPanel;
 
 impl PreviewPanel {
-    pub fn render(ui: &mut egui::Ui, _selected_email: usize) {
+    pub fn render(ui: &mut egui::Ui, email: Option<&Email>, content: &PreviewContent) -> Option<PreviewAction> {
         ui.spacing_mut().item_spacing.y = 2.0;
+
+        let Some(email) = email else {
+            ui.weak("No message selected");
+            return None;
+        };
+        if content.thread.len() > 1 {
+            return Self::render_thread(ui, email, content, "preview_content");
+        }
         
         // Clean email headers
         ui.vertical(|ui| {
-            ui.weak("alice@example.com");
-            ui.strong("Project Update"); 
-            ui.weak("Jan 15, 2024 at 2:30 PM");
+            ui.weak(&email.sender);
+            ui.strong(&email.subject); 
+            ui.weak(&email.date);
+            Self::render_tracking_report(ui, content.remote_images);
         });
-        
+
+        let draft_action = Self::render_draft_button(ui, email);
+        let mut action = Self::render_attachments(ui, email, content.downloading).or(draft_action);
+
         ui.add_space(8.0);
         
         // Email content
@@ -22,19 +78,33 @@ impl PreviewPanel {
             .auto_shrink([false, false])
             .id_salt("preview_content")
             .show(ui, |ui| {
-                ui.label("Hi team,\n\nJust wanted to update you on the project progress. We've made significant strides in the past week:\n\n1. Completed the authentication module\n2. Implemented the new dashboard design\n3. Fixed critical bugs in the email client\n4. Added responsive layout support\n\nNext week we'll focus on:\n- Performance optimizations\n- Mobile responsiveness\n- User testing\n\nPlease let me know if you have any questions or concerns.\n\nBest regards,\nAlice");
+                let body_action = Self::render_body(ui, email, content);
+                action = action.take().or(body_action);
             });
+        action
     }
 
-    pub fn render_mobile(ui: &mut egui::Ui, _selected_email: usize) {
+    pub fn render_mobile(ui: &mut egui::Ui, email: Option<&Email>, content: &PreviewContent) -> Option<PreviewAction> {
         ui.spacing_mut().item_spacing.y = 1.0;
+
+        let Some(email) = email else {
+            ui.weak("No message selected");
+            return None;
+        };
+        if content.thread.len() > 1 {
+            return Self::render_thread(ui, email, content, "preview_mobile");
+        }
         
         // Minimal mobile header
         ui.vertical(|ui| {
-            ui.weak("alice@example.com");
-            ui.strong("Project Update");
+            ui.weak(&email.sender);
+            ui.strong(&email.subject);
+            Self::render_tracking_report(ui, content.remote_images);
         });
-        
+
+        let draft_action = Self::render_draft_button(ui, email);
+        let mut action = Self::render_attachments(ui, email, content.downloading).or(draft_action);
+
         ui.add_space(6.0);
         
         // Condensed content
@@ -42,7 +112,214 @@ impl PreviewPanel {
             .auto_shrink([false, false])
             .id_salt("preview_mobile")
             .show(ui, |ui| {
-                ui.label("Hi team,\n\nProject update - we've made good progress this week. Key achievements:\n\n• Authentication module done\n• New dashboard design\n• Email client bug fixes\n• Responsive layout\n\nNext: performance & mobile work.\n\nLet me know if questions.\n\nBest,\nAlice");
+                let body_action = Self::render_body(ui, email, content);
+                action = action.take().or(body_action);
+            });
+        action
+    }
+
+    fn render_draft_button(ui: &mut egui::Ui, email: &Email) -> Option<PreviewAction> {
+        if email.flags.draft && ui.button("✏ Edit draft").clicked() {
+            return Some(PreviewAction::EditDraft);
+        }
+        None
+    }
+
+    /// The whole conversation, oldest first: the current message in full,
+    /// the others collapsed to their sender until opened.
+    fn render_thread(ui: &mut egui::Ui, email: &Email, content: &PreviewContent, id_salt: &str) -> Option<PreviewAction> {
+        ui.vertical(|ui| {
+            ui.strong(&email.subject);
+            ui.weak(format!("{} messages", content.thread.len()));
+            Self::render_tracking_report(ui, content.remote_images);
+        });
+        ui.add_space(8.0);
+
+        let mut action = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .id_salt(id_salt)
+            .show(ui, |ui| {
+                for &(message, depth) in content.thread {
+                    ui.horizontal(|ui| {
+                        ui.add_space(depth.min(MAX_THREAD_INDENT) as f32 * 12.0);
+                        ui.vertical(|ui| {
+                            let message_action = if message.id == email.id {
+                                Self::render_thread_current(ui, message, content)
+                            } else {
+                                Self::render_thread_message(ui, message, content)
+                            };
+                            action = action.take().or(message_action);
+                        });
+                    });
+                    ui.add_space(4.0);
+                }
+            });
+        action
+    }
+
+    fn render_thread_current(ui: &mut egui::Ui, email: &Email, content: &PreviewContent) -> Option<PreviewAction> {
+        egui::Frame::group(ui.style())
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.horizontal(|ui| {
+                    ui.strong(&email.sender);
+                    ui.weak(&email.date);
+                });
+                let action = Self::render_attachments(ui, email, content.downloading);
+                ui.add_space(4.0);
+                let body_action = Self::render_body(ui, email, content);
+                action.or(body_action)
+            })
+            .inner
+    }
+
+    fn render_thread_message(ui: &mut egui::Ui, email: &Email, content: &PreviewContent) -> Option<PreviewAction> {
+        let id = ui.make_persistent_id(("thread_message", email.id));
+        let state = egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false);
+        let was_open = state.is_open();
+
+        let mut action = None;
+        state
+            .show_header(ui, |ui| {
+                let sender = egui::RichText::new(&email.sender);
+                let sender = if email.flags.seen { sender } else { sender.strong() };
+                if ui.selectable_label(false, sender).on_hover_text("Show this message").clicked() {
+                    action = Some(PreviewAction::SelectEmail(email.id));
+                }
+                ui.weak(&email.date);
+            })
+            .body(|ui| {
+                if email.body_loaded {
+                    ui.label(&email.body);
+                } else {
+                    Self::render_body_placeholder(ui, content.bodies_loading.contains(&email.id));
+                }
+            });
+
+        let opened = !was_open && egui::collapsing_header::CollapsingState::load(ui.ctx(), id).is_some_and(|state| state.is_open());
+        if opened && !email.body_loaded {
+            action = action.or(Some(PreviewAction::LoadBody(email.id)));
+        }
+        action
+    }
+
+    fn render_tracking_report(ui: &mut egui::Ui, remote_images: &[RemoteImage]) {
+        let trackers: Vec<&RemoteImage> = remote_images.iter().filter(|image| image.tracking_pixel).collect();
+        if trackers.is_empty() {
+            return;
+        }
+        let label = match trackers.len() {
+            1 => "🛡 1 tracking pixel blocked".to_string(),
+            n => format!("🛡 {} tracking pixels blocked", n),
+        };
+        let mut hosts: Vec<&str> = trackers.iter().map(|image| image.host()).collect();
+        hosts.sort_unstable();
+        hosts.dedup();
+        ui.weak(label)
+            .on_hover_text(format!("Images that report when the message is opened, from {}", hosts.join(", ")));
+    }
+
+    /// Offers to load remote images while they are blocked.
+    fn render_remote_banner(ui: &mut egui::Ui, email: &Email, content: &PreviewContent) -> Option<PreviewAction> {
+        let blocked = content.remote_images.iter().filter(|image| !image.tracking_pixel).count();
+        if blocked == 0 || content.images.load_remote {
+            return None;
+        }
+
+        let mut action = None;
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                let images = if blocked == 1 { "1 remote image".to_string() } else { format!("{} remote images", blocked) };
+                ui.label(format!("🛡 {} blocked to protect your privacy", images));
+                if ui.small_button("Load once").clicked() {
+                    action = Some(PreviewAction::LoadRemoteImagesOnce);
+                }
+                if let Some(address) = email.sender_address() {
+                    if ui.small_button(format!("Always for {}", address)).clicked() {
+                        action = Some(PreviewAction::AllowRemoteImages(address.clone()));
+                    }
+                    if let Some((_, domain)) = address.rsplit_once('@')
+                        && ui.small_button(format!("Always for {}", domain)).clicked() {
+                        action = Some(PreviewAction::AllowRemoteImages(domain.to_string()));
+                    }
+                }
+            });
+        });
+        ui.add_space(4.0);
+        action
+    }
+
+    fn render_attachments(ui: &mut egui::Ui, email: &Email, downloading: &HashSet<String>) -> Option<PreviewAction> {
+        if email.attachments.is_empty() {
+            return None;
+        }
+
+        let mut action = None;
+        ui.add_space(4.0);
+        for attachment in &email.attachments {
+            ui.horizontal(|ui| {
+                ui.label(format!("📎 {}", attachment.filename))
+                    .on_hover_text(&attachment.mime_type);
+                ui.weak(attachment.size_description());
+                if downloading.contains(&attachment.part_id) {
+                    ui.spinner();
+                    return;
+                }
+                if ui.small_button("Save").on_hover_text("Save to the download folder").clicked() {
+                    action = Some(PreviewAction::SaveAttachment(attachment.clone()));
+                }
+                if ui.small_button("Open").on_hover_text("Open with the default application").clicked() {
+                    action = Some(PreviewAction::OpenAttachment(attachment.clone()));
+                }
+            });
+        }
+        action
+    }
+
+    fn render_body(ui: &mut egui::Ui, email: &Email, content: &PreviewContent) -> Option<PreviewAction> {
+        if !email.body_loaded {
+            Self::render_body_placeholder(ui, content.loading);
+            return None;
+        }
+
+        let Some(html) = content.html else {
+            ui.label(&email.body);
+            return None;
+        };
+
+        let mut action = None;
+        ui.horizontal(|ui| {
+            if ui.selectable_label(!content.plain_text, "HTML").clicked() {
+                action = Some(PreviewAction::ShowPlainText(false));
+            }
+            if ui.selectable_label(content.plain_text, "Plain text").clicked() {
+                action = Some(PreviewAction::ShowPlainText(true));
+            }
+        });
+        ui.add_space(4.0);
+
+        if content.plain_text {
+            ui.label(&email.body);
+        } else {
+            let banner_action = Self::render_remote_banner(ui, email, content);
+            action = action.or(banner_action);
+            let missing = HtmlView::render(ui, html, &content.images);
+            if action.is_none() && !missing.is_empty() {
+                action = Some(PreviewAction::LoadImages(missing));
+            }
+        }
+        action
+    }
+
+    fn render_body_placeholder(ui: &mut egui::Ui, loading: bool) {
+        if loading {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.weak("Loading message...");
             });
+        } else {
+            ui.weak("Message body not loaded");
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,15 +1,26 @@
 use eframe::egui;
+use crate::backend::{OutboxEntry, OutboxState, unix_now};
 use crate::ui::{LayoutMode, ResponsiveLayout};
 
+/// Requested from the status bar.
+#[derive(Debug, Clone)]
+pub enum StatusAction {
+    /// Take a message still in its undo window back into the composer
+    UndoSend(String), // outbox id
+}
+
 pub struct StatusPanel;
 
 impl StatusPanel {
-    pub fn render(ui: &mut egui::Ui, layout_mode: LayoutMode, status_message: &str) {
+    pub fn render(ui: &mut egui::Ui, layout_mode: LayoutMode, status_message: &str, outbox: &[OutboxEntry]) -> Option<StatusAction> {
+        let mut action = None;
         ui.horizontal(|ui| {
             ui.spacing_mut().item_spacing.x = ResponsiveLayout::PANEL_SPACING * 3.0;
             
             // Status message
             ui.colored_label(ui.visuals().weak_text_color(), status_message);
+            Self::render_outbox(ui, outbox);
+            action = Self::render_undo(ui, outbox);
             
             // Essential shortcuts only
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -25,17 +36,57 @@ impl StatusPanel {
                 }
             });
         });
+        action
     }
     
-    pub fn render_minimal(ui: &mut egui::Ui, status_message: &str) {
+    pub fn render_minimal(ui: &mut egui::Ui, status_message: &str, outbox: &[OutboxEntry]) -> Option<StatusAction> {
         // Ultra-minimal status for mobile/compact modes
+        let mut action = None;
         ui.horizontal(|ui| {
             ui.spacing_mut().item_spacing.x = ResponsiveLayout::PANEL_SPACING;
             ui.colored_label(ui.visuals().weak_text_color(), status_message);
+            Self::render_outbox(ui, outbox);
+            action = Self::render_undo(ui, outbox);
             
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 ui.weak("Ctrl+H Help");
             });
         });
+        action
+    }
+
+    /// A countdown with an Undo button for the latest message that can
+    /// still be taken back.
+    fn render_undo(ui: &mut egui::Ui, outbox: &[OutboxEntry]) -> Option<StatusAction> {
+        let now = unix_now();
+        let (entry, remaining) = outbox
+            .iter()
+            .rev()
+            .find_map(|entry| entry.undo_remaining(now).map(|remaining| (entry, remaining)))?;
+        ui.label(format!("Sending \"{}\" in {} s", entry.subject, remaining));
+        ui.button("Undo").clicked().then(|| StatusAction::UndoSend(entry.id.clone()))
+    }
+
+    /// How many messages wait in the outbox, by state
+    fn render_outbox(ui: &mut egui::Ui, outbox: &[OutboxEntry]) {
+        let count = |state| outbox.iter().filter(|entry| entry.state == state).count();
+        let parts: Vec<String> = [
+            (count(OutboxState::Sending), "sending"),
+            (count(OutboxState::Queued), "queued"),
+            (count(OutboxState::Failed), "failed"),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, state)| format!("{} {}", count, state))
+        .collect();
+        if parts.is_empty() {
+            return;
+        }
+        let text = format!("📤 {}", parts.join(" · "));
+        if count(OutboxState::Failed) > 0 {
+            ui.colored_label(ui.visuals().warn_fg_color, text);
+        } else {
+            ui.weak(text);
+        }
     }
-}
\ No newline at end of file
+}
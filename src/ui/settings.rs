@@ -1,19 +1,35 @@
 use eframe::egui;
-
-#[derive(Debug, Clone)]
+use crate::backend::error_log;
+use crate::backend::mail_store::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SettingsWindow {
+    #[serde(skip)]
     pub visible: bool,
     pub vim_mode: bool,
     pub theme: AppTheme,
     pub show_status_bar: bool,
     pub show_folder_icons: bool,
+    pub subscribed_only: bool,
     pub auto_refresh: bool,
     pub refresh_interval: u32, // minutes
     pub confirm_delete: bool,
     pub compact_layout: bool,
+    /// Group the message list into conversations
+    pub conversation_view: bool,
+    /// How long a sent message can still be taken back, 0 to send at once
+    pub undo_send_delay: u32, // seconds
+    /// Sender addresses and domains whose messages may load remote images
+    pub remote_image_senders: BTreeSet<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AppTheme {
     Light,
     Dark,
@@ -47,10 +63,14 @@ impl SettingsWindow {
             theme: AppTheme::Auto,
             show_status_bar: true,
             show_folder_icons: true,
-            auto_refresh: false,
+            subscribed_only: false,
+            auto_refresh: true,
             refresh_interval: 5,
             confirm_delete: true,
             compact_layout: false,
+            conversation_view: false,
+            undo_send_delay: 10,
+            remote_image_senders: BTreeSet::new(),
         }
     }
 
@@ -118,7 +138,9 @@ impl SettingsWindow {
 
                     ui.checkbox(&mut self.show_status_bar, "Show status bar");
                     ui.checkbox(&mut self.show_folder_icons, "Show folder icons");
+                    ui.checkbox(&mut self.subscribed_only, "Show subscribed folders only");
                     ui.checkbox(&mut self.compact_layout, "Use compact layout");
+                    ui.checkbox(&mut self.conversation_view, "Group messages into conversations");
 
                     ui.add_space(15.0);
 
@@ -134,11 +156,26 @@ impl SettingsWindow {
                             egui::Slider::new(&mut self.refresh_interval, 1..=60)
                                 .text("minutes")
                                 .suffix(" min")
-                        );
+                        )
+                        .on_hover_text("How often to check servers that cannot push new mail");
                     });
 
                     ui.checkbox(&mut self.confirm_delete, "Confirm before deleting emails");
 
+                    ui.horizontal(|ui| {
+                        ui.label("Undo send:");
+                        ui.add(egui::Slider::new(&mut self.undo_send_delay, 0..=60).suffix(" s"))
+                            .on_hover_text("How long a sent message waits in the outbox and can be taken back; 0 sends right away");
+                    });
+
+                    ui.add_space(15.0);
+
+                    // Privacy Settings
+                    ui.heading("Privacy");
+                    ui.separator();
+
+                    self.render_remote_image_senders(ui);
+
                     ui.add_space(15.0);
 
                     // Keyboard Shortcuts Info
@@ -191,21 +228,83 @@ impl SettingsWindow {
         action
     }
 
+    fn render_remote_image_senders(&mut self, ui: &mut egui::Ui) {
+        ui.label("Remote images are blocked unless the sender is listed here:");
+        if self.remote_image_senders.is_empty() {
+            ui.weak("No senders or domains allowed");
+            return;
+        }
+
+        let mut removed = None;
+        for sender in &self.remote_image_senders {
+            ui.horizontal(|ui| {
+                if ui.small_button("✕").on_hover_text("Block again").clicked() {
+                    removed = Some(sender.clone());
+                }
+                ui.label(sender);
+            });
+        }
+        if let Some(sender) = removed {
+            self.remote_image_senders.remove(&sender);
+        }
+    }
+
     pub fn reset_to_defaults(&mut self) {
         *self = Self::new();
         self.visible = true; // Keep settings window open
     }
 
-    #[allow(dead_code)] // Will be used for settings persistence
+    /// `$XDG_CONFIG_HOME/mailcross/settings.json`
+    fn config_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("mailcross").join("settings.json"))
+    }
+
+    /// Replaces the settings with the saved ones, if any. Settings missing
+    /// from the file keep their defaults.
     pub fn load_from_config(&mut self) {
-        // TODO: Load settings from config file
-        // For now, use defaults
+        let Some(path) = Self::config_path() else {
+            return;
+        };
+        match fs::read(&path) {
+            Ok(bytes) => match serde_json::from_slice::<Self>(&bytes) {
+                Ok(settings) => *self = Self { visible: self.visible, ..settings },
+                Err(e) => error_log::report(format!("Ignoring unreadable settings at {}: {}", path.display(), e)),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => error_log::report(format!("Failed to read settings at {}: {}", path.display(), e)),
+        }
     }
 
-    #[allow(dead_code)] // Will be used for settings persistence
     pub fn save_to_config(&self) {
-        // TODO: Save settings to config file
-        // For now, this is a no-op
+        let Some(path) = Self::config_path() else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(self)
+            .map_err(io::Error::other)
+            .and_then(|bytes| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                // A crash mid-write must not cost the saved settings
+                write_atomic(&path, &bytes)
+            });
+        if let Err(e) = result {
+            error_log::report(format!("Failed to save settings to {}: {}", path.display(), e));
+        }
+    }
+
+    /// Whether messages from `address` may load remote images, by address
+    /// or by domain.
+    pub fn allows_remote_images(&self, address: &str) -> bool {
+        let address = address.to_lowercase();
+        let domain = address.rsplit_once('@').map(|(_, domain)| domain);
+        self.remote_image_senders.contains(&address)
+            || domain.is_some_and(|domain| self.remote_image_senders.contains(domain))
+    }
+
+    /// Allows remote images for a sender address or a whole domain.
+    pub fn allow_remote_images(&mut self, sender: &str) {
+        self.remote_image_senders.insert(sender.to_lowercase());
     }
 
     // Getters for settings values
@@ -228,16 +327,18 @@ impl SettingsWindow {
         self.show_folder_icons
     }
 
-    #[allow(dead_code)] // Will be used for email refresh
     pub fn get_auto_refresh(&self) -> bool {
         self.auto_refresh
     }
 
-    #[allow(dead_code)] // Will be used for refresh timing
     pub fn get_refresh_interval(&self) -> u32 {
         self.refresh_interval
     }
 
+    pub fn get_undo_send_delay(&self) -> u32 {
+        self.undo_send_delay
+    }
+
     #[allow(dead_code)] // Will be used for delete behavior
     pub fn get_confirm_delete(&self) -> bool {
         self.confirm_delete